@@ -62,7 +62,7 @@ fn create_test_dataset(size: usize) -> (OrderAoS, OrderSoA) {
             shipping_address_hash: (i as u64).wrapping_mul(31),
         };
 
-        aos_data.push(order.clone());
+        aos_data.push(order);
         soa_data.push(order);
     }
 
@@ -146,6 +146,17 @@ fn benchmark_aggregation_comparison(c: &mut Criterion) {
             &size,
             |b, _| b.iter(|| black_box(soa_data.revenue_by_payment_method_memory_optimized())),
         );
+
+        // AoSoA tiles: columnar within a tile, one contiguous block per 8 rows
+        #[cfg(target_arch = "x86_64")]
+        {
+            use example_app::optimizations::simd_tiled_revenue_analysis;
+
+            let tiled_data = OrderAoSoA::from(&soa_data);
+            group.bench_with_input(BenchmarkId::new("aosoa_simd", size), &size, |b, _| {
+                b.iter(|| black_box(simd_tiled_revenue_analysis(black_box(&tiled_data))))
+            });
+        }
     }
 
     group.finish();
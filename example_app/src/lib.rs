@@ -1,35 +1,43 @@
-use soa_macros::{SoA, SoAStore};
+use soa_macros::{SoA, SoAArrow, SoAEnum, SoAStore};
 use std::collections::HashMap;
 
 pub mod optimizations;
 pub mod persistence;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, SoAEnum, SoAArrow)]
 pub enum OrderStatus {
+    #[default]
     Pending,
     Processing,
     Shipped,
     Delivered,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, SoAEnum, SoAArrow)]
 pub enum PaymentMethod {
+    #[default]
     CreditCard,
     PayPal,
     BankTransfer,
 }
 
-#[derive(Debug, Clone, Copy, SoA, SoAStore)]
+#[derive(Debug, Clone, Copy, SoA, SoAStore, SoAArrow)]
 #[soa_store(key = "order_id", shards = 16)]
+#[soa(layout = "aosoa", lanes = 8, copy)]
 pub struct Order {
     pub order_id: u64,
+    #[soa(group = "hot", index = "hash")]
     pub customer_id: u64,
     pub product_id: u64,
     pub quantity: u32,
     pub unit_price: f64,
+    #[soa(group = "hot")]
     pub total_amount: f64,
+    #[soa(group = "hot", index = "bitmap")]
     pub status: OrderStatus,
+    #[soa(group = "hot", index = "bitmap")]
     pub payment_method: PaymentMethod,
+    #[soa(index = "btree")]
     pub order_timestamp: u64,
     pub shipping_address_hash: u64,
 }
@@ -128,7 +136,8 @@ impl OrderSoA {
     }
 
     /// Memory-optimized layout that interleaves frequently accessed fields
-    /// Demonstrates how to reorganize data for optimal cache usage
+    /// Demonstrates how to reorganize data for optimal cache usage; a struct can keep
+    /// such fields interleaved permanently with `#[soa(pack(...))]`
     pub fn revenue_by_payment_method_memory_optimized(&self) -> HashMap<PaymentMethod, f64> {
         // Create a temporary structure that packs the fields we need together
         // This simulates what an optimized memory layout would look like
@@ -205,3 +214,965 @@ impl Default for OrderAoS {
 
 // Re-export the persistence types for convenience
 pub use persistence::PersistentOrderStore;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, SoA)]
+    pub struct Shipment {
+        pub shipment_id: u64,
+        pub discount: Option<f64>,
+        pub shipped_at: Option<u64>,
+    }
+
+    #[test]
+    fn test_nullable_columns() {
+        let mut soa = ShipmentSoA::new();
+        soa.push(Shipment {
+            shipment_id: 1,
+            discount: Some(0.1),
+            shipped_at: None,
+        });
+        soa.push(Shipment {
+            shipment_id: 2,
+            discount: None,
+            shipped_at: Some(1_700_000_000),
+        });
+
+        let first = soa.view(0);
+        assert_eq!(first.discount, Some(&0.1));
+        assert_eq!(first.shipped_at, None);
+
+        // Nulls keep a default placeholder in the dense value column
+        assert_eq!(soa.discount_raw_array(), &[0.1, 0.0]);
+        assert_eq!(
+            soa.shipped_at_validity().iter().collect::<Vec<_>>(),
+            vec![false, true]
+        );
+
+        if let Some(shipped_at) = soa.view_mut(1).shipped_at {
+            *shipped_at += 60;
+        }
+        soa.set_discount(1, Some(0.25));
+        soa.set_shipped_at(0, Some(1_600_000_000));
+
+        assert_eq!(soa.view(1).shipped_at, Some(&1_700_000_060));
+        assert_eq!(soa.view(1).discount, Some(&0.25));
+        assert_eq!(soa.view(0).shipped_at, Some(&1_600_000_000));
+    }
+
+    #[derive(Debug, Clone, SoA)]
+    pub struct LineItem {
+        pub sku: String,
+        pub quantity: u32,
+        pub thumbnail: Vec<u8>,
+    }
+
+    #[test]
+    fn test_variable_length_columns() {
+        let mut soa = LineItemSoA::new();
+        soa.push(LineItem {
+            sku: "SKU-1".to_string(),
+            quantity: 2,
+            thumbnail: vec![1, 2, 3],
+        });
+        soa.push(LineItem {
+            sku: "SKU-22".to_string(),
+            quantity: 1,
+            thumbnail: Vec::new(),
+        });
+
+        assert_eq!(soa.view(1).sku, "SKU-22");
+        assert_eq!(soa.view(0).thumbnail, &[1, 2, 3]);
+
+        // Values are contiguous, delimited by Arrow-style offsets
+        assert_eq!(soa.sku_column().values(), b"SKU-1SKU-22");
+        assert_eq!(soa.sku_column().offsets(), &[0, 5, 11]);
+
+        *soa.view_mut(0).quantity += 1;
+        soa.set_sku(0, "SKU-100");
+        assert_eq!(
+            soa.sku_column().iter().collect::<Vec<_>>(),
+            vec!["SKU-100", "SKU-22"]
+        );
+        assert_eq!(soa.sku_column().offsets(), &[0, 7, 13]);
+        assert_eq!(*soa.view(0).quantity, 3);
+    }
+
+    fn order_ids(soa: &OrderSoA) -> Vec<u64> {
+        soa.order_id_raw_array().to_vec()
+    }
+
+    #[test]
+    fn test_row_removal() {
+        let mut soa = OrderSoA::new();
+        for id in 0..6 {
+            soa.push(Order::new(id, 100 + id, 1, 1, 10.0 * id as f64));
+        }
+
+        let removed = soa.swap_remove(1);
+        assert_eq!(removed.order_id, 1);
+        assert_eq!(order_ids(&soa), vec![0, 5, 2, 3, 4]);
+
+        let removed = soa.remove(0);
+        assert_eq!(removed.customer_id, 100);
+        assert_eq!(order_ids(&soa), vec![5, 2, 3, 4]);
+
+        soa.retain(|order| *order.order_id % 2 == 0);
+        assert_eq!(order_ids(&soa), vec![2, 4]);
+        assert_eq!(soa.customer_id_raw_array(), &[102, 104]);
+
+        soa.push(Order::new(7, 107, 1, 1, 70.0));
+        let drained: Vec<u64> = soa.drain(1..).map(|order| order.order_id).collect();
+        assert_eq!(drained, vec![4, 7]);
+        assert_eq!(order_ids(&soa), vec![2]);
+
+        soa.truncate(5);
+        assert_eq!(soa.len(), 1);
+        soa.clear();
+        assert!(soa.is_empty());
+    }
+
+    #[test]
+    fn test_row_removal_keeps_varlen_and_nullable_columns_aligned() {
+        let mut items = LineItemSoA::new();
+        for (sku, quantity) in [("a", 1), ("bb", 2), ("ccc", 3), ("dddd", 4)] {
+            items.push(LineItem {
+                sku: sku.to_string(),
+                quantity,
+                thumbnail: vec![quantity as u8; quantity as usize],
+            });
+        }
+
+        assert_eq!(items.swap_remove(0).sku, "a");
+        assert_eq!(items.remove(1).thumbnail, vec![2, 2]);
+        assert_eq!(
+            items.sku_column().iter().collect::<Vec<_>>(),
+            vec!["dddd", "ccc"]
+        );
+        assert_eq!(items.sku_column().offsets(), &[0, 4, 7]);
+        assert_eq!(items.view(1).thumbnail, &[3, 3, 3]);
+
+        let mut shipments = ShipmentSoA::new();
+        for id in 0..4 {
+            shipments.push(Shipment {
+                shipment_id: id,
+                discount: (id % 2 == 1).then_some(id as f64),
+                shipped_at: None,
+            });
+        }
+        shipments.retain(|s| *s.shipment_id != 1);
+        assert_eq!(shipments.view(2).discount, Some(&3.0));
+        assert_eq!(
+            shipments.discount_validity().iter().collect::<Vec<_>>(),
+            vec![false, false, true]
+        );
+
+        let drained: Vec<_> = shipments.drain(..2).map(|s| s.shipment_id).collect();
+        assert_eq!(drained, vec![0, 2]);
+        assert_eq!(shipments.view(0).discount, Some(&3.0));
+
+        // `append` moves every row of the other collection, offsets rebased
+        let mut more = LineItemSoA::new();
+        more.push(LineItem {
+            sku: "ee".to_string(),
+            quantity: 5,
+            thumbnail: vec![5],
+        });
+        items.append(&mut more);
+        assert!(more.is_empty());
+        assert_eq!(items.sku_column().offsets(), &[0, 4, 7, 9]);
+        assert_eq!(
+            (items.view(2).sku, items.view(2).thumbnail),
+            ("ee", &[5][..])
+        );
+
+        let mut more: ShipmentSoA = drained
+            .into_iter()
+            .map(|id| Shipment {
+                shipment_id: id,
+                discount: None,
+                shipped_at: Some(id),
+            })
+            .collect();
+        shipments.append(&mut more);
+        assert!(more.is_empty());
+        assert_eq!(shipments.len(), 3);
+        assert_eq!(
+            shipments.discount_validity().iter().collect::<Vec<_>>(),
+            vec![true, false, false]
+        );
+        assert_eq!(shipments.view(2).shipped_at, Some(&2));
+    }
+
+    #[test]
+    fn test_remove_by_key() {
+        let mut store = OrderStore::new();
+        for id in 0..3 {
+            store.add(Order::new(id, 100 + id, 1, 1, 10.0)).unwrap();
+        }
+        assert_eq!(store.remove_by_key(&1).map(|o| o.customer_id), Some(101));
+        assert!(store.remove_by_key(&1).is_none());
+        assert_eq!(order_ids(store.kernel()), vec![0, 2]);
+
+        let mut sharded = OrderShardedStore::with_shards(4, 8);
+        for id in 0..20 {
+            sharded.add(Order::new(id, 100 + id, 1, 1, 10.0));
+        }
+        assert_eq!(sharded.remove_by_key(&7).map(|o| o.order_id), Some(7));
+        assert!(sharded.remove_by_key(&7).is_none());
+        let remaining: usize = (0..sharded.shard_count())
+            .map(|i| sharded.shard(i).len())
+            .sum();
+        assert_eq!(remaining, 19);
+
+        // The shard key index follows rows moved by earlier removals and later adds
+        sharded.add(Order::new(20, 120, 1, 1, 10.0));
+        for id in (0..21).filter(|&id| id != 7) {
+            assert_eq!(
+                sharded.remove_by_key(&id).map(|o| o.customer_id),
+                Some(100 + id)
+            );
+        }
+        assert!((0..sharded.shard_count()).all(|i| sharded.shard(i).is_empty()));
+
+        // Writes through `shard_mut` are picked up, and shard keys need not be unique
+        let mut by_station = ReadingByStationShardedStore::with_shards(2, 0);
+        for at in 0..6 {
+            by_station.add(ReadingByStation { at, station: 3 });
+        }
+        assert!(by_station.remove_by_key(&3).is_some());
+        let si = by_station.shard_for_key(&3);
+        *by_station.shard_mut(si).view_mut(0).station = 4;
+        assert_eq!(by_station.shard(si).len(), 5);
+        let mut removed = 0;
+        while by_station.remove_by_key(&3).is_some() {
+            removed += 1;
+        }
+        assert_eq!(removed, 4);
+        assert_eq!(by_station.shard(si).station_raw_array(), &[4]);
+
+        let concurrent = ReadingByStationConcurrentStore::from(by_station);
+        for at in 0..3 {
+            concurrent.add(ReadingByStation { at, station: 3 });
+        }
+        assert!(concurrent.remove_by_key(&4).is_some());
+        let ats: Vec<u64> = (0..3)
+            .filter_map(|_| concurrent.remove_by_key(&3).map(|r| r.at))
+            .collect();
+        assert_eq!(ats.len(), 3);
+        assert!(concurrent.is_empty());
+    }
+
+    #[test]
+    fn test_key_index() {
+        let mut store = OrderStore::new();
+        for id in 0..4 {
+            assert_eq!(
+                store.add(Order::new(id, 100 + id, 1, 1, 10.0)).unwrap(),
+                id as usize
+            );
+        }
+        let dup = store.add(Order::new(2, 999, 1, 1, 1.0)).unwrap_err();
+        assert_eq!((dup.key, dup.row), (2, 2));
+        assert_eq!(store.kernel().len(), 4);
+
+        assert!(store.contains_key(&3));
+        assert_eq!(store.get_by_key(&1).map(|o| *o.customer_id), Some(101));
+        assert!(store.get_by_key(&9).is_none());
+
+        // Removing row 0 moves the last row (key 3) into its slot
+        store.remove_by_key(&0).unwrap();
+        assert_eq!(store.get_by_key(&3).map(|o| *o.customer_id), Some(103));
+
+        assert_eq!(store.update_by_key(&1, |o| *o.quantity = 7), Ok(Some(())));
+        assert_eq!(store.get_by_key(&1).map(|o| *o.quantity), Some(7));
+        store.update_by_key(&1, |o| *o.order_id = 10).unwrap();
+        assert!(!store.contains_key(&1));
+        assert_eq!(store.get_by_key(&10).map(|o| *o.customer_id), Some(101));
+        assert_eq!(store.update_by_key(&1, |_| ()), Ok(None));
+
+        // Moving a row onto a key in use undoes every change, indexes included
+        assert_eq!(store.find_by_customer_id(&101).count(), 1);
+        let dup = store
+            .update_by_key(&10, |o| {
+                *o.order_id = 2;
+                *o.customer_id = 555;
+                *o.status = OrderStatus::Shipped;
+                *o.quantity = 9;
+            })
+            .unwrap_err();
+        assert_eq!((dup.key, dup.row), (2, 2));
+        let row = store.get_by_key(&10).unwrap();
+        assert_eq!((*row.customer_id, *row.quantity), (101, 7));
+        assert_eq!(*row.status, OrderStatus::Pending);
+        assert_eq!(store.get_by_key(&2).map(|o| *o.customer_id), Some(102));
+        assert!(store.find_by_customer_id(&555).next().is_none());
+        assert_eq!(store.find_by_customer_id(&101).count(), 1);
+        assert!(store.find_by_status(&OrderStatus::Shipped).next().is_none());
+
+        // A clone shares the built key index until one side writes
+        let snapshot = store.clone();
+        store.add(Order::new(7, 107, 1, 1, 1.0)).unwrap();
+        store.remove_by_key(&10).unwrap();
+        assert!(snapshot.contains_key(&10) && !snapshot.contains_key(&7));
+        assert!(store.contains_key(&7) && !store.contains_key(&10));
+
+        // Direct writes through the kernel invalidate the index
+        *store.kernel_mut().view_mut(0).order_id = 42;
+        assert!(store.contains_key(&42));
+        assert!(!store.contains_key(&3));
+    }
+
+    #[test]
+    fn test_secondary_indexes() {
+        fn ids<'a>(rows: impl Iterator<Item = OrderView<'a>>) -> Vec<u64> {
+            let mut ids: Vec<u64> = rows.map(|o| *o.order_id).collect();
+            ids.sort_unstable();
+            ids
+        }
+
+        let mut store = OrderStore::new();
+        for id in 0..6 {
+            let mut order = Order::new(id, 100 + id % 3, 1, 1, 10.0);
+            order.order_timestamp = 1000 + id * 10;
+            store.add(order).unwrap();
+        }
+        assert_eq!(ids(store.find_by_customer_id(&100)), vec![0, 3]);
+        assert_eq!(ids(store.find_by_status(&OrderStatus::Pending)).len(), 6);
+        let in_range: Vec<u64> = store
+            .range_by_order_timestamp(1010..1040)
+            .map(|o| *o.order_id)
+            .collect();
+        assert_eq!(in_range, vec![1, 2, 3]);
+
+        // Added after the indexes were built
+        store.add(Order::new(6, 100, 1, 1, 10.0)).unwrap();
+        assert_eq!(ids(store.find_by_customer_id(&100)), vec![0, 3, 6]);
+
+        store
+            .update_by_key(&3, |o| {
+                *o.customer_id = 101;
+                *o.status = OrderStatus::Shipped;
+            })
+            .unwrap();
+        assert_eq!(ids(store.find_by_customer_id(&100)), vec![0, 6]);
+        assert_eq!(ids(store.find_by_customer_id(&101)), vec![1, 3, 4]);
+        assert_eq!(ids(store.find_by_status(&OrderStatus::Shipped)), vec![3]);
+
+        // Removing row 0 moves the last row (order 6) into its slot
+        store.remove_by_key(&0).unwrap();
+        assert_eq!(ids(store.find_by_customer_id(&100)), vec![6]);
+        assert_eq!(
+            store
+                .find_by_customer_id(&100)
+                .next()
+                .map(|o| *o.customer_id),
+            Some(100)
+        );
+        assert!(store.range_by_order_timestamp(..1010).next().is_none());
+        store.remove_by_key(&3).unwrap();
+        assert!(store.find_by_status(&OrderStatus::Shipped).next().is_none());
+
+        *store.kernel_mut().view_mut(0).order_timestamp = 5;
+        assert_eq!(ids(store.range_by_order_timestamp(..1000)), vec![6]);
+
+        // A clone shares the built indexes until one side writes
+        let snapshot = store.clone();
+        store.add(Order::new(7, 100, 1, 1, 10.0)).unwrap();
+        store.remove_by_key(&6).unwrap();
+        assert_eq!(ids(store.find_by_customer_id(&100)), vec![7]);
+        assert_eq!(ids(snapshot.find_by_customer_id(&100)), vec![6]);
+        assert!(!snapshot.contains_key(&7));
+        assert_eq!(ids(snapshot.range_by_order_timestamp(..1000)), vec![6]);
+    }
+
+    #[test]
+    fn test_bitmap_indexes() {
+        let statuses = [
+            OrderStatus::Pending,
+            OrderStatus::Shipped,
+            OrderStatus::Delivered,
+        ];
+        let mut store = OrderStore::new();
+        // Enough rows for several chunks, with both sparse and dense ones
+        for id in 0..150_000u64 {
+            let payment = if id % 2 == 0 {
+                PaymentMethod::PayPal
+            } else {
+                PaymentMethod::CreditCard
+            };
+            let order = Order::new_with_payment(id, id % 7, 1, 1, 1.0, payment)
+                .with_status(statuses[id as usize % 3]);
+            store.add(order).unwrap();
+        }
+
+        let delivered = store.status_bitmap(&OrderStatus::Delivered);
+        let paypal = store.payment_method_bitmap(&PaymentMethod::PayPal);
+        assert_eq!(delivered.len(), 50_000);
+        assert!(store.status_bitmap(&OrderStatus::Processing).is_empty());
+
+        let both = delivered & paypal;
+        assert_eq!(both.len(), 25_000);
+        assert!(both.iter().all(|i| i % 6 == 2));
+        let either = delivered | paypal;
+        assert_eq!(either.len(), 100_000);
+        assert!(either.contains(5) && !either.contains(3));
+        assert!(store
+            .select(&both)
+            .all(|o| *o.status == OrderStatus::Delivered
+                && *o.payment_method == PaymentMethod::PayPal));
+
+        store
+            .update_by_key(&2, |o| *o.status = OrderStatus::Processing)
+            .unwrap();
+        assert_eq!(
+            store
+                .status_bitmap(&OrderStatus::Processing)
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(store.status_bitmap(&OrderStatus::Delivered).len(), 49_999);
+
+        // Removing row 2 moves the last row (order 149_999, Delivered) into its slot
+        store.remove_by_key(&2).unwrap();
+        let delivered = store.status_bitmap(&OrderStatus::Delivered);
+        assert!(store.status_bitmap(&OrderStatus::Processing).is_empty());
+        assert!(delivered.contains(2) && !delivered.contains(149_999));
+        assert_eq!(
+            store.find_by_status(&OrderStatus::Delivered).count(),
+            delivered.len()
+        );
+
+        let mut set: soa_runtime::RowSet = (0..5000).collect();
+        for row in 0..1000 {
+            set.remove(row);
+        }
+        assert_eq!(set, (1000..5000).collect());
+    }
+
+    #[test]
+    fn test_concurrent_store() {
+        let store = OrderConcurrentStore::with_shards(4, 64);
+        std::thread::scope(|scope| {
+            for worker in 0..4u64 {
+                let store = &store;
+                scope.spawn(move || {
+                    for id in (worker * 100)..(worker * 100 + 100) {
+                        store.add(Order::new(id, id % 10, 1, 1, 2.0));
+                    }
+                });
+            }
+        });
+        assert_eq!(store.len(), 400);
+
+        // Same key -> shard mapping as the single-threaded store
+        let mut sharded = OrderShardedStore::with_shards(4, 64);
+        for id in 0..400 {
+            let (si, _) = sharded.add(Order::new(id, id % 10, 1, 1, 2.0));
+            assert!(store.with_shard(si, |soa| soa.iter().any(|o| *o.order_id == id)));
+        }
+
+        let snapshot = store.snapshot();
+        assert_eq!(store.remove_by_key(&42).map(|o| o.order_id), Some(42));
+        assert!(store.remove_by_key(&42).is_none());
+        assert_eq!(store.len(), 399);
+        let snapshot_len: usize = (0..snapshot.shard_count())
+            .map(|i| snapshot.shard(i).len())
+            .sum();
+        assert_eq!(snapshot_len, 400);
+
+        let doubled: usize = (0..store.shard_count())
+            .map(|i| {
+                store.with_shard_mut(i, |soa| {
+                    soa.for_each_mut(|o| *o.total_amount *= 2.0);
+                    soa.len()
+                })
+            })
+            .sum();
+        assert_eq!(doubled, 399);
+        let sharded = store.into_sharded();
+        assert!((0..sharded.shard_count())
+            .flat_map(|i| sharded.shard(i).total_amount_raw_array().to_vec())
+            .all(|amount| amount == 4.0));
+    }
+
+    #[test]
+    fn test_aos_interop() {
+        let orders: Vec<Order> = (0..4)
+            .map(|id| Order::new(id, 100 + id, 1, 2, 5.0))
+            .collect();
+
+        let mut soa: OrderSoA = orders.iter().copied().collect();
+        soa.extend_from_slice(&orders[..1]);
+        soa.extend(std::iter::once(Order::new(9, 109, 1, 1, 1.0)));
+        assert_eq!(order_ids(&soa), vec![0, 1, 2, 3, 0, 9]);
+
+        assert_eq!(soa.get_owned(1).customer_id, 101);
+        let aos = soa.to_aos();
+        assert_eq!(aos.len(), soa.len());
+        assert_eq!(aos[5].order_id, 9);
+
+        let owned: Vec<u64> = soa.clone().into_iter().rev().map(|o| o.order_id).collect();
+        assert_eq!(owned, vec![9, 0, 3, 2, 1, 0]);
+
+        let back = OrderSoA::from_aos(soa.into_aos());
+        assert_eq!(order_ids(&back), vec![0, 1, 2, 3, 0, 9]);
+    }
+
+    #[test]
+    fn test_stable_shard_routing() {
+        // Pinned: changing these moves existing keys to other shards
+        let sharded = OrderShardedStore::with_shards(16, 0);
+        let shards: Vec<usize> = (0..8).map(|id| sharded.shard_for_key(&id)).collect();
+        assert_eq!(shards, vec![11, 3, 7, 5, 13, 10, 15, 10]);
+
+        let concurrent = OrderConcurrentStore::new();
+        assert_eq!(
+            concurrent.shard_count(),
+            OrderConcurrentStore::DEFAULT_SHARDS
+        );
+        assert!((0..100).all(|id| concurrent.shard_for_key(&id) == sharded.shard_for_key(&id)));
+
+        // Non-integer keys go through `StableHash`'s explicit bytes, not `std`'s `Hash`
+        let stable = |key: &dyn Fn(&mut soa_runtime::StableHasher)| {
+            let mut h = soa_runtime::StableHasher::default();
+            key(&mut h);
+            std::hash::Hasher::finish(&h)
+        };
+        use soa_runtime::StableHash;
+        let text = stable(&|h| "EU-1".stable_hash(h));
+        assert_eq!(text, 0xcfd3a3e5dc7c46a9);
+        assert_eq!(text, stable(&|h| String::from("EU-1").stable_hash(h)));
+        assert_eq!(
+            text,
+            stable(&|h| {
+                std::hash::Hasher::write(h, &4u64.to_le_bytes());
+                std::hash::Hasher::write(h, b"EU-1");
+            })
+        );
+        let pair = stable(&|h| (7u32, "EU-1").stable_hash(h));
+        assert_eq!(pair, 0x618388d77213cefd);
+
+        let mut legacy = ReadingByStationShardedStore::with_shards(4, 0);
+        let (si, _) = legacy.add(ReadingByStation { at: 0, station: 7 });
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        std::hash::Hash::hash(&7u32, &mut h);
+        assert_eq!(si as u64, std::hash::Hasher::finish(&h) % 4);
+    }
+
+    #[test]
+    fn test_range_partitioning() {
+        let mut sharded = ReadingShardedStore::with_ranges(vec![100, 200], 8);
+        assert_eq!(sharded.shard_count(), 3);
+        assert_eq!(sharded.bounds(), &[100, 200]);
+        for at in [5, 99, 100, 150, 200, 1_000] {
+            sharded.add(Reading { at, station: 1 });
+        }
+        let per_shard: Vec<Vec<u64>> = (0..3)
+            .map(|i| sharded.shard(i).at_raw_array().to_vec())
+            .collect();
+        assert_eq!(
+            per_shard,
+            vec![vec![5, 99], vec![100, 150], vec![200, 1_000]]
+        );
+        assert_eq!(sharded.remove_by_key(&150).map(|r| r.at), Some(150));
+
+        let concurrent = ReadingConcurrentStore::with_ranges(vec![10], 0);
+        concurrent.add(Reading { at: 12, station: 2 });
+        assert_eq!(concurrent.shard_for_key(&12), 1);
+        assert_eq!(concurrent.snapshot().bounds(), &[10]);
+        assert_eq!(ReadingConcurrentStore::default().shard_count(), 1);
+    }
+
+    #[test]
+    fn test_resharding() {
+        fn fill(n: u64, mut add: impl FnMut(u64)) {
+            (0..n).for_each(&mut add);
+        }
+        fn placed_by_key(sharded: &VisitShardedStore) -> bool {
+            (0..sharded.shard_count()).all(|s| {
+                sharded
+                    .shard(s)
+                    .id_raw_array()
+                    .iter()
+                    .all(|id| sharded.shard_for_key(id) == s)
+            })
+        }
+
+        let mut modulo = OrderShardedStore::with_shards(8, 0);
+        fill(9_000, |id| {
+            modulo.add(Order::new(id, 1, 1, 1, 1.0));
+        });
+        let mut jump = VisitShardedStore::with_shards(8, 0);
+        fill(9_000, |id| {
+            jump.add(Visit { id });
+        });
+        assert!(jump.skew() < 1.2);
+
+        // One more shard: modulo routing moves most rows, jump hashing about 1/9 of them
+        assert!(modulo.reshard(9) > 6_000);
+        let moved = jump.reshard(9);
+        assert!((500..1_500).contains(&moved), "moved {}", moved);
+        assert!(placed_by_key(&jump));
+        assert_eq!(jump.shard_sizes().iter().sum::<usize>(), 9_000);
+        assert_eq!(modulo.shard_sizes().len(), 9);
+        assert!((0..9_000).step_by(97).all(|id| {
+            let s = modulo.shard_for_key(&id);
+            modulo.shard(s).order_id_raw_array().contains(&id)
+        }));
+
+        // Shrinking moves only the rows of the removed shards
+        let last = jump.shard_sizes()[8];
+        assert_eq!(jump.reshard(8), last);
+        assert!(placed_by_key(&jump));
+
+        let mut ranged = ReadingShardedStore::with_ranges(vec![], 0);
+        for at in 0..100 {
+            ranged.add(Reading { at, station: 1 });
+        }
+        assert_eq!(ranged.skew(), 1.0);
+        assert_eq!(ranged.reshard_ranges(vec![25, 50, 75]), 75);
+        assert_eq!(ranged.shard_sizes(), vec![25; 4]);
+        assert_eq!(ranged.skew(), 1.0);
+
+        let concurrent = ReadingConcurrentStore::from(ranged);
+        concurrent.add(Reading { at: 80, station: 2 });
+        assert_eq!(concurrent.shard_sizes(), vec![25, 25, 25, 26]);
+        assert!((concurrent.skew() - 104.0 / 101.0).abs() < 1e-9);
+    }
+
+    #[derive(Clone, Copy, SoA, SoAStore)]
+    #[soa_store(key = "id", partition = "jump")]
+    pub struct Visit {
+        pub id: u64,
+    }
+
+    #[derive(Clone, Copy, SoA, SoAStore)]
+    #[soa_store(key = "at", partition = "range")]
+    pub struct Reading {
+        pub at: u64,
+        pub station: u32,
+    }
+
+    /// Routed with `std`'s hasher, as stores were before the stable default
+    #[derive(Clone, Copy, SoA, SoAStore)]
+    #[soa_store(key = "station", hasher = "std::collections::hash_map::DefaultHasher")]
+    pub struct ReadingByStation {
+        pub at: u64,
+        pub station: u32,
+    }
+
+    /// Not `Clone`: owned iteration moves rows out without copying them
+    #[derive(SoA)]
+    pub struct Booking {
+        pub booking_id: u64,
+        pub seat: String,
+        pub note: Option<String>,
+    }
+
+    #[test]
+    fn test_owned_iteration() {
+        let soa: BookingSoA = (0..3)
+            .map(|i| Booking {
+                booking_id: i,
+                seat: format!("{}A", i + 1),
+                note: (i == 1).then(|| "window seat".to_string()),
+            })
+            .collect();
+        assert_eq!(soa.get_owned(2).seat, "3A");
+
+        let mut rows = soa.into_iter();
+        assert_eq!(rows.len(), 3);
+        let last = rows.next_back().unwrap();
+        assert_eq!(last.seat, "3A");
+        let first = rows.next().unwrap();
+        assert_eq!((first.booking_id, first.note), (0, None));
+        assert_eq!(rows.next().unwrap().note.as_deref(), Some("window seat"));
+        assert!(rows.next().is_none());
+    }
+
+    #[test]
+    fn test_bounds_checked_access() {
+        let mut soa: OrderSoA = (0..3)
+            .map(|id| Order::new(id, 100 + id, 1, 1, 5.0))
+            .collect();
+
+        assert_eq!(soa.get(2).map(|o| *o.order_id), Some(2));
+        assert!(soa.get(3).is_none());
+        assert!(soa.get_mut(3).is_none());
+        assert_eq!(soa.first().map(|o| *o.order_id), Some(0));
+        assert_eq!(soa.last().map(|o| *o.order_id), Some(2));
+
+        *soa.last_mut().unwrap().quantity = 9;
+        assert_eq!(soa.quantity_raw_array(), &[1, 1, 9]);
+
+        let reversed: Vec<u64> = soa.iter().rev().map(|o| *o.order_id).collect();
+        assert_eq!(reversed, vec![2, 1, 0]);
+        assert_eq!(soa.iter().len(), 3);
+
+        let empty = OrderSoA::new();
+        assert!(empty.first().is_none() && empty.last().is_none());
+    }
+
+    #[test]
+    fn test_mutable_iteration() {
+        let mut soa: OrderSoA = (0..4)
+            .map(|id| Order::new(id, 100 + id, 1, 1, 5.0))
+            .collect();
+
+        soa.for_each_mut(|order| {
+            if *order.order_id % 2 == 0 {
+                *order.status = OrderStatus::Shipped;
+            }
+        });
+        assert_eq!(
+            soa.status_raw_array(),
+            &[
+                OrderStatus::Shipped,
+                OrderStatus::Pending,
+                OrderStatus::Shipped,
+                OrderStatus::Pending
+            ]
+        );
+
+        let mut rows = soa.iter_mut();
+        assert_eq!(rows.len(), 4);
+        *rows.next_back().unwrap().total_amount = 0.0;
+        assert_eq!(soa.total_amount_raw_array(), &[5.0, 5.0, 5.0, 0.0]);
+
+        // Nullable and variable-length columns are split-borrowed alongside plain ones
+        let mut bookings: BookingSoA = (0..2)
+            .map(|i| Booking {
+                booking_id: i,
+                seat: "1A".to_string(),
+                note: (i == 0).then(|| "aisle".to_string()),
+            })
+            .collect();
+        for booking in bookings.iter_mut() {
+            *booking.booking_id += 10;
+            if let Some(note) = booking.note {
+                note.push_str(" seat");
+            }
+            assert_eq!(booking.seat, "1A");
+        }
+        assert_eq!(bookings.booking_id_raw_array(), &[10, 11]);
+        assert_eq!(
+            bookings.view(0).note.map(String::as_str),
+            Some("aisle seat")
+        );
+    }
+
+    pub trait Unit: Clone + Default {
+        const SYMBOL: &'static str;
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    pub struct Celsius;
+
+    impl Unit for Celsius {
+        const SYMBOL: &'static str = "°C";
+    }
+
+    #[derive(Debug, Clone, SoA)]
+    pub struct Measurement<U, const N: usize>
+    where
+        U: Unit,
+    {
+        pub sensor_id: u32,
+        pub readings: [f64; N],
+        pub unit: U,
+        pub offset: Option<f64>,
+    }
+
+    #[derive(Debug, Clone, SoA)]
+    pub struct Tagged<'a, T: Copy> {
+        pub tag: &'a str,
+        pub value: T,
+    }
+
+    #[test]
+    fn test_generic_structs() {
+        let mut soa: MeasurementSoA<Celsius, 2> = (0..3)
+            .map(|i| Measurement {
+                sensor_id: i,
+                readings: [i as f64, i as f64 + 0.5],
+                unit: Celsius,
+                offset: None,
+            })
+            .collect();
+
+        for reading in soa.iter_mut() {
+            reading.readings[1] += 1.0;
+        }
+        let view: MeasurementView<'_, Celsius, 2> = soa.view(2);
+        assert_eq!(view.readings, &[2.0, 3.5]);
+        assert_eq!(<Celsius as Unit>::SYMBOL, "°C");
+
+        let owned: Measurement<Celsius, 2> = soa.swap_remove(0);
+        assert_eq!(owned.sensor_id, 0);
+        assert_eq!(soa.len(), 2);
+
+        let tags = ["hot".to_string(), "cold".to_string()];
+        let tagged: TaggedSoA<'_, i8> = tags
+            .iter()
+            .zip([30, -5])
+            .map(|(tag, value)| Tagged { tag, value })
+            .collect();
+        let coldest = tagged.iter().min_by_key(|t| *t.value).unwrap();
+        assert_eq!(*coldest.tag, "cold");
+
+        // Generic code can go through `SoaModel` without naming the generated types
+        fn count_rows<T: soa_runtime::SoaModel<Soa = MeasurementSoA<Celsius, 2>>>(
+            soa: &T::Soa,
+        ) -> usize {
+            soa.len()
+        }
+        assert_eq!(count_rows::<Measurement<Celsius, 2>>(&soa), 2);
+    }
+
+    #[derive(Debug, Clone, SoA)]
+    pub struct Session {
+        pub session_id: u64,
+        #[soa(skip)]
+        pub scratch: Vec<u64>,
+        pub user: String,
+    }
+
+    #[test]
+    fn test_skipped_fields_rebuilt_with_default() {
+        let mut soa = SessionSoA::new();
+        soa.push(Session {
+            session_id: 1,
+            scratch: vec![1, 2, 3],
+            user: "ada".to_string(),
+        });
+
+        let owned = soa.get_owned(0);
+        assert_eq!((owned.session_id, owned.user.as_str()), (1, "ada"));
+        assert!(owned.scratch.is_empty());
+        assert!(soa.swap_remove(0).scratch.is_empty());
+    }
+
+    #[derive(Debug, Clone, PartialEq, SoA)]
+    #[soa(pack(status, payment_method, total_amount))]
+    pub struct Invoice {
+        pub invoice_id: u64,
+        pub status: OrderStatus,
+        pub note: Option<String>,
+        pub payment_method: PaymentMethod,
+        pub total_amount: f64,
+    }
+
+    fn invoice(invoice_id: u64, status: OrderStatus, total_amount: f64) -> Invoice {
+        Invoice {
+            invoice_id,
+            status,
+            note: None,
+            payment_method: PaymentMethod::PayPal,
+            total_amount,
+        }
+    }
+
+    #[test]
+    fn test_packed_fields() {
+        let mut soa: InvoiceSoA = vec![
+            invoice(1, OrderStatus::Delivered, 10.0),
+            invoice(2, OrderStatus::Pending, 20.0),
+            invoice(3, OrderStatus::Delivered, 30.0),
+        ]
+        .into_iter()
+        .collect();
+
+        // The packed fields share one record per row
+        let packed = soa.packed_raw_array();
+        assert_eq!(packed.len(), 3);
+        assert_eq!(packed[1].total_amount, 20.0);
+
+        // Packed fields have `*_strided` accessors that index and iterate like slices, while
+        // unpacked fields keep their contiguous `*_raw_array` slices
+        let amounts = soa.total_amount_strided();
+        assert_eq!(amounts[2], 30.0);
+        assert_eq!(amounts.iter().sum::<f64>(), 60.0);
+        assert_eq!(amounts.slice(1..3).to_vec(), vec![20.0, 30.0]);
+        assert_eq!(soa.invoice_id_raw_array(), &[1, 2, 3]);
+
+        // Views read through to the packed record
+        let view = soa.view(0);
+        assert_eq!(
+            (*view.invoice_id, *view.status),
+            (1, OrderStatus::Delivered)
+        );
+        {
+            let row = soa.view_mut(1);
+            *row.status = OrderStatus::Delivered;
+            *row.total_amount += 1.0;
+        }
+        for row in soa.iter_mut() {
+            *row.invoice_id *= 10;
+            *row.payment_method = PaymentMethod::CreditCard;
+        }
+        assert_eq!(soa.get_owned(1).total_amount, 21.0);
+        assert!(soa
+            .payment_method_strided()
+            .iter()
+            .all(|&p| p == PaymentMethod::CreditCard));
+
+        // Row removal keeps packed and columnar fields aligned
+        soa.set_note(2, Some("late".to_string()));
+        let removed = soa.remove(0);
+        assert_eq!(removed.invoice_id, 10);
+        assert_eq!(soa.view(1).note.map(String::as_str), Some("late"));
+        soa.retain(|row| *row.total_amount > 25.0);
+        assert_eq!(soa.len(), 1);
+        assert_eq!(soa.view(0).invoice_id, &30);
+
+        soa.push(invoice(4, OrderStatus::Shipped, 40.0));
+        let rows: Vec<Invoice> = soa.into_iter().rev().collect();
+        assert_eq!(rows[0], invoice(4, OrderStatus::Shipped, 40.0));
+        assert_eq!(rows[1].note.as_deref(), Some("late"));
+    }
+
+    #[derive(Debug, Clone, SoA)]
+    #[soa(align = 128)]
+    pub struct Tracked {
+        pub id: u64,
+        pub handle: std::rc::Rc<()>,
+    }
+
+    #[test]
+    fn test_aligned_columns() {
+        let orders: OrderSoA = (0..5).map(|i| Order::new(i, 1, 2, 3, 4.0)).collect();
+        assert_eq!(orders.order_id_raw_array().as_ptr() as usize % 64, 0);
+        assert_eq!(orders.quantity_raw_array().as_ptr() as usize % 64, 0);
+        assert_eq!(orders.status_raw_array().as_ptr() as usize % 64, 0);
+
+        let handle = std::rc::Rc::new(());
+        let mut soa: TrackedSoA = (0..100)
+            .map(|id| Tracked {
+                id,
+                handle: handle.clone(),
+            })
+            .collect();
+        assert_eq!(soa.id_raw_array().as_ptr() as usize % 128, 0);
+        assert_eq!(std::rc::Rc::strong_count(&handle), 101);
+
+        // Every removal path drops exactly the rows it removes
+        drop(soa.remove(3));
+        drop(soa.swap_remove(0));
+        drop(soa.drain(10..20));
+        soa.retain(|row| row.id % 2 == 0);
+        soa.truncate(20);
+        assert_eq!(std::rc::Rc::strong_count(&handle), 21);
+        assert_eq!(soa.id_raw_array()[..3], [2, 4, 6]);
+
+        let mut rows = soa.into_iter();
+        drop(rows.next());
+        drop(rows.next_back());
+        assert_eq!(rows.len(), 18);
+        drop(rows);
+        assert_eq!(std::rc::Rc::strong_count(&handle), 1);
+    }
+}
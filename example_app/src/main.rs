@@ -6,7 +6,7 @@ use example_app::{Order, OrderStatus, PaymentMethod, PersistentOrderStore};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    ddd_repository_demo();
+    ddd_repository_demo()?;
 
     println!("\n");
 
@@ -81,12 +81,12 @@ async fn persistent_repository_demo() -> Result<(), Box<dyn std::error::Error>>
 }
 
 /// Demonstration of DDD Repository pattern using generated OrderStore
-fn ddd_repository_demo() {
+fn ddd_repository_demo() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n🏛️ DDD Repository Pattern with SoA Backend");
     println!("{}", "=".repeat(60));
 
     // Import the OrderStore types - other types already available from global imports
-    use example_app::{OrderShardedStore, OrderStore};
+    use example_app::{OrderConcurrentStore, OrderShardedStore, OrderStore};
 
     // 1. Domain-Driven Design Repository Pattern
     let mut order_repository = OrderStore::new();
@@ -97,17 +97,17 @@ fn ddd_repository_demo() {
     order_repository.add(
         Order::new_with_payment(1, 1001, 2001, 2, 50.0, PaymentMethod::CreditCard)
             .with_status(OrderStatus::Delivered),
-    );
+    )?;
 
     order_repository.add(
         Order::new_with_payment(2, 1002, 2002, 1, 75.0, PaymentMethod::PayPal)
             .with_status(OrderStatus::Delivered),
-    );
+    )?;
 
     order_repository.add(
         Order::new_with_payment(3, 1003, 2003, 3, 25.0, PaymentMethod::BankTransfer)
             .with_status(OrderStatus::Pending),
-    );
+    )?;
 
     println!("✅ Added {} orders", order_repository.kernel().len());
 
@@ -122,6 +122,11 @@ fn ddd_repository_demo() {
 
     println!("  Total delivered revenue: ${:.2}", delivered_revenue);
 
+    // Key lookups go through the store's order_id index instead of scanning
+    if let Some(order) = order_repository.get_by_key(&2) {
+        println!("  Order #2 total: ${:.2}", order.total_amount);
+    }
+
     // 3. Sharded Repository for High Performance
     println!("\n🚀 High-Performance Sharded Repository:");
     let mut sharded_repo = OrderShardedStore::with_shards(4, 1000);
@@ -154,10 +159,45 @@ fn ddd_repository_demo() {
         }
     }
 
+    // 4. Concurrent ingestion: workers add through `&self`, locking only their row's shard
+    println!("\n🧵 Concurrent Sharded Repository:");
+    let concurrent_repo = OrderConcurrentStore::with_shards(4, 1000);
+    std::thread::scope(|scope| {
+        for worker in 0..4u64 {
+            let repo = &concurrent_repo;
+            scope.spawn(move || {
+                for i in 0..25 {
+                    let id = 200 + worker * 25 + i;
+                    repo.add(Order::new_with_payment(
+                        id,
+                        1000 + id,
+                        2000 + (id % 10),
+                        1,
+                        100.0,
+                        PaymentMethod::PayPal,
+                    ));
+                }
+            });
+        }
+    });
+    println!(
+        "  4 workers added {} orders across {} shards",
+        concurrent_repo.len(),
+        concurrent_repo.shard_count()
+    );
+    for shard_id in 0..concurrent_repo.shard_count() {
+        concurrent_repo.with_shard(shard_id, |shard| {
+            println!("  Shard {}: {} orders", shard_id, shard.len());
+        });
+    }
+
     println!("\n✨ Key Benefits:");
     println!("  • DDD-style API: Clean, intuitive domain methods");
     println!("  • SoA Performance: Optimized memory layout underneath");
     println!("  • Thread Safety: Arc-based sharing with copy-on-write");
     println!("  • Sharding: Automatic partitioning for parallel processing");
+    println!("  • Concurrency: Per-shard locks let workers ingest in parallel");
     println!("  • Zero Cost: Repository wrapper has no runtime overhead");
+
+    Ok(())
 }
@@ -180,17 +180,19 @@ mod tests {
                 OrderStatus::Pending
             };
 
-            store.add(
-                Order::new_with_payment(
-                    i as u64,
-                    100 + (i % 50) as u64,
-                    200,
-                    1,
-                    50.0 + (i % 100) as f64,
-                    payment,
+            store
+                .add(
+                    Order::new_with_payment(
+                        i as u64,
+                        100 + (i % 50) as u64,
+                        200,
+                        1,
+                        50.0 + (i % 100) as f64,
+                        payment,
+                    )
+                    .with_status(status),
                 )
-                .with_status(status),
-            );
+                .unwrap();
         }
 
         store
@@ -97,6 +97,18 @@ pub fn direct_access_bulk_filter(store: &OrderStore, min_amount: f64) -> Vec<u64
     results
 }
 
+/// Revenue of the orders with the given status and payment method. The row selection is
+/// answered from the bitmap indexes, so only `total_amount` of the selected rows is read.
+pub fn bitmap_filtered_revenue(
+    store: &OrderStore,
+    status: OrderStatus,
+    payment: PaymentMethod,
+) -> f64 {
+    let amounts = store.kernel().total_amount_raw_array();
+    let rows = store.status_bitmap(&status) & store.payment_method_bitmap(&payment);
+    rows.iter().map(|i| amounts[i]).sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,14 +118,18 @@ mod tests {
         let mut store = OrderStore::new();
 
         // Add test data
-        store.add(
-            Order::new_with_payment(1, 100, 200, 2, 50.0, PaymentMethod::CreditCard)
-                .with_status(OrderStatus::Delivered),
-        );
-        store.add(
-            Order::new_with_payment(2, 100, 201, 1, 75.0, PaymentMethod::PayPal)
-                .with_status(OrderStatus::Delivered),
-        );
+        store
+            .add(
+                Order::new_with_payment(1, 100, 200, 2, 50.0, PaymentMethod::CreditCard)
+                    .with_status(OrderStatus::Delivered),
+            )
+            .unwrap();
+        store
+            .add(
+                Order::new_with_payment(2, 100, 201, 1, 75.0, PaymentMethod::PayPal)
+                    .with_status(OrderStatus::Delivered),
+            )
+            .unwrap();
 
         store
     }
@@ -126,4 +142,32 @@ mod tests {
         assert!(results.contains_key(&PaymentMethod::CreditCard));
         assert!(results.contains_key(&PaymentMethod::PayPal));
     }
+
+    #[test]
+    fn test_bitmap_filtered_revenue() {
+        let mut store = create_test_store();
+        store
+            .add(
+                Order::new_with_payment(3, 101, 202, 3, 10.0, PaymentMethod::PayPal)
+                    .with_status(OrderStatus::Delivered),
+            )
+            .unwrap();
+        store
+            .add(Order::new_with_payment(
+                4,
+                101,
+                203,
+                1,
+                99.0,
+                PaymentMethod::PayPal,
+            ))
+            .unwrap();
+
+        let paypal = bitmap_filtered_revenue(&store, OrderStatus::Delivered, PaymentMethod::PayPal);
+        assert!((paypal - 105.0).abs() < 1e-9);
+        assert_eq!(
+            bitmap_filtered_revenue(&store, OrderStatus::Processing, PaymentMethod::PayPal),
+            0.0
+        );
+    }
 }
@@ -135,20 +135,7 @@ impl From<&crate::OrderStore> for OptimizedOrderLayout {
 
         // Reconstruct orders and re-layout them optimally
         for i in 0..len {
-            let view = soa.view(i);
-            let order = Order {
-                order_id: *view.order_id,
-                customer_id: *view.customer_id,
-                product_id: *view.product_id,
-                quantity: *view.quantity,
-                unit_price: *view.unit_price,
-                total_amount: *view.total_amount,
-                status: *view.status,
-                payment_method: *view.payment_method,
-                order_timestamp: *view.order_timestamp,
-                shipping_address_hash: *view.shipping_address_hash,
-            };
-            optimized.push(order);
+            optimized.push(soa.get_owned(i));
         }
 
         optimized
@@ -156,74 +143,23 @@ impl From<&crate::OrderStore> for OptimizedOrderLayout {
 }
 
 /// Alternative layout: Hot/Cold field separation
-/// Separates frequently accessed ("hot") fields from rarely accessed ("cold") fields
-#[derive(Debug, Clone)]
-pub struct HotColdOrderLayout {
-    // Hot fields: accessed frequently in analytics
-    hot_fields: Vec<HotOrderData>,
+/// Separates frequently accessed ("hot") fields from rarely accessed ("cold") fields.
+/// The split is generated by `#[derive(SoA)]` from the `#[soa(group = "hot")]` fields of `Order`
+pub type HotColdOrderLayout = crate::OrderHotCold;
 
-    // Cold fields: accessed rarely, stored separately to avoid cache pollution
-    cold_fields: Vec<ColdOrderData>,
-}
+/// Hot fields: accessed frequently in analytics
+pub type HotOrderData = crate::OrderHot;
 
-#[derive(Debug, Clone, Copy)]
-pub struct HotOrderData {
-    pub status: OrderStatus,
-    pub payment_method: PaymentMethod,
-    pub total_amount: f64,
-    pub customer_id: u64,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub struct ColdOrderData {
-    pub order_id: u64,
-    pub product_id: u64,
-    pub quantity: u32,
-    pub unit_price: f64,
-    pub order_timestamp: u64,
-    pub shipping_address_hash: u64,
-}
+/// Cold fields: accessed rarely, stored separately to avoid cache pollution
+pub type ColdOrderData = crate::OrderCold;
 
 impl HotColdOrderLayout {
-    pub fn new() -> Self {
-        Self {
-            hot_fields: Vec::new(),
-            cold_fields: Vec::new(),
-        }
-    }
-
-    pub fn len(&self) -> usize {
-        self.hot_fields.len()
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.hot_fields.is_empty()
-    }
-
-    pub fn push(&mut self, order: Order) {
-        self.hot_fields.push(HotOrderData {
-            status: order.status,
-            payment_method: order.payment_method,
-            total_amount: order.total_amount,
-            customer_id: order.customer_id,
-        });
-
-        self.cold_fields.push(ColdOrderData {
-            order_id: order.order_id,
-            product_id: order.product_id,
-            quantity: order.quantity,
-            unit_price: order.unit_price,
-            order_timestamp: order.order_timestamp,
-            shipping_address_hash: order.shipping_address_hash,
-        });
-    }
-
     /// Ultra-fast revenue analysis - only touches hot fields
     pub fn revenue_by_payment_method(&self) -> HashMap<PaymentMethod, f64> {
         let mut results = HashMap::new();
 
         // Only access hot fields, cold fields stay out of cache
-        for hot in &self.hot_fields {
+        for hot in self.hot() {
             if matches!(hot.status, OrderStatus::Delivered) {
                 *results.entry(hot.payment_method).or_insert(0.0) += hot.total_amount;
             }
@@ -236,7 +172,7 @@ impl HotColdOrderLayout {
     pub fn customer_lifetime_values(&self) -> HashMap<u64, f64> {
         let mut results = HashMap::new();
 
-        for hot in &self.hot_fields {
+        for hot in self.hot() {
             if matches!(hot.status, OrderStatus::Delivered) {
                 *results.entry(hot.customer_id).or_insert(0.0) += hot.total_amount;
             }
@@ -246,36 +182,9 @@ impl HotColdOrderLayout {
     }
 }
 
-impl Default for HotColdOrderLayout {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl From<&crate::OrderStore> for HotColdOrderLayout {
     fn from(store: &crate::OrderStore) -> Self {
-        let soa = store.kernel();
-        let len = soa.len();
-        let mut layout = HotColdOrderLayout::new();
-
-        for i in 0..len {
-            let view = soa.view(i);
-            let order = Order {
-                order_id: *view.order_id,
-                customer_id: *view.customer_id,
-                product_id: *view.product_id,
-                quantity: *view.quantity,
-                unit_price: *view.unit_price,
-                total_amount: *view.total_amount,
-                status: *view.status,
-                payment_method: *view.payment_method,
-                order_timestamp: *view.order_timestamp,
-                shipping_address_hash: *view.shipping_address_hash,
-            };
-            layout.push(order);
-        }
-
-        layout
+        Self::from(store.kernel())
     }
 }
 
@@ -323,5 +232,46 @@ mod tests {
 
         let revenue = layout.revenue_by_payment_method();
         assert_eq!(revenue.len(), 2);
+
+        // Cold fields are kept and rows round-trip through the split
+        assert_eq!(layout.cold()[2].product_id, 202);
+        let order = layout.get_owned(0);
+        assert_eq!((order.order_id, order.customer_id), (1, 100));
+
+        // `#[soa(copy)]` records copy out of the layout like the hand-written ones did
+        let hot: HotOrderData = layout.hot()[1];
+        let cold: ColdOrderData = layout.cold()[1];
+        assert_eq!((hot.customer_id, cold.order_id), (101, 2));
+        assert!(format!("{:?}", layout).contains("OrderHot"));
+    }
+
+    #[test]
+    fn test_aosoa_layout() {
+        let mut layout: crate::OrderAoSoA = create_test_orders().into_iter().collect();
+        assert_eq!(crate::OrderAoSoA::LANES, 8);
+        assert_eq!((layout.len(), layout.tiles().len()), (3, 1));
+        assert_eq!(layout.tile_len(0), 3);
+
+        // Every lane array starts on its own cache line
+        let tile = &layout.tiles()[0];
+        assert_eq!(tile.total_amount.as_ptr() as usize % 64, 0);
+        assert_eq!(tile.status.as_ptr() as usize % 64, 0);
+        assert_eq!(&tile.customer_id[..3], &[100, 101, 100]);
+
+        // Unused lanes hold defaults, so a whole-tile sum counts each row once
+        assert_eq!(&tile.order_id[3..], &[0; 5]);
+        let amounts: f64 = layout.iter().map(|o| o.total_amount).sum();
+        assert_eq!(tile.total_amount.iter().sum::<f64>(), amounts);
+
+        for i in 3..10 {
+            layout.push(Order::new(i, 100, 200, 1, 10.0));
+        }
+        let tiles: Vec<usize> = layout.iter_tiles().map(|(_, len)| len).collect();
+        assert_eq!(tiles, vec![8, 2]);
+        assert_eq!(layout.get_owned(9).order_id, 9);
+        assert_eq!(
+            layout.iter().map(|o| o.order_id).sum::<u64>(),
+            1 + 2 + 3 + (3..10).sum::<u64>()
+        );
     }
 }
@@ -2,6 +2,9 @@ pub mod cache_blocking;
 pub mod direct_access;
 pub mod memory_layout;
 
+#[cfg(feature = "parallel")]
+pub mod parallel;
+
 #[cfg(target_arch = "x86_64")]
 pub mod simd;
 
@@ -9,6 +12,9 @@ pub use cache_blocking::*;
 pub use direct_access::*;
 pub use memory_layout::*;
 
+#[cfg(feature = "parallel")]
+pub use parallel::*;
+
 #[cfg(target_arch = "x86_64")]
 pub use simd::*;
 
@@ -0,0 +1,253 @@
+//! Multi-core versions of the `direct_access` aggregations.
+//!
+//! Each rayon task folds a chunk of rows into its own `HashMap`, and the per-task maps are
+//! merged pairwise, so no locks or atomics are touched while scanning the columns.
+
+use crate::{OrderShardedStore, OrderStatus, OrderStore, PaymentMethod};
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Rows per parallel task: large enough to amortise scheduling, small enough to keep every
+/// core busy until the end
+const CHUNK_ROWS: usize = 16 * 1024;
+
+/// Merge `b` into `a`, combining values of keys present in both with `add`
+fn merge_maps<K: Hash + Eq, V>(
+    a: HashMap<K, V>,
+    b: HashMap<K, V>,
+    add: impl Fn(&mut V, V),
+) -> HashMap<K, V> {
+    // Insert the smaller map into the larger one
+    let (mut into, from) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    for (k, v) in from {
+        match into.entry(k) {
+            Entry::Occupied(mut e) => add(e.get_mut(), v),
+            Entry::Vacant(e) => {
+                e.insert(v);
+            }
+        }
+    }
+    into
+}
+
+/// Parallel [`direct_access_revenue_analysis`](super::direct_access_revenue_analysis)
+pub fn par_direct_access_revenue_analysis(store: &OrderStore) -> HashMap<PaymentMethod, f64> {
+    let soa = store.kernel();
+    let statuses = soa.status_raw_array();
+    let payments = soa.payment_method_raw_array();
+    let amounts = soa.total_amount_raw_array();
+
+    soa.par_chunks(CHUNK_ROWS)
+        .fold(HashMap::new, |mut acc, rows| {
+            for i in rows {
+                if matches!(statuses[i], OrderStatus::Delivered) {
+                    *acc.entry(payments[i]).or_insert(0.0) += amounts[i];
+                }
+            }
+            acc
+        })
+        .reduce(HashMap::new, |a, b| merge_maps(a, b, |x, y| *x += y))
+}
+
+/// Parallel [`direct_access_customer_analysis`](super::direct_access_customer_analysis)
+pub fn par_direct_access_customer_analysis(store: &OrderStore) -> HashMap<u64, (u32, f64)> {
+    let soa = store.kernel();
+    let customers = soa.customer_id_raw_array();
+    let statuses = soa.status_raw_array();
+    let amounts = soa.total_amount_raw_array();
+
+    soa.par_chunks(CHUNK_ROWS)
+        .fold(HashMap::new, |mut acc: HashMap<u64, (u32, f64)>, rows| {
+            for i in rows {
+                let entry = acc.entry(customers[i]).or_insert((0, 0.0));
+                entry.0 += 1;
+                if matches!(statuses[i], OrderStatus::Delivered) {
+                    entry.1 += amounts[i];
+                }
+            }
+            acc
+        })
+        .reduce(HashMap::new, |a, b| {
+            merge_maps(a, b, |x, y| {
+                x.0 += y.0;
+                x.1 += y.1;
+            })
+        })
+}
+
+/// Parallel [`direct_access_product_performance`](super::direct_access_product_performance)
+pub fn par_direct_access_product_performance(store: &OrderStore) -> HashMap<u64, (u32, f64, f64)> {
+    let soa = store.kernel();
+    let products = soa.product_id_raw_array();
+    let quantities = soa.quantity_raw_array();
+    let statuses = soa.status_raw_array();
+    let amounts = soa.total_amount_raw_array();
+
+    soa.par_chunks(CHUNK_ROWS)
+        .fold(
+            HashMap::new,
+            |mut acc: HashMap<u64, (u32, f64, f64)>, rows| {
+                for i in rows {
+                    let entry = acc.entry(products[i]).or_insert((0, 0.0, 0.0));
+                    entry.0 += quantities[i];
+                    entry.1 += amounts[i];
+                    if matches!(statuses[i], OrderStatus::Delivered) {
+                        entry.2 += amounts[i];
+                    }
+                }
+                acc
+            },
+        )
+        .reduce(HashMap::new, |a, b| {
+            merge_maps(a, b, |x, y| {
+                x.0 += y.0;
+                x.1 += y.1;
+                x.2 += y.2;
+            })
+        })
+}
+
+/// Parallel [`direct_access_bulk_filter`](super::direct_access_bulk_filter); ids come back in
+/// row order
+pub fn par_direct_access_bulk_filter(store: &OrderStore, min_amount: f64) -> Vec<u64> {
+    let soa = store.kernel();
+    let order_ids = soa.order_id_raw_array();
+    let amounts = soa.total_amount_raw_array();
+    let statuses = soa.status_raw_array();
+    let payments = soa.payment_method_raw_array();
+
+    soa.par_chunks(CHUNK_ROWS)
+        .flat_map_iter(|rows| {
+            rows.filter(|&i| {
+                amounts[i] > min_amount
+                    && matches!(statuses[i], OrderStatus::Delivered)
+                    && matches!(payments[i], PaymentMethod::CreditCard)
+            })
+            .map(|i| order_ids[i])
+        })
+        .collect()
+}
+
+/// Delivered revenue by payment method over a sharded store, one rayon task per shard
+pub fn par_sharded_revenue_analysis(store: &OrderShardedStore) -> HashMap<PaymentMethod, f64> {
+    store
+        .par_shards()
+        .map(|shard| {
+            let mut acc = HashMap::new();
+            for order in shard.iter() {
+                if matches!(*order.status, OrderStatus::Delivered) {
+                    *acc.entry(*order.payment_method).or_insert(0.0) += *order.total_amount;
+                }
+            }
+            acc
+        })
+        .reduce(HashMap::new, |a, b| merge_maps(a, b, |x, y| *x += y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizations::direct_access::*;
+    use crate::Order;
+
+    fn create_test_store(n: u64) -> OrderStore {
+        let mut store = OrderStore::new();
+        for i in 0..n {
+            let payment = match i % 3 {
+                0 => PaymentMethod::CreditCard,
+                1 => PaymentMethod::PayPal,
+                _ => PaymentMethod::BankTransfer,
+            };
+            let status = if i % 4 == 0 {
+                OrderStatus::Pending
+            } else {
+                OrderStatus::Delivered
+            };
+            store
+                .add(
+                    Order::new_with_payment(
+                        i,
+                        i % 97,
+                        i % 13,
+                        1 + (i % 5) as u32,
+                        10.0 + (i % 50) as f64,
+                        payment,
+                    )
+                    .with_status(status),
+                )
+                .unwrap();
+        }
+        store
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6 * b.abs().max(1.0), "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        // Several chunks, the last one partial
+        let store = create_test_store(3 * CHUNK_ROWS as u64 + 123);
+
+        let seq = direct_access_revenue_analysis(&store);
+        let par = par_direct_access_revenue_analysis(&store);
+        assert_eq!(seq.len(), par.len());
+        for (method, revenue) in &seq {
+            assert_close(par[method], *revenue);
+        }
+
+        let seq = direct_access_customer_analysis(&store);
+        let par = par_direct_access_customer_analysis(&store);
+        assert_eq!(seq.len(), par.len());
+        for (customer, (count, value)) in &seq {
+            assert_eq!(par[customer].0, *count);
+            assert_close(par[customer].1, *value);
+        }
+
+        let seq = direct_access_product_performance(&store);
+        let par = par_direct_access_product_performance(&store);
+        assert_eq!(seq.len(), par.len());
+        for (product, (quantity, revenue, delivered)) in &seq {
+            assert_eq!(par[product].0, *quantity);
+            assert_close(par[product].1, *revenue);
+            assert_close(par[product].2, *delivered);
+        }
+
+        assert_eq!(
+            par_direct_access_bulk_filter(&store, 40.0),
+            direct_access_bulk_filter(&store, 40.0)
+        );
+    }
+
+    #[test]
+    fn test_par_shards() {
+        let store = create_test_store(10_000);
+        let mut sharded = OrderShardedStore::with_shards(8, 0);
+        for i in 0..store.kernel().len() {
+            sharded.add(store.kernel().get_owned(i));
+        }
+
+        let seq = direct_access_revenue_analysis(&store);
+        let par = par_sharded_revenue_analysis(&sharded);
+        for (method, revenue) in &seq {
+            assert_close(par[method], *revenue);
+        }
+
+        sharded
+            .par_shards_mut()
+            .for_each(|shard| shard.for_each_mut(|o| *o.quantity = 0));
+        assert!(sharded
+            .par_shards()
+            .all(|shard| shard.quantity_raw_array().iter().all(|&q| q == 0)));
+        assert_eq!(
+            store
+                .kernel()
+                .par_iter()
+                .filter(|o| *o.quantity == 1)
+                .count(),
+            2_000
+        );
+    }
+}
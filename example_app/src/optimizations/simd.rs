@@ -1,7 +1,7 @@
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
-use crate::{OrderStatus, OrderStore, PaymentMethod};
+use crate::{OrderAoSoA, OrderStatus, OrderStore, PaymentMethod};
 use std::collections::HashMap;
 
 /// SIMD-optimized revenue analysis using AVX2 instructions
@@ -209,6 +209,46 @@ pub fn hybrid_simd_aggregation(store: &OrderStore) -> HashMap<PaymentMethod, f64
     results
 }
 
+/// SIMD revenue analysis over the AoSoA tiles of `OrderAoSoA`
+/// Full tiles hold a whole number of 4-lane vectors, so only the last tile needs scalar code
+#[cfg(target_arch = "x86_64")]
+pub fn simd_tiled_revenue_analysis(tiles: &OrderAoSoA) -> HashMap<PaymentMethod, f64> {
+    const _: () = assert!(OrderAoSoA::LANES.is_multiple_of(4));
+
+    let mut results = HashMap::new();
+
+    for (tile, len) in tiles.iter_tiles() {
+        // Only the partially filled last tile needs scalar code
+        if len < OrderAoSoA::LANES {
+            for j in 0..len {
+                if matches!(tile.status[j], OrderStatus::Delivered) {
+                    *results.entry(tile.payment_method[j]).or_insert(0.0) += tile.total_amount[j];
+                }
+            }
+            continue;
+        }
+
+        unsafe {
+            for i in (0..OrderAoSoA::LANES).step_by(4) {
+                let amounts_vec = _mm256_loadu_pd(tile.total_amount.as_ptr().add(i));
+                let delivered_mask = create_delivered_mask(&tile.status[i..i + 4]);
+                let filtered_amounts = _mm256_and_pd(amounts_vec, delivered_mask);
+
+                let mut extracted = [0.0; 4];
+                _mm256_storeu_pd(extracted.as_mut_ptr(), filtered_amounts);
+
+                for (j, &amount) in extracted.iter().enumerate() {
+                    if amount != 0.0 {
+                        *results.entry(tile.payment_method[i + j]).or_insert(0.0) += amount;
+                    }
+                }
+            }
+        }
+    }
+
+    results
+}
+
 /// Check if CPU supports required SIMD instructions
 #[cfg(target_arch = "x86_64")]
 pub fn cpu_supports_avx2() -> bool {
@@ -246,6 +286,19 @@ pub fn hybrid_simd_aggregation(store: &OrderStore) -> HashMap<PaymentMethod, f64
     crate::optimizations::cache_blocking::cache_blocked_aggregation(store)
 }
 
+#[cfg(not(target_arch = "x86_64"))]
+pub fn simd_tiled_revenue_analysis(tiles: &OrderAoSoA) -> HashMap<PaymentMethod, f64> {
+    let mut results = HashMap::new();
+    for (tile, len) in tiles.iter_tiles() {
+        for j in 0..len {
+            if matches!(tile.status[j], OrderStatus::Delivered) {
+                *results.entry(tile.payment_method[j]).or_insert(0.0) += tile.total_amount[j];
+            }
+        }
+    }
+    results
+}
+
 #[cfg(not(target_arch = "x86_64"))]
 pub fn cpu_supports_avx2() -> bool {
     false
@@ -254,10 +307,10 @@ pub fn cpu_supports_avx2() -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Order, OrderAnalytics};
+    use crate::Order;
 
     fn create_large_test_store(size: usize) -> OrderStore {
-        let mut analytics = OrderAnalytics::new();
+        let mut store = OrderStore::new();
 
         for i in 0..size {
             let payment = match i % 3 {
@@ -271,20 +324,22 @@ mod tests {
                 OrderStatus::Pending
             };
 
-            analytics.add_order(
-                Order::new_with_payment(
-                    i as u64,
-                    100 + (i % 50) as u64,
-                    200,
-                    1,
-                    50.0 + (i % 100) as f64,
-                    payment,
+            store
+                .add(
+                    Order::new_with_payment(
+                        i as u64,
+                        100 + (i % 50) as u64,
+                        200,
+                        1,
+                        50.0 + (i % 100) as f64,
+                        payment,
+                    )
+                    .with_status(status),
                 )
-                .with_status(status),
-            );
+                .unwrap();
         }
 
-        analytics.get_store().clone()
+        store
     }
 
     #[test]
@@ -293,7 +348,7 @@ mod tests {
         let results = simd_revenue_analysis(&store);
 
         // Should have results for all payment methods
-        assert!(results.len() > 0);
+        assert!(!results.is_empty());
         assert!(results.values().all(|&v| v > 0.0));
     }
 
@@ -317,4 +372,29 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_tiled_simd_vs_scalar() {
+        // 1001 rows: 125 full tiles plus a partial one
+        let store = create_large_test_store(1001);
+        let tiles = OrderAoSoA::from(store.kernel());
+        assert_eq!(tiles.tiles().len(), 126);
+        assert_eq!(tiles.tile_len(125), 1);
+
+        let tiled_results = simd_tiled_revenue_analysis(&tiles);
+        let scalar_results =
+            crate::optimizations::direct_access::direct_access_revenue_analysis(&store);
+
+        assert_eq!(tiled_results.len(), scalar_results.len());
+        for (method, tiled_amount) in &tiled_results {
+            let scalar_amount = scalar_results[method];
+            assert!(
+                (tiled_amount - scalar_amount).abs() < 0.01,
+                "tiled vs scalar mismatch for {:?}: {} vs {}",
+                method,
+                tiled_amount,
+                scalar_amount
+            );
+        }
+    }
 }
@@ -1,8 +1,8 @@
-use example_app::persistence::PersistentOrderStore;
+use example_app::persistence::{OrderRevenueSoA, PersistentOrderStore};
 use example_app::{Order, OrderSoA, OrderStatus, PaymentMethod};
 use parquet::basic::Compression;
-use soa_persistence::{ParquetPersistence, SoAPersistence};
-use std::path::PathBuf;
+use soa_persistence::{col, Manifest, ParquetPersistence, SoAPersistence};
+use std::path::{Path, PathBuf};
 
 /// Demonstration of persistent SoA store with Parquet disk-based backend
 #[tokio::main]
@@ -61,165 +61,167 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Note: In a full integration, PersistentOrderStore would directly use ParquetPersistence
     // For this demo, we show the Parquet persistence capability
 
-    if let Some(soa_data) = memory_store.query_storage(|_| true).await? {
-        // Create Parquet persistence backend
-        let mut parquet_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir)
-            .with_compression(Compression::ZSTD(Default::default()))
-            .with_page_size(8192);
+    let soa_data = memory_store.kernel();
+    // Create Parquet persistence backend
+    let mut parquet_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir)
+        .with_compression(Compression::ZSTD(Default::default()))
+        .with_page_size(8192);
 
-        // Save to disk
-        parquet_persistence.save(&soa_data).await?;
+    // Save to disk
+    parquet_persistence.save(soa_data).await?;
 
-        let file_path = data_dir.join("data.parquet");
-        let metadata = std::fs::metadata(&file_path)?;
+    let manifest = parquet_persistence.manifest().await?;
+    let size = stored_size(&data_dir, &manifest)?;
 
-        println!("  ✅ Saved to Parquet file");
-        println!("     File: {:?}", file_path);
+    println!("  ✅ Saved to Parquet file");
+    for part in &manifest.parts {
+        println!("     File: {:?}", data_dir.join(&part.file));
+    }
+    println!("     Size: {} bytes ({:.2} KB)", size, size as f64 / 1024.0);
+    println!("     Rows: {}", parquet_persistence.count().await?);
+
+    // 4. Demonstrate data survives "application restart"
+    println!("\n🔄 Simulating application restart:");
+    println!("  💀 Application terminated...");
+    println!("  🔌 Application restarted...");
+
+    // Create new persistence instance (simulating restart)
+    let restored_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir)
+        .with_compression(Compression::ZSTD(Default::default()));
+
+    // Load data from disk
+    if let Some(loaded_data) = restored_persistence.load().await? {
+        println!("  ✅ Successfully loaded data from disk");
         println!(
-            "     Size: {} bytes ({:.2} KB)",
-            metadata.len(),
-            metadata.len() as f64 / 1024.0
+            "     Restored {} orders",
+            loaded_data.order_id_raw_array().len()
         );
-        println!("     Rows: {}", parquet_persistence.count().await?);
 
-        // 4. Demonstrate data survives "application restart"
-        println!("\n🔄 Simulating application restart:");
-        println!("  💀 Application terminated...");
-        println!("  🔌 Application restarted...");
+        // Verify data integrity
+        let original_total: f64 = soa_data.total_amount_raw_array().iter().sum();
+        let loaded_total: f64 = loaded_data.total_amount_raw_array().iter().sum();
 
-        // Create new persistence instance (simulating restart)
-        let restored_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir)
-            .with_compression(Compression::ZSTD(Default::default()));
+        println!("\n📊 Data Integrity Check:");
+        println!("  Original total revenue: ${:.2}", original_total);
+        println!("  Loaded total revenue:   ${:.2}", loaded_total);
+        println!(
+            "  Match: {}",
+            if (original_total - loaded_total).abs() < 0.01 {
+                "✅"
+            } else {
+                "❌"
+            }
+        );
 
-        // Load data from disk
-        if let Some(loaded_data) = restored_persistence.load().await? {
-            println!("  ✅ Successfully loaded data from disk");
-            println!(
-                "     Restored {} orders",
-                loaded_data.order_id_raw_array().len()
-            );
+        // 5. Demonstrate append operation
+        println!("\n➕ Appending new orders:");
+
+        let new_orders = vec![
+            Order::new_with_payment(9, 1009, 2009, 2, 175.00, PaymentMethod::CreditCard)
+                .with_status(OrderStatus::Processing),
+            Order::new_with_payment(10, 1010, 2010, 1, 425.99, PaymentMethod::PayPal)
+                .with_status(OrderStatus::Pending),
+        ];
+
+        // Add new orders to memory store
+        let mut temp_store = PersistentOrderStore::with_capacity(10);
+        temp_store.add_batch(new_orders).await?;
+
+        let new_soa_data = temp_store.kernel();
+        // Append to existing Parquet file
+        let mut append_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir);
+        append_persistence.append(new_soa_data).await?;
+
+        // Each append writes a new part file; existing parts are not rewritten
+        let manifest = append_persistence.manifest().await?;
+        println!(
+            "  ✅ Appended 2 new orders as {}",
+            manifest.parts.last().map_or("", |part| part.file.as_str())
+        );
+        println!(
+            "  Total orders in {} parts: {}",
+            manifest.parts.len(),
+            append_persistence.count().await?
+        );
 
-            // Verify data integrity
-            let original_total: f64 = soa_data.total_amount_raw_array().iter().sum();
-            let loaded_total: f64 = loaded_data.total_amount_raw_array().iter().sum();
+        // Merge the small parts back into one file
+        let merged = append_persistence.compact().await?;
+        println!(
+            "  ✅ Compaction merged away {} parts, {} left",
+            merged,
+            append_persistence.manifest().await?.parts.len()
+        );
 
-            println!("\n📊 Data Integrity Check:");
-            println!("  Original total revenue: ${:.2}", original_total);
-            println!("  Loaded total revenue:   ${:.2}", loaded_total);
+        // Reload to verify
+        if let Some(all_data) = append_persistence.load().await? {
             println!(
-                "  Match: {}",
-                if (original_total - loaded_total).abs() < 0.01 {
-                    "✅"
-                } else {
-                    "❌"
-                }
+                "  Verified: {} orders now in storage",
+                all_data.order_id_raw_array().len()
             );
+        }
 
-            // 5. Demonstrate append operation
-            println!("\n➕ Appending new orders:");
-
-            let new_orders = vec![
-                Order::new_with_payment(9, 1009, 2009, 2, 175.00, PaymentMethod::CreditCard)
-                    .with_status(OrderStatus::Processing),
-                Order::new_with_payment(10, 1010, 2010, 1, 425.99, PaymentMethod::PayPal)
-                    .with_status(OrderStatus::Pending),
-            ];
-
-            // Add new orders to memory store
-            let mut temp_store = PersistentOrderStore::with_capacity(10);
-            temp_store.add_batch(new_orders).await?;
-
-            if let Some(new_soa_data) = temp_store.query_storage(|_| true).await? {
-                // Append to existing Parquet file
-                let mut append_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir);
-                append_persistence.append(&new_soa_data).await?;
-
-                println!("  ✅ Appended 2 new orders");
-                println!(
-                    "  Total orders in file: {}",
-                    append_persistence.count().await?
-                );
-
-                // Reload to verify
-                if let Some(all_data) = append_persistence.load().await? {
-                    println!(
-                        "  Verified: {} orders now in storage",
-                        all_data.order_id_raw_array().len()
-                    );
-                }
-            }
+        // 6. Query operations on disk-persisted data
+        println!("\n🔍 Querying disk-persisted data:");
 
-            // 6. Query operations on disk-persisted data
-            println!("\n🔍 Querying disk-persisted data:");
-
-            let query_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir);
-
-            // Query for delivered orders
-            if let Some(all_orders) = query_persistence.load().await? {
-                let delivered_count = all_orders
-                    .status_raw_array()
-                    .iter()
-                    .filter(|&&status| status == OrderStatus::Delivered)
-                    .count();
-
-                let delivered_revenue: f64 = all_orders
-                    .status_raw_array()
-                    .iter()
-                    .zip(all_orders.total_amount_raw_array().iter())
-                    .filter(|(&status, _)| status == OrderStatus::Delivered)
-                    .map(|(_, &amount)| amount)
-                    .sum();
-
-                println!("  Delivered orders: {}", delivered_count);
-                println!("  Delivered revenue: ${:.2}", delivered_revenue);
-
-                // High-value orders
-                let high_value_count = all_orders
-                    .total_amount_raw_array()
-                    .iter()
-                    .filter(|&&amount| amount > 200.0)
-                    .count();
-
-                println!("  High-value orders (>$200): {}", high_value_count);
-
-                // Payment method breakdown
-                let credit_card_count = all_orders
-                    .payment_method_raw_array()
-                    .iter()
-                    .filter(|&&method| method == PaymentMethod::CreditCard)
-                    .count();
-
-                let paypal_count = all_orders
-                    .payment_method_raw_array()
-                    .iter()
-                    .filter(|&&method| method == PaymentMethod::PayPal)
-                    .count();
-
-                println!("  Credit Card payments: {}", credit_card_count);
-                println!("  PayPal payments: {}", paypal_count);
-            }
+        let query_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir);
 
-            // 7. Efficient metadata operations
-            println!("\n⚡ Efficient metadata operations:");
+        // Delivered orders: the filter runs while reading, so other rows are never decoded
+        let delivered = col("status").eq(OrderStatus::Delivered);
+        if let Some(delivered_orders) = query_persistence.query(&delivered).await? {
+            let delivered_revenue: f64 = delivered_orders.total_amount_raw_array().iter().sum();
 
-            let metadata_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir);
+            println!("  Delivered orders: {}", delivered_orders.len());
+            println!("  Delivered revenue: ${:.2}", delivered_revenue);
+        }
 
-            // Count without reading data (uses Parquet metadata)
-            let count = metadata_persistence.count().await?;
-            println!("  Row count (metadata only): {}", count);
-            println!("  ✅ No data read - instant operation");
+        // Only the three revenue columns are read from the file
+        if let Some(all_orders) = query_persistence.load_as::<OrderRevenueSoA>().await? {
+            // High-value orders
+            let high_value_count = all_orders
+                .total_amount_raw_array()
+                .iter()
+                .filter(|&&amount| amount > 200.0)
+                .count();
+
+            println!("  High-value orders (>$200): {}", high_value_count);
+
+            // Payment method breakdown
+            let credit_card_count = all_orders
+                .payment_method_raw_array()
+                .iter()
+                .filter(|&&method| method == PaymentMethod::CreditCard)
+                .count();
+
+            let paypal_count = all_orders
+                .payment_method_raw_array()
+                .iter()
+                .filter(|&&method| method == PaymentMethod::PayPal)
+                .count();
+
+            println!("  Credit Card payments: {}", credit_card_count);
+            println!("  PayPal payments: {}", paypal_count);
+        }
 
-            // 8. Compression comparison
-            println!("\n🗜️  Compression effectiveness:");
+        // 7. Efficient metadata operations
+        println!("\n⚡ Efficient metadata operations:");
 
-            let file_size = std::fs::metadata(data_dir.join("data.parquet"))?.len();
-            let estimated_raw_size = count * 100; // Rough estimate: 100 bytes per row
-            let compression_ratio = estimated_raw_size as f64 / file_size as f64;
+        let metadata_persistence = ParquetPersistence::<OrderSoA>::new(&data_dir);
 
-            println!("  Compressed size: {} bytes", file_size);
-            println!("  Estimated raw size: {} bytes", estimated_raw_size);
-            println!("  Compression ratio: {:.2}x", compression_ratio);
-        }
+        // Count without reading data (uses Parquet metadata)
+        let count = metadata_persistence.count().await?;
+        println!("  Row count (metadata only): {}", count);
+        println!("  ✅ No data read - instant operation");
+
+        // 8. Compression comparison
+        println!("\n🗜️  Compression effectiveness:");
+
+        let file_size = stored_size(&data_dir, &metadata_persistence.manifest().await?)?;
+        let estimated_raw_size = count * 100; // Rough estimate: 100 bytes per row
+        let compression_ratio = estimated_raw_size as f64 / file_size as f64;
+
+        println!("  Compressed size: {} bytes", file_size);
+        println!("  Estimated raw size: {} bytes", estimated_raw_size);
+        println!("  Compression ratio: {:.2}x", compression_ratio);
     }
 
     // 9. Performance characteristics
@@ -251,9 +253,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n✨ Parquet persistence demo completed successfully!");
     println!("\n💡 Next steps:");
     println!("  1. Try different compression algorithms (SNAPPY, GZIP, LZ4)");
-    println!("  2. Read the Parquet file with Python/Pandas");
+    println!("  2. Read the Parquet files with Python/Pandas");
     println!("  3. Compare Arrow (in-memory) vs Parquet (disk) performance");
     println!("  4. Integrate with DuckDB for SQL queries");
 
     Ok(())
 }
+
+/// Total size of the part files listed by `manifest`
+fn stored_size(data_dir: &Path, manifest: &Manifest) -> std::io::Result<u64> {
+    manifest
+        .parts
+        .iter()
+        .map(|part| Ok(std::fs::metadata(data_dir.join(&part.file))?.len()))
+        .sum()
+}
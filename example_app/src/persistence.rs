@@ -1,179 +1,13 @@
 use crate::{Order, OrderSoA, OrderStatus, PaymentMethod};
-use ::arrow_array::{Array, Float64Array, RecordBatch, UInt32Array, UInt64Array, UInt8Array};
-use ::arrow_schema::{DataType, Field, Schema};
-use soa_persistence::{
-    ArrowPersistence, ArrowSchemaGen, MemoryStats, PersistenceError, SoAPersistence, ToArrow,
-};
-use std::sync::Arc;
-
-// Implement ArrowSchemaGen for OrderSoA
-impl ArrowSchemaGen for OrderSoA {
-    fn arrow_schema() -> Arc<Schema> {
-        Arc::new(Schema::new(vec![
-            Field::new("order_id", DataType::UInt64, false),
-            Field::new("customer_id", DataType::UInt64, false),
-            Field::new("product_id", DataType::UInt64, false),
-            Field::new("quantity", DataType::UInt32, false),
-            Field::new("unit_price", DataType::Float64, false),
-            Field::new("total_amount", DataType::Float64, false),
-            Field::new("status", DataType::UInt8, false),
-            Field::new("payment_method", DataType::UInt8, false),
-            Field::new("order_timestamp", DataType::UInt64, false),
-            Field::new("shipping_address_hash", DataType::UInt64, false),
-        ]))
-    }
-
-    fn arrow_field_names() -> Vec<&'static str> {
-        vec![
-            "order_id",
-            "customer_id",
-            "product_id",
-            "quantity",
-            "unit_price",
-            "total_amount",
-            "status",
-            "payment_method",
-            "order_timestamp",
-            "shipping_address_hash",
-        ]
-    }
-
-    fn arrow_field_types() -> Vec<DataType> {
-        vec![
-            DataType::UInt64,  // order_id
-            DataType::UInt64,  // customer_id
-            DataType::UInt64,  // product_id
-            DataType::UInt32,  // quantity
-            DataType::Float64, // unit_price
-            DataType::Float64, // total_amount
-            DataType::UInt8,   // status (enum)
-            DataType::UInt8,   // payment_method (enum)
-            DataType::UInt64,  // order_timestamp
-            DataType::UInt64,  // shipping_address_hash
-        ]
-    }
-}
-
-// Helper functions for enum conversion
-impl From<OrderStatus> for u8 {
-    fn from(status: OrderStatus) -> Self {
-        match status {
-            OrderStatus::Pending => 0,
-            OrderStatus::Processing => 1,
-            OrderStatus::Shipped => 2,
-            OrderStatus::Delivered => 3,
-        }
-    }
-}
+use soa_persistence::{ArrowPersistence, Expr, MemoryStats, PersistenceError, SoAPersistence};
 
-impl TryFrom<u8> for OrderStatus {
-    type Error = String;
-
-    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
-        match value {
-            0 => Ok(OrderStatus::Pending),
-            1 => Ok(OrderStatus::Processing),
-            2 => Ok(OrderStatus::Shipped),
-            3 => Ok(OrderStatus::Delivered),
-            _ => Err(format!("Invalid OrderStatus value: {}", value)),
-        }
-    }
-}
-
-impl From<PaymentMethod> for u8 {
-    fn from(method: PaymentMethod) -> Self {
-        match method {
-            PaymentMethod::CreditCard => 0,
-            PaymentMethod::PayPal => 1,
-            PaymentMethod::BankTransfer => 2,
-        }
-    }
-}
-
-impl TryFrom<u8> for PaymentMethod {
-    type Error = String;
-
-    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
-        match value {
-            0 => Ok(PaymentMethod::CreditCard),
-            1 => Ok(PaymentMethod::PayPal),
-            2 => Ok(PaymentMethod::BankTransfer),
-            _ => Err(format!("Invalid PaymentMethod value: {}", value)),
-        }
-    }
-}
-
-// Implement ToArrow for OrderSoA
-impl ToArrow for OrderSoA {
-    fn to_record_batch(&self) -> soa_persistence::Result<RecordBatch> {
-        let schema = Self::arrow_schema();
-
-        // Convert enums to u8 vectors
-        let status_u8: Vec<u8> = self.status.iter().map(|&s| s.into()).collect();
-        let payment_u8: Vec<u8> = self.payment_method.iter().map(|&p| p.into()).collect();
-
-        // Create Arrow arrays from SoA vectors (zero-copy where possible)
-        let columns: Vec<Arc<dyn Array>> = vec![
-            Arc::new(UInt64Array::from(self.order_id.clone())),
-            Arc::new(UInt64Array::from(self.customer_id.clone())),
-            Arc::new(UInt64Array::from(self.product_id.clone())),
-            Arc::new(UInt32Array::from(self.quantity.clone())),
-            Arc::new(Float64Array::from(self.unit_price.clone())),
-            Arc::new(Float64Array::from(self.total_amount.clone())),
-            Arc::new(UInt8Array::from(status_u8)),
-            Arc::new(UInt8Array::from(payment_u8)),
-            Arc::new(UInt64Array::from(self.order_timestamp.clone())),
-            Arc::new(UInt64Array::from(self.shipping_address_hash.clone())),
-        ];
-
-        RecordBatch::try_new(schema, columns).map_err(PersistenceError::ArrowError)
-    }
-
-    fn from_record_batch(batch: &RecordBatch) -> soa_persistence::Result<Self> {
-        use soa_persistence::arrow_conversion::downcast_array;
-
-        // Extract columns from Arrow RecordBatch
-        let order_ids = downcast_array::<UInt64Array>(batch.column(0), "order_id")?;
-        let customer_ids = downcast_array::<UInt64Array>(batch.column(1), "customer_id")?;
-        let product_ids = downcast_array::<UInt64Array>(batch.column(2), "product_id")?;
-        let quantities = downcast_array::<UInt32Array>(batch.column(3), "quantity")?;
-        let unit_prices = downcast_array::<Float64Array>(batch.column(4), "unit_price")?;
-        let total_amounts = downcast_array::<Float64Array>(batch.column(5), "total_amount")?;
-        let status_u8 = downcast_array::<UInt8Array>(batch.column(6), "status")?;
-        let payment_u8 = downcast_array::<UInt8Array>(batch.column(7), "payment_method")?;
-        let timestamps = downcast_array::<UInt64Array>(batch.column(8), "order_timestamp")?;
-        let address_hashes =
-            downcast_array::<UInt64Array>(batch.column(9), "shipping_address_hash")?;
-
-        // Convert u8 arrays back to enums
-        let mut statuses = Vec::with_capacity(status_u8.len());
-        let mut payment_methods = Vec::with_capacity(payment_u8.len());
-
-        for i in 0..status_u8.len() {
-            statuses.push(
-                OrderStatus::try_from(status_u8.value(i))
-                    .map_err(|e| PersistenceError::TypeConversion { message: e })?,
-            );
-            payment_methods.push(
-                PaymentMethod::try_from(payment_u8.value(i))
-                    .map_err(|e| PersistenceError::TypeConversion { message: e })?,
-            );
-        }
-
-        // Reconstruct SoA from Arrow data
-        Ok(OrderSoA {
-            order_id: order_ids.values().to_vec(),
-            customer_id: customer_ids.values().to_vec(),
-            product_id: product_ids.values().to_vec(),
-            quantity: quantities.values().to_vec(),
-            unit_price: unit_prices.values().to_vec(),
-            total_amount: total_amounts.values().to_vec(),
-            status: statuses,
-            payment_method: payment_methods,
-            order_timestamp: timestamps.values().to_vec(),
-            shipping_address_hash: address_hashes.values().to_vec(),
-        })
-    }
+/// The columns of `Order` that revenue reports read; loaded with `SoAPersistence::load_as`
+/// without decoding the other fields
+#[derive(Debug, Clone, soa_macros::SoA, soa_macros::SoAArrow)]
+pub struct OrderRevenue {
+    pub status: OrderStatus,
+    pub payment_method: PaymentMethod,
+    pub total_amount: f64,
 }
 
 /// Persistent wrapper for OrderStore with Arrow-based storage
@@ -182,6 +16,12 @@ pub struct PersistentOrderStore {
     persistence: ArrowPersistence<OrderSoA>,
 }
 
+fn duplicate_order(err: soa_runtime::DuplicateKeyError<u64>) -> PersistenceError {
+    PersistenceError::DuplicateKey {
+        key: err.key.to_string(),
+    }
+}
+
 impl Default for PersistentOrderStore {
     fn default() -> Self {
         Self::new()
@@ -205,9 +45,9 @@ impl PersistentOrderStore {
         }
     }
 
-    /// Add an order and persist it
+    /// Add an order and persist it; fails if an order with the same id exists
     pub async fn add(&mut self, order: Order) -> soa_persistence::Result<usize> {
-        let index = self.store.add(order);
+        let index = self.store.add(order).map_err(duplicate_order)?;
 
         // Auto-persist after each addition
         self.persistence.save(self.store.kernel()).await?;
@@ -220,7 +60,7 @@ impl PersistentOrderStore {
         let mut indices = Vec::with_capacity(orders.len());
 
         for order in orders {
-            let index = self.store.add(order);
+            let index = self.store.add(order).map_err(duplicate_order)?;
             indices.push(index);
         }
 
@@ -251,12 +91,14 @@ impl PersistentOrderStore {
         self.persistence.append(self.store.kernel()).await
     }
 
-    /// Query persistent storage with a predicate
-    pub async fn query_storage<F>(&self, predicate: F) -> soa_persistence::Result<Option<OrderSoA>>
-    where
-        F: Fn(&OrderSoA) -> bool + Send + Sync,
-    {
-        self.persistence.query(predicate).await
+    /// Load the persisted orders matching `filter`
+    pub async fn query_storage(&self, filter: &Expr) -> soa_persistence::Result<Option<OrderSoA>> {
+        self.persistence.query(filter).await
+    }
+
+    /// Load the revenue columns of the persisted orders
+    pub async fn load_revenue_columns(&self) -> soa_persistence::Result<Option<OrderRevenueSoA>> {
+        self.persistence.load_as().await
     }
 
     /// Get count of records in persistent storage
@@ -315,3 +157,932 @@ impl PersistentOrderStore {
         self.store.kernel().is_empty()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OrderStatus, PaymentMethod};
+    use ::arrow_schema::{DataType, Field, Schema};
+    use soa_persistence::arrow_conversion::enum_dictionary_type;
+    use soa_persistence::{ArrowField, ArrowSchemaGen, PersistenceError, RecordBatch, ToArrow};
+    use std::sync::Arc;
+
+    fn create_test_soa() -> OrderSoA {
+        let mut soa = OrderSoA::new();
+        soa.push(
+            Order::new_with_payment(1, 100, 200, 2, 50.0, PaymentMethod::CreditCard)
+                .with_status(OrderStatus::Delivered),
+        );
+        soa.push(
+            Order::new_with_payment(2, 101, 201, 1, 75.0, PaymentMethod::PayPal)
+                .with_status(OrderStatus::Shipped),
+        );
+        soa
+    }
+
+    #[test]
+    fn test_derived_arrow_schema() {
+        let schema = OrderSoA::arrow_schema();
+
+        assert_eq!(schema.fields().len(), 10);
+        assert_eq!(OrderSoA::arrow_field_names()[0], "order_id");
+        assert_eq!(schema.field(3).data_type(), &DataType::UInt32);
+        assert_eq!(schema.field(6).data_type(), &enum_dictionary_type());
+    }
+
+    #[test]
+    fn test_record_batch_round_trip() {
+        let soa = create_test_soa();
+        let batch = soa.to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+
+        let restored = OrderSoA::from_record_batch(&batch).unwrap();
+        assert_eq!(restored.order_id_raw_array(), soa.order_id_raw_array());
+        assert_eq!(restored.status_raw_array(), soa.status_raw_array());
+        assert_eq!(
+            restored.payment_method_raw_array(),
+            soa.payment_method_raw_array()
+        );
+        assert_eq!(
+            restored.total_amount_raw_array(),
+            soa.total_amount_raw_array()
+        );
+    }
+
+    #[test]
+    fn test_enum_dictionary_column() {
+        let statuses = [OrderStatus::Delivered, OrderStatus::Pending];
+        let array = OrderStatus::to_array(&statuses);
+
+        assert_eq!(array.data_type(), &enum_dictionary_type());
+        assert_eq!(
+            OrderStatus::from_array(array.as_ref(), "status").unwrap(),
+            statuses
+        );
+    }
+
+    #[test]
+    fn test_unknown_enum_variant_is_rejected() {
+        let names = ::arrow_array::StringArray::from(vec!["Pending", "Refunded"]);
+        let err = OrderStatus::from_array(&names, "status").unwrap_err();
+
+        assert!(matches!(err, PersistenceError::TypeConversion { .. }));
+        assert!(err.to_string().contains("Refunded"));
+    }
+
+    #[test]
+    fn test_columns_resolved_by_name() {
+        let soa = create_test_soa();
+        let batch = soa.to_record_batch().unwrap();
+
+        // Reverse the column order and add a column this build does not know about
+        let schema = batch.schema();
+        let mut fields: Vec<_> = schema.fields().iter().cloned().rev().collect();
+        let mut columns: Vec<_> = batch.columns().iter().cloned().rev().collect();
+        fields.push(Arc::new(Field::new("loyalty_tier", DataType::UInt8, false)));
+        columns.push(Arc::new(::arrow_array::UInt8Array::from(vec![1, 2])));
+        let reordered = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+
+        let restored = OrderSoA::from_record_batch(&reordered).unwrap();
+        assert_eq!(restored.order_id_raw_array(), soa.order_id_raw_array());
+        assert_eq!(restored.quantity_raw_array(), soa.quantity_raw_array());
+        assert_eq!(restored.status_raw_array(), soa.status_raw_array());
+    }
+
+    #[test]
+    fn test_column_type_mismatch() {
+        let soa = create_test_soa();
+        let batch = soa.to_record_batch().unwrap();
+
+        // Store quantity as u64 instead of the expected u32
+        let schema = batch.schema();
+        let index = schema.index_of("quantity").unwrap();
+        let mut fields: Vec<_> = schema.fields().iter().cloned().collect();
+        let mut columns = batch.columns().to_vec();
+        fields[index] = Arc::new(Field::new("quantity", DataType::UInt64, false));
+        columns[index] = Arc::new(::arrow_array::UInt64Array::from(vec![2, 1]));
+        let mismatched = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+
+        match OrderSoA::from_record_batch(&mismatched) {
+            Err(PersistenceError::SchemaMismatch { expected, found }) => {
+                assert!(expected.contains("UInt32"));
+                assert!(found.contains("UInt64"));
+            }
+            Err(other) => panic!("expected SchemaMismatch, got {:?}", other),
+            Ok(_) => panic!("expected SchemaMismatch, decoding succeeded"),
+        }
+
+        let missing = mismatched.project(&[0, 1]).unwrap();
+        assert!(matches!(
+            OrderSoA::from_record_batch(&missing),
+            Err(PersistenceError::ColumnNotFound { .. })
+        ));
+    }
+
+    #[derive(Debug, Clone, soa_macros::SoA, soa_macros::SoAArrow)]
+    pub struct Refund {
+        pub refund_id: u64,
+        pub reason: Option<OrderStatus>,
+        pub approved_at: Option<u64>,
+    }
+
+    #[test]
+    fn test_nullable_column_round_trip() {
+        let mut soa = RefundSoA::new();
+        soa.push(Refund {
+            refund_id: 1,
+            reason: None,
+            approved_at: Some(42),
+        });
+        soa.push(Refund {
+            refund_id: 2,
+            reason: Some(OrderStatus::Shipped),
+            approved_at: None,
+        });
+
+        let schema = RefundSoA::arrow_schema();
+        assert!(!schema.field(0).is_nullable());
+        assert!(schema.field(1).is_nullable());
+
+        let batch = soa.to_record_batch().unwrap();
+        assert_eq!(batch.column(1).null_count(), 1);
+        assert_eq!(batch.column(2).null_count(), 1);
+
+        let restored = RefundSoA::from_record_batch(&batch).unwrap();
+        assert_eq!(restored.view(0).reason, None);
+        assert_eq!(restored.view(0).approved_at, Some(&42));
+        assert_eq!(restored.view(1).reason, Some(&OrderStatus::Shipped));
+        assert_eq!(restored.view(1).approved_at, None);
+    }
+
+    #[derive(Debug, Clone, soa_macros::SoA, soa_macros::SoAArrow)]
+    pub struct Customer {
+        pub customer_id: u64,
+        pub email: String,
+        pub avatar: Vec<u8>,
+        pub nickname: Option<String>,
+    }
+
+    #[test]
+    fn test_variable_length_round_trip() {
+        use arrow::array::{LargeStringArray, StringArray};
+        use arrow::datatypes::DataType;
+
+        let mut soa = CustomerSoA::new();
+        soa.push(Customer {
+            customer_id: 1,
+            email: "ada@example.com".to_string(),
+            avatar: vec![0xff, 0xd8],
+            nickname: None,
+        });
+        soa.push(Customer {
+            customer_id: 2,
+            email: "grace@example.com".to_string(),
+            avatar: Vec::new(),
+            nickname: Some("amazing grace".to_string()),
+        });
+
+        let batch = soa.to_record_batch().unwrap();
+        assert_eq!(batch.column(1).data_type(), &DataType::LargeUtf8);
+        assert_eq!(batch.column(2).data_type(), &DataType::LargeBinary);
+        assert_eq!(batch.column(3).null_count(), 1);
+
+        // The batch shares the column's buffers, and a later write leaves it untouched
+        let emails = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<LargeStringArray>()
+            .unwrap();
+        assert_eq!(
+            emails.value_data().as_ptr(),
+            soa.email_column().values().as_ptr()
+        );
+        soa.set_email(0, "ada@example.org");
+        assert_eq!(emails.value(0), "ada@example.com");
+
+        let restored = CustomerSoA::from_record_batch(&batch).unwrap();
+        assert_eq!(restored.view(1).email, "grace@example.com");
+        assert_eq!(restored.view(0).avatar, &[0xff, 0xd8]);
+        assert_eq!(restored.view(0).nickname, None);
+        assert_eq!(
+            restored.view(1).nickname.map(String::as_str),
+            Some("amazing grace")
+        );
+
+        // A sliced batch starts at a non-zero offset, and `Utf8` is read as well
+        let sliced = batch.slice(1, 1);
+        let emails = sliced
+            .column(1)
+            .as_any()
+            .downcast_ref::<LargeStringArray>()
+            .unwrap();
+        let small = StringArray::from_iter_values(emails.iter().flatten());
+        let mut columns = sliced.columns().to_vec();
+        columns[1] = std::sync::Arc::new(small);
+        let schema = arrow::datatypes::Schema::new(vec![
+            sliced.schema().field(0).clone(),
+            arrow::datatypes::Field::new("email", DataType::Utf8, false),
+            sliced.schema().field(2).clone(),
+            sliced.schema().field(3).clone(),
+        ]);
+        let large_batch =
+            arrow::record_batch::RecordBatch::try_new(std::sync::Arc::new(schema), columns)
+                .unwrap();
+
+        let restored = CustomerSoA::from_record_batch(&large_batch).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored.view(0).email, "grace@example.com");
+        assert_eq!(restored.email_column().offsets(), &[0, 17]);
+    }
+
+    #[derive(Debug, Clone, soa_macros::SoA, soa_macros::SoAArrow)]
+    pub struct Sample<T: soa_persistence::ArrowField> {
+        pub ts: u64,
+        pub value: T,
+    }
+
+    #[test]
+    fn test_generic_struct_round_trip() {
+        let soa: SampleSoA<f32> = (0..3)
+            .map(|i| Sample {
+                ts: i,
+                value: i as f32 * 1.5,
+            })
+            .collect();
+
+        let batch = soa.to_record_batch().unwrap();
+        assert_eq!(
+            batch.schema().field(1).data_type(),
+            &arrow::datatypes::DataType::Float32
+        );
+
+        let restored = SampleSoA::<f32>::from_record_batch(&batch).unwrap();
+        assert_eq!(restored.value_raw_array(), &[0.0, 1.5, 3.0]);
+    }
+
+    #[derive(Debug, Clone, soa_macros::SoA, soa_macros::SoAArrow)]
+    pub struct Visit {
+        #[soa(rename = "visit_key")]
+        pub id: u64,
+        #[soa(skip)]
+        pub cached_label: String,
+        pub duration_ms: u32,
+    }
+
+    #[test]
+    fn test_renamed_and_skipped_columns() {
+        let soa: VisitSoA = (0..2)
+            .map(|i| Visit {
+                id: i,
+                cached_label: "not persisted".to_string(),
+                duration_ms: 100 * i as u32,
+            })
+            .collect();
+
+        assert_eq!(
+            VisitSoA::arrow_field_names(),
+            vec!["visit_key", "duration_ms"]
+        );
+
+        let batch = soa.to_record_batch().unwrap();
+        assert!(batch.column_by_name("visit_key").is_some());
+        assert!(batch.column_by_name("cached_label").is_none());
+
+        let restored = VisitSoA::from_record_batch(&batch).unwrap();
+        assert_eq!(restored.id_raw_array(), &[0, 1]);
+        assert_eq!(restored.get_owned(1).cached_label, "");
+    }
+
+    #[derive(Debug, Clone, soa_macros::SoA, soa_macros::SoAArrow)]
+    #[soa(pack(status, amount))]
+    pub struct Charge {
+        pub charge_id: u64,
+        pub status: OrderStatus,
+        pub amount: f64,
+        #[soa(default = 1)]
+        pub attempts: u32,
+    }
+
+    #[test]
+    fn test_packed_columns_round_trip() {
+        let soa: ChargeSoA = (0..3)
+            .map(|i| Charge {
+                charge_id: i,
+                status: OrderStatus::Shipped,
+                amount: i as f64 * 2.5,
+                attempts: 2,
+            })
+            .collect();
+
+        // Packing only changes the in-memory layout: each field is still its own column
+        assert_eq!(
+            ChargeSoA::arrow_field_names(),
+            vec!["charge_id", "status", "amount", "attempts"]
+        );
+
+        let batch = soa.to_record_batch().unwrap();
+        let restored = ChargeSoA::from_record_batch(&batch).unwrap();
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.amount_strided().to_vec(), vec![0.0, 2.5, 5.0]);
+        assert_eq!(restored.packed_raw_array()[1].status, OrderStatus::Shipped);
+        assert_eq!(restored.attempts_raw_array(), &[2, 2, 2]);
+    }
+
+    mod schema_evolution {
+        use soa_macros::{SoA, SoAArrow};
+        use soa_persistence::{col, ParquetPersistence, PersistenceError, SoAPersistence};
+
+        // The same aggregate as persisted by an older deploy...
+        #[derive(Debug, Clone, SoA, SoAArrow)]
+        pub struct InvoiceV1 {
+            pub invoice_id: u64,
+            pub amount: f64,
+            pub legacy_flags: u32,
+        }
+
+        // ...and after renaming `amount`, adding `priority` and dropping `legacy_flags`
+        #[derive(Debug, Clone, SoA, SoAArrow)]
+        #[soa(version = 2)]
+        pub struct InvoiceV2 {
+            pub invoice_id: u64,
+            #[soa(renamed_from = "amount")]
+            pub total: f64,
+            #[soa(default = 7)]
+            pub priority: u8,
+        }
+
+        // A projection of the current version
+        #[derive(Debug, Clone, SoA, SoAArrow)]
+        pub struct InvoiceTotal {
+            pub total: f64,
+            pub priority: u8,
+        }
+
+        #[tokio::test]
+        async fn test_load_older_schema_version() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let mut v1 = InvoiceV1SoA::new();
+            v1.push(InvoiceV1 {
+                invoice_id: 1,
+                amount: 12.5,
+                legacy_flags: 3,
+            });
+            v1.push(InvoiceV1 {
+                invoice_id: 2,
+                amount: 40.0,
+                legacy_flags: 0,
+            });
+            let mut old_store = ParquetPersistence::<InvoiceV1SoA>::new(dir.path());
+            old_store.save(&v1).await.unwrap();
+
+            let mut new_store = ParquetPersistence::<InvoiceV2SoA>::new(dir.path());
+            let v2 = new_store.load().await.unwrap().unwrap();
+
+            assert_eq!(v2.invoice_id_raw_array(), &[1, 2]);
+            assert_eq!(v2.total_raw_array(), &[12.5, 40.0]);
+            assert_eq!(v2.priority_raw_array(), &[7, 7]);
+
+            // Parts of both versions load together, and compaction rewrites them as version 2
+            let mut appended = InvoiceV2SoA::new();
+            appended.push(InvoiceV2 {
+                invoice_id: 3,
+                total: 5.0,
+                priority: 1,
+            });
+            new_store.append(&appended).await.unwrap();
+            for merged in [1, 0] {
+                let v2 = new_store.load().await.unwrap().unwrap();
+                assert_eq!(v2.invoice_id_raw_array(), &[1, 2, 3]);
+                assert_eq!(v2.total_raw_array(), &[12.5, 40.0, 5.0]);
+                assert_eq!(v2.priority_raw_array(), &[7, 7, 1]);
+                assert_eq!(new_store.compact().await.unwrap(), merged);
+            }
+            assert!(matches!(
+                old_store.load().await,
+                Err(PersistenceError::SchemaMismatch { .. })
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_query_older_schema_version() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let mut v1 = InvoiceV1SoA::new();
+            v1.push(InvoiceV1 {
+                invoice_id: 1,
+                amount: 12.5,
+                legacy_flags: 3,
+            });
+            v1.push(InvoiceV1 {
+                invoice_id: 2,
+                amount: 40.0,
+                legacy_flags: 0,
+            });
+            ParquetPersistence::<InvoiceV1SoA>::new(dir.path())
+                .save(&v1)
+                .await
+                .unwrap();
+
+            // Filters name the current columns: `total` is read from the renamed `amount`
+            // and the missing `priority` takes its default
+            let mut store = ParquetPersistence::<InvoiceV2SoA>::new(dir.path());
+            let mut appended = InvoiceV2SoA::new();
+            appended.push(InvoiceV2 {
+                invoice_id: 3,
+                total: 25.0,
+                priority: 1,
+            });
+            store.append(&appended).await.unwrap();
+
+            let large = col("total").gt(20.0);
+            let matching = store.query(&large).await.unwrap().unwrap();
+            assert_eq!(matching.invoice_id_raw_array(), &[2, 3]);
+            assert_eq!(matching.total_raw_array(), &[40.0, 25.0]);
+            assert_eq!(matching.priority_raw_array(), &[7, 1]);
+            let plan = store.scan_plan(&large).await.unwrap().unwrap();
+            assert_eq!(plan.total_rows, 3);
+
+            let defaulted = col("priority").eq(7u8);
+            let matching = store.query(&defaulted).await.unwrap().unwrap();
+            assert_eq!(matching.invoice_id_raw_array(), &[1, 2]);
+            assert_eq!(matching.total_raw_array(), &[12.5, 40.0]);
+            let plan = store.scan_plan(&defaulted).await.unwrap().unwrap();
+            assert_eq!(plan.row_groups, vec![0]);
+
+            let both = col("priority").eq(7u8).and(col("total").lt(20.0));
+            let matching = store.query(&both).await.unwrap().unwrap();
+            assert_eq!(matching.invoice_id_raw_array(), &[1]);
+
+            // Dropped columns are no longer part of the schema
+            assert!(matches!(
+                store.query(&col("legacy_flags").eq(0u32)).await,
+                Err(PersistenceError::ColumnNotFound { .. })
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_load_as_mixed_schema_versions() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let mut v1 = InvoiceV1SoA::new();
+            v1.push(InvoiceV1 {
+                invoice_id: 1,
+                amount: 12.5,
+                legacy_flags: 3,
+            });
+            ParquetPersistence::<InvoiceV1SoA>::new(dir.path())
+                .save(&v1)
+                .await
+                .unwrap();
+            let mut store = ParquetPersistence::<InvoiceV2SoA>::new(dir.path());
+            let mut appended = InvoiceV2SoA::new();
+            appended.push(InvoiceV2 {
+                invoice_id: 2,
+                total: 5.0,
+                priority: 1,
+            });
+            store.append(&appended).await.unwrap();
+
+            // `total` is read from the renamed `amount` of the first part, and `priority`
+            // takes its default there
+            let totals = store.load_as::<InvoiceTotalSoA>().await.unwrap().unwrap();
+            assert_eq!(totals.total_raw_array(), &[12.5, 5.0]);
+            assert_eq!(totals.priority_raw_array(), &[7, 1]);
+
+            let batch = store
+                .load_columns(&["total", "invoice_id"])
+                .await
+                .unwrap()
+                .unwrap();
+            let schema = batch.schema();
+            let names: Vec<_> = schema.fields().iter().map(|f| f.name()).collect();
+            assert_eq!(names, vec!["total", "invoice_id"]);
+            assert_eq!(batch.num_rows(), 2);
+
+            assert!(matches!(
+                store.load_columns(&["legacy_flags"]).await,
+                Err(PersistenceError::ColumnNotFound { .. })
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_reject_newer_schema_version() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let mut v2 = InvoiceV2SoA::new();
+            v2.push(InvoiceV2 {
+                invoice_id: 1,
+                total: 12.5,
+                priority: 1,
+            });
+            let mut new_store = ParquetPersistence::<InvoiceV2SoA>::new(dir.path());
+            new_store.save(&v2).await.unwrap();
+
+            let old_store = ParquetPersistence::<InvoiceV1SoA>::new(dir.path());
+            assert!(matches!(
+                old_store.load().await,
+                Err(PersistenceError::SchemaMismatch { .. })
+            ));
+        }
+    }
+
+    mod reading {
+        use crate::persistence::OrderRevenueSoA;
+        use crate::{Order, OrderSoA, OrderStatus, PaymentMethod};
+        use soa_persistence::{
+            col, ArrowPersistence, ParquetPersistence, PersistenceError, SoABatchPersistence,
+            SoAPersistence, ToArrow,
+        };
+        use tokio_stream::StreamExt;
+
+        /// Orders with ascending timestamps; status cycles every 4 rows, payment every 3
+        fn create_orders(n: u64) -> OrderSoA {
+            (0..n)
+                .map(|i| {
+                    let payment = match i % 3 {
+                        0 => PaymentMethod::CreditCard,
+                        1 => PaymentMethod::PayPal,
+                        _ => PaymentMethod::BankTransfer,
+                    };
+                    let status = match i % 4 {
+                        0 => OrderStatus::Pending,
+                        1 => OrderStatus::Shipped,
+                        _ => OrderStatus::Delivered,
+                    };
+                    Order {
+                        order_timestamp: 1_000_000 + i,
+                        ..Order::new_with_payment(i, i % 50, i % 7, 1, (i % 200) as f64, payment)
+                            .with_status(status)
+                    }
+                })
+                .collect()
+        }
+
+        #[test]
+        fn test_expr_evaluate() {
+            let batch = create_orders(8).to_record_batch().unwrap();
+
+            let delivered = col("status").eq(OrderStatus::Delivered);
+            let mask = delivered.evaluate(&batch).unwrap();
+            let rows: Vec<_> = (0..8).filter(|&i| mask.value(i)).collect();
+            assert_eq!(rows, vec![2, 3, 6, 7]);
+
+            // Enums order by declaration, not by variant name ("Delivered" < "Pending")
+            let early = col("status").lt(OrderStatus::Shipped);
+            let mask = early.evaluate(&batch).unwrap();
+            let rows: Vec<_> = (0..8).filter(|&i| mask.value(i)).collect();
+            assert_eq!(rows, vec![0, 4]);
+            let later = col("status").gt(OrderStatus::Pending);
+            assert_eq!(later.filter(&batch).unwrap().num_rows(), 6);
+
+            // `!` is pushed down to the comparisons; integer literals are cast to the column
+            let filter = !(col("quantity").gt(0).and(col("order_id").lt_eq(3u64)));
+            assert_eq!(filter.filter(&batch).unwrap().num_rows(), 4);
+            assert_eq!(filter.columns(), vec!["quantity", "order_id"]);
+
+            assert!(matches!(
+                col("discount").eq(1.0).evaluate(&batch),
+                Err(PersistenceError::ColumnNotFound { .. })
+            ));
+            assert!(matches!(
+                col("quantity").eq(-1).evaluate(&batch),
+                Err(PersistenceError::TypeConversion { .. })
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_arrow_query() {
+            let mut store = ArrowPersistence::<OrderSoA>::new();
+            let filter = col("status")
+                .eq(OrderStatus::Delivered)
+                .and(col("total_amount").gt(100.0));
+            assert!(store.query(&filter).await.unwrap().is_none());
+
+            let orders = create_orders(1_000);
+            store.save(&orders).await.unwrap();
+            store.append(&create_orders(10)).await.unwrap();
+
+            let matching = store.query(&filter).await.unwrap().unwrap();
+            let expected = orders
+                .iter()
+                .filter(|o| *o.status == OrderStatus::Delivered && *o.total_amount > 100.0)
+                .count();
+            assert_eq!(matching.len(), expected);
+            assert!(matching
+                .iter()
+                .all(|o| *o.status == OrderStatus::Delivered && *o.total_amount > 100.0));
+
+            let none = col("order_id").gt(1_000_000u64);
+            assert!(store.query(&none).await.unwrap().unwrap().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_load_columns() {
+            let dir = tempfile::tempdir().unwrap();
+            let orders = create_orders(5_000);
+            let mut parquet = ParquetPersistence::<OrderSoA>::new(dir.path());
+            let mut arrow = ArrowPersistence::<OrderSoA>::new();
+            assert!(parquet.load_columns(&["status"]).await.unwrap().is_none());
+            assert!(arrow.load_as::<OrderRevenueSoA>().await.unwrap().is_none());
+            parquet.save(&orders).await.unwrap();
+            arrow.save(&orders).await.unwrap();
+
+            for batch in [
+                parquet.load_columns(&["total_amount", "status"]).await,
+                arrow.load_columns(&["total_amount", "status"]).await,
+            ] {
+                let batch = batch.unwrap().unwrap();
+                assert_eq!(batch.num_rows(), 5_000);
+                // Columns come back in the requested order, not the stored one
+                let schema = batch.schema();
+                let names: Vec<_> = schema.fields().iter().map(|f| f.name()).collect();
+                assert_eq!(names, vec!["total_amount", "status"]);
+            }
+
+            for revenue in [
+                parquet.load_as::<OrderRevenueSoA>().await,
+                arrow.load_as::<OrderRevenueSoA>().await,
+            ] {
+                let revenue = revenue.unwrap().unwrap();
+                assert_eq!(
+                    revenue.total_amount_raw_array(),
+                    orders.total_amount_raw_array()
+                );
+                assert_eq!(revenue.status_raw_array(), orders.status_raw_array());
+                assert_eq!(
+                    revenue.payment_method_raw_array(),
+                    orders.payment_method_raw_array()
+                );
+            }
+
+            assert!(matches!(
+                parquet.load_columns(&["status", "discount"]).await,
+                Err(PersistenceError::ColumnNotFound { column_name }) if column_name == "discount"
+            ));
+            assert!(matches!(
+                arrow.load_columns(&["discount"]).await,
+                Err(PersistenceError::ColumnNotFound { .. })
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_stream_bounded_chunks() {
+            let dir = tempfile::tempdir().unwrap();
+            let orders = create_orders(10_050);
+            let mut parquet = ParquetPersistence::<OrderSoA>::new(dir.path());
+            let mut arrow = ArrowPersistence::<OrderSoA>::new();
+            assert!(parquet.stream(1_000).next().await.is_none());
+            parquet.save(&orders).await.unwrap();
+            arrow.save(&orders).await.unwrap();
+
+            for mut chunks in [parquet.stream(1_000), arrow.stream(1_000)] {
+                let mut order_ids = Vec::new();
+                let mut count = 0;
+                while let Some(chunk) = chunks.next().await {
+                    let chunk = chunk.unwrap();
+                    assert!(!chunk.is_empty() && chunk.len() <= 1_000);
+                    order_ids.extend_from_slice(chunk.order_id_raw_array());
+                    count += 1;
+                }
+                assert_eq!(count, 11);
+                assert_eq!(order_ids, orders.order_id_raw_array());
+            }
+
+            // Dropping the stream after the first chunk stops the reader
+            let mut chunks = parquet.stream(100);
+            assert_eq!(chunks.next().await.unwrap().unwrap().len(), 100);
+            drop(chunks);
+
+            // `load_batches` counts rows, and chunks never span two stored batches
+            arrow.append(&create_orders(10)).await.unwrap();
+            let sizes: Vec<_> = arrow
+                .load_batches(4_000)
+                .await
+                .unwrap()
+                .iter()
+                .map(|chunk| chunk.len())
+                .collect();
+            assert_eq!(sizes, vec![4_000, 4_000, 2_050, 10]);
+        }
+
+        #[tokio::test]
+        async fn test_parquet_query_pushdown() {
+            let dir = tempfile::tempdir().unwrap();
+            let orders = create_orders(100_000);
+            let mut store = ParquetPersistence::<OrderSoA>::new(dir.path())
+                .with_row_group_size(10_000)
+                .with_page_size(4 * 1024);
+            assert!(store
+                .query(&col("order_id").eq(1u64))
+                .await
+                .unwrap()
+                .is_none());
+            store.save(&orders).await.unwrap();
+
+            // Timestamps ascend, so row group statistics rule out all but the first 3 groups
+            // and the page index narrows the third one further
+            let recent = col("order_timestamp")
+                .lt(1_025_000u64)
+                .and(col("status").eq(OrderStatus::Delivered));
+            let plan = store.scan_plan(&recent).await.unwrap().unwrap();
+            assert_eq!(plan.total_row_groups, 10);
+            assert_eq!(plan.row_groups, vec![0, 1, 2]);
+            assert!(plan.selected_rows < 30_000);
+            assert!(plan.selected_rows >= 25_000);
+
+            let matching = store.query(&recent).await.unwrap().unwrap();
+            assert_eq!(matching.len(), 12_500);
+            assert!(matching
+                .iter()
+                .all(|o| *o.order_timestamp < 1_025_000 && *o.status == OrderStatus::Delivered));
+
+            // Either side of an `or` keeps its row groups
+            let ends = col("order_id")
+                .lt(5u64)
+                .or(col("order_id").gt_eq(99_995u64));
+            let plan = store.scan_plan(&ends).await.unwrap().unwrap();
+            assert_eq!(plan.row_groups, vec![0, 9]);
+            let matching = store.query(&ends).await.unwrap().unwrap();
+            assert_eq!(
+                matching.order_id_raw_array(),
+                &[0, 1, 2, 3, 4, 99_995, 99_996, 99_997, 99_998, 99_999]
+            );
+
+            // Enum ordering follows declaration order and is not pruned by name statistics
+            let early = col("status").lt(OrderStatus::Shipped);
+            let plan = store.scan_plan(&early).await.unwrap().unwrap();
+            assert_eq!(plan.selected_rows, plan.total_rows);
+            let matching = store.query(&early).await.unwrap().unwrap();
+            assert_eq!(matching.len(), 25_000);
+            assert!(matching.iter().all(|o| *o.status == OrderStatus::Pending));
+            let late = col("status").gt_eq(OrderStatus::Shipped);
+            assert_eq!(store.query(&late).await.unwrap().unwrap().len(), 75_000);
+
+            // `>` on a float column cannot be pruned (NaN is not in the statistics) but is
+            // still evaluated exactly
+            let high_value = col("total_amount").gt(198.0);
+            let plan = store.scan_plan(&high_value).await.unwrap().unwrap();
+            assert_eq!(plan.selected_rows, plan.total_rows);
+            assert_eq!(store.query(&high_value).await.unwrap().unwrap().len(), 500);
+
+            assert!(matches!(
+                store.query(&col("discount").gt(0.5)).await,
+                Err(PersistenceError::ColumnNotFound { .. })
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_parquet_append_parts_and_compact() {
+            let dir = tempfile::tempdir().unwrap();
+            let orders = create_orders(1_000);
+            let mut store =
+                ParquetPersistence::<OrderSoA>::new(dir.path()).with_target_part_rows(250);
+            assert_eq!(store.compact().await.unwrap(), 0);
+
+            // Each append adds a part file and leaves the earlier ones untouched
+            for start in (0..1_000).step_by(100) {
+                let chunk: OrderSoA = (start..start + 100).map(|i| orders.get_owned(i)).collect();
+                store.append(&chunk).await.unwrap();
+            }
+            store.append(&OrderSoA::new()).await.unwrap();
+            let manifest = store.manifest().await.unwrap();
+            assert_eq!(manifest.parts.len(), 10);
+            assert_eq!(manifest.parts[9].file, "part-00009.parquet");
+            assert!(manifest
+                .parts
+                .iter()
+                .all(|part| part.rows == 100 && dir.path().join(&part.file).exists()));
+
+            assert_eq!(store.count().await.unwrap(), 1_000);
+            let loaded = store.load().await.unwrap().unwrap();
+            assert_eq!(loaded.order_id_raw_array(), orders.order_id_raw_array());
+            let plan = store
+                .scan_plan(&col("order_id").gt_eq(950u64))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(plan.total_row_groups, 10);
+            assert_eq!(plan.row_groups, vec![9]);
+
+            // Runs of at most 250 rows merge into one part each, keeping row order
+            assert_eq!(store.compact().await.unwrap(), 5);
+            let manifest = store.manifest().await.unwrap();
+            let rows: Vec<_> = manifest.parts.iter().map(|part| part.rows).collect();
+            assert_eq!(rows, vec![200, 200, 200, 200, 200]);
+            // Five parts, the manifest and its lock file
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 7);
+            let loaded = store.load().await.unwrap().unwrap();
+            assert_eq!(loaded.order_id_raw_array(), orders.order_id_raw_array());
+            assert_eq!(store.compact().await.unwrap(), 0);
+
+            // `save` replaces every part
+            store.save(&create_orders(10)).await.unwrap();
+            assert_eq!(store.manifest().await.unwrap().parts.len(), 1);
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+            assert_eq!(store.count().await.unwrap(), 10);
+
+            store.clear().await.unwrap();
+            assert!(store.load().await.unwrap().is_none());
+            assert!(store.is_empty().await.unwrap());
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn test_parquet_concurrent_writers() {
+            let dir = tempfile::tempdir().unwrap();
+            let orders = create_orders(400);
+            let chunk = |n: usize| -> OrderSoA {
+                (n * 10..n * 10 + 10).map(|i| orders.get_owned(i)).collect()
+            };
+
+            // Two handles on one directory append at the same time without losing parts
+            let mut first = ParquetPersistence::<OrderSoA>::new(dir.path());
+            let mut second = ParquetPersistence::<OrderSoA>::new(dir.path());
+            let (a, b) = tokio::join!(
+                async {
+                    for n in 0..10 {
+                        first.append(&chunk(n)).await?;
+                    }
+                    Ok::<_, PersistenceError>(())
+                },
+                async {
+                    for n in 10..20 {
+                        second.append(&chunk(n)).await?;
+                    }
+                    Ok::<_, PersistenceError>(())
+                },
+            );
+            a.unwrap();
+            b.unwrap();
+            assert_eq!(first.manifest().await.unwrap().parts.len(), 20);
+            assert_eq!(first.count().await.unwrap(), 200);
+
+            // Compaction runs in the background while appends go on, and keeps their parts
+            let store = first.with_target_part_rows(1_000);
+            let compaction = store.compact();
+            for n in 20..40 {
+                second.append(&chunk(n)).await.unwrap();
+            }
+            let merged = compaction.await.unwrap();
+            assert!(merged >= 19);
+            let manifest = store.manifest().await.unwrap();
+            assert_eq!(manifest.rows(), 400);
+            assert_eq!(manifest.parts.len(), 40 - merged);
+
+            let mut ids = store
+                .load()
+                .await
+                .unwrap()
+                .unwrap()
+                .order_id_raw_array()
+                .to_vec();
+            ids.sort_unstable();
+            assert_eq!(ids, orders.order_id_raw_array());
+
+            // A compaction finishes even when nobody awaits it
+            drop(store.compact());
+            let files = || {
+                std::fs::read_dir(dir.path())
+                    .unwrap()
+                    .filter(|entry| {
+                        let name = entry.as_ref().unwrap().file_name();
+                        name.to_string_lossy().ends_with(".parquet")
+                    })
+                    .count()
+            };
+            for _ in 0..500 {
+                if store.manifest().await.unwrap().parts.len() == 1 && files() == 1 {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            assert_eq!(store.manifest().await.unwrap().parts.len(), 1);
+            assert_eq!(files(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_parquet_legacy_single_file() {
+            let dir = tempfile::tempdir().unwrap();
+            let orders = create_orders(100);
+            let mut store = ParquetPersistence::<OrderSoA>::new(dir.path());
+            store.save(&orders).await.unwrap();
+
+            // The layout before part files: one `data.parquet` and no manifest
+            std::fs::rename(
+                dir.path().join("part-00000.parquet"),
+                dir.path().join("data.parquet"),
+            )
+            .unwrap();
+            std::fs::remove_file(dir.path().join("_manifest")).unwrap();
+
+            assert_eq!(store.count().await.unwrap(), 100);
+            store.append(&create_orders(5)).await.unwrap();
+            assert_eq!(store.count().await.unwrap(), 105);
+            assert_eq!(store.load().await.unwrap().unwrap().len(), 105);
+
+            assert_eq!(store.compact().await.unwrap(), 1);
+            assert!(!dir.path().join("data.parquet").exists());
+            assert_eq!(store.load().await.unwrap().unwrap().len(), 105);
+        }
+    }
+}
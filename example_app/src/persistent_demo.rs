@@ -1,5 +1,6 @@
 use example_app::persistence::PersistentOrderStore;
 use example_app::{Order, OrderStatus, PaymentMethod};
+use soa_persistence::col;
 
 /// Demonstration of persistent SoA store with Arrow backend
 #[tokio::main]
@@ -55,25 +56,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Query for delivered orders
     let delivered_query = persistent_store
-        .query_storage(|soa| soa.status_raw_array().contains(&OrderStatus::Delivered))
+        .query_storage(&col("status").eq(OrderStatus::Delivered))
         .await?;
 
     if let Some(delivered_orders) = delivered_query {
-        let delivered_count = delivered_orders
-            .status_raw_array()
-            .iter()
-            .filter(|&&status| status == OrderStatus::Delivered)
-            .count();
-        println!("  Found {} delivered orders in storage", delivered_count);
+        println!(
+            "  Found {} delivered orders in storage",
+            delivered_orders.len()
+        );
 
         // Calculate revenue from delivered orders
-        let delivered_revenue: f64 = delivered_orders
-            .status_raw_array()
-            .iter()
-            .zip(delivered_orders.total_amount_raw_array().iter())
-            .filter(|(&status, _)| status == OrderStatus::Delivered)
-            .map(|(_, &amount)| amount)
-            .sum();
+        let delivered_revenue: f64 = delivered_orders.total_amount_raw_array().iter().sum();
 
         println!("  Total delivered revenue: ${:.2}", delivered_revenue);
     }
@@ -146,37 +139,18 @@ async fn demonstrate_queries(
 
     // Query by payment method
     let credit_card_query = store
-        .query_storage(|soa| {
-            soa.payment_method_raw_array()
-                .contains(&PaymentMethod::CreditCard)
-        })
+        .query_storage(&col("payment_method").eq(PaymentMethod::CreditCard))
         .await?;
 
     if let Some(cc_orders) = credit_card_query {
-        let cc_count = cc_orders
-            .payment_method_raw_array()
-            .iter()
-            .filter(|&&method| method == PaymentMethod::CreditCard)
-            .count();
-        println!("  Credit card orders: {}", cc_count);
+        println!("  Credit card orders: {}", cc_orders.len());
     }
 
     // Query high-value orders
-    let high_value_query = store
-        .query_storage(|soa| {
-            soa.total_amount_raw_array()
-                .iter()
-                .any(|&amount| amount > 200.0)
-        })
-        .await?;
+    let high_value_query = store.query_storage(&col("total_amount").gt(200.0)).await?;
 
     if let Some(high_value) = high_value_query {
-        let high_value_count = high_value
-            .total_amount_raw_array()
-            .iter()
-            .filter(|&&amount| amount > 200.0)
-            .count();
-        println!("  High-value orders (>$200): {}", high_value_count);
+        println!("  High-value orders (>$200): {}", high_value.len());
     }
 
     Ok(())
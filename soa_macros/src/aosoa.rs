@@ -0,0 +1,211 @@
+//! Tiled layout emitted by `#[derive(SoA)]` for `#[soa(layout = "aosoa", lanes = N)]`.
+//!
+//! `{Ident}Tile` holds `N` consecutive rows as one `soa_runtime::Lanes` array per field, and
+//! `{Ident}AoSoA` is a vector of tiles: scans stay columnar within a tile while every field
+//! of a row lives within one contiguous block. Fields must be `Copy + Default`
+//! (`soa_runtime::LaneValue`): lanes are copied in and out, and the unused lanes of the last
+//! tile hold `Default::default()`.
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::{Generics, Ident, LitInt, LitStr, Type, Visibility};
+
+use crate::column::ColumnKind;
+
+/// Rows per tile when `lanes` is not given
+const DEFAULT_LANES: usize = 8;
+
+pub(crate) fn expand(
+    ident: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
+    layout: &LitStr,
+    lanes: Option<&LitInt>,
+    fields: &[(&Ident, &Type)],
+    skipped_defaults: &[TokenStream],
+) -> syn::Result<TokenStream> {
+    if !generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            generics,
+            "`#[soa(layout = \"aosoa\")]` is not supported on generic structs",
+        ));
+    }
+    for (id, ty) in fields {
+        if !matches!(ColumnKind::of(ty), ColumnKind::Plain) {
+            return Err(syn::Error::new_spanned(
+                id,
+                "AoSoA tiles hold fixed-width fields only; mark `Option`, `String` and \
+                 `Vec<u8>` fields `#[soa(skip)]` or use the default layout",
+            ));
+        }
+    }
+
+    let lanes = match lanes {
+        Some(lit) => lit.base10_parse::<usize>()?,
+        None => DEFAULT_LANES,
+    };
+
+    let tile_ident = format_ident!("{}Tile", ident);
+    let layout_ident = format_ident!("{}AoSoA", ident);
+    let soa_ident = format_ident!("{}SoA", ident);
+    let (field_idents, field_types): (Vec<_>, Vec<_>) = fields.iter().copied().unzip();
+
+    let tile_doc = format!(
+        "{} consecutive rows of [`{}`], one cache-line aligned lane array per field",
+        lanes, ident
+    );
+    let layout_doc = format!(
+        "Array of [`{}`]s: [`{}`] rows stored {} per tile",
+        tile_ident, ident, lanes
+    );
+
+    // Spanned at `layout = "aosoa"` so a field type that is not `Copy + Default` is reported there
+    let lane_value_checks = field_types.iter().map(|ty| {
+        let ty = respanned(ty.to_token_stream(), layout.span());
+        quote_spanned! {layout.span()=>
+            soa_runtime::assert_lane_value::<#ty>();
+        }
+    });
+
+    Ok(quote! {
+        const _: fn() = || {
+            #( #lane_value_checks )*
+        };
+
+        #[doc = #tile_doc]
+        #[derive(Clone, Copy)]
+        #vis struct #tile_ident {
+            #( pub #field_idents: soa_runtime::Lanes<#field_types, #lanes>, )*
+        }
+
+        #[doc = #layout_doc]
+        #[derive(Clone, Default)]
+        #vis struct #layout_ident {
+            tiles: ::std::vec::Vec<#tile_ident>,
+            len: usize,
+        }
+
+        impl #layout_ident {
+            /// Rows per tile
+            #vis const LANES: usize = #lanes;
+
+            #vis fn new() -> Self {
+                Self::default()
+            }
+            #vis fn with_capacity(cap: usize) -> Self {
+                Self {
+                    tiles: ::std::vec::Vec::with_capacity(cap.div_ceil(Self::LANES)),
+                    len: 0,
+                }
+            }
+            #vis fn len(&self) -> usize {
+                self.len
+            }
+            #vis fn is_empty(&self) -> bool {
+                self.len == 0
+            }
+            /// Append a row. Unused lanes of the last tile hold `Default::default()`, so
+            /// whole-tile sums and counts of non-default values stay exact
+            #vis fn push(&mut self, v: #ident) -> usize {
+                let lane = self.len % Self::LANES;
+                if lane == 0 {
+                    self.tiles.push(#tile_ident {
+                        #(
+                            #field_idents: soa_runtime::Lanes(
+                                [<#field_types as ::std::default::Default>::default(); #lanes],
+                            ),
+                        )*
+                    });
+                }
+                let tile = self.tiles.last_mut().unwrap();
+                #( tile.#field_idents[lane] = v.#field_idents; )*
+                self.len += 1;
+                self.len - 1
+            }
+            /// Copy of row `i`; panics if `i` is out of bounds
+            #vis fn get_owned(&self, i: usize) -> #ident {
+                assert!(i < self.len, "row {} out of bounds (len {})", i, self.len);
+                let tile = &self.tiles[i / Self::LANES];
+                let lane = i % Self::LANES;
+                #ident {
+                    #( #field_idents: tile.#field_idents[lane], )*
+                    #( #skipped_defaults, )*
+                }
+            }
+            /// Rows in order
+            #vis fn iter(&self) -> impl ::std::iter::ExactSizeIterator<Item = #ident> + '_ {
+                (0..self.len).map(|i| self.get_owned(i))
+            }
+            #vis fn clear(&mut self) {
+                self.tiles.clear();
+                self.len = 0;
+            }
+            /// Raw tiles; only the first [`Self::tile_len`] lanes of each tile are rows
+            #vis fn tiles(&self) -> &[#tile_ident] {
+                &self.tiles
+            }
+            #vis fn tiles_mut(&mut self) -> &mut [#tile_ident] {
+                &mut self.tiles
+            }
+            /// Rows held by tile `t`: `LANES` for every tile except a partially filled last one
+            #vis fn tile_len(&self, t: usize) -> usize {
+                assert!(t < self.tiles.len(), "tile {} out of bounds ({} tiles)", t, self.tiles.len());
+                (self.len - t * Self::LANES).min(Self::LANES)
+            }
+            /// Each tile together with the number of rows it holds
+            #vis fn iter_tiles(
+                &self,
+            ) -> impl ::std::iter::DoubleEndedIterator<Item = (&#tile_ident, usize)>
+                   + ::std::iter::ExactSizeIterator
+                   + '_ {
+                self.tiles
+                    .iter()
+                    .enumerate()
+                    .map(|(t, tile)| (tile, self.tile_len(t)))
+            }
+        }
+
+        impl ::std::convert::From<&#soa_ident> for #layout_ident {
+            fn from(soa: &#soa_ident) -> Self {
+                (0..soa.len()).map(|i| soa.get_owned(i)).collect()
+            }
+        }
+
+        impl ::std::iter::Extend<#ident> for #layout_ident {
+            fn extend<I: ::std::iter::IntoIterator<Item = #ident>>(&mut self, iter: I) {
+                let iter = iter.into_iter();
+                let rows = self.len + iter.size_hint().0;
+                self.tiles
+                    .reserve(rows.div_ceil(Self::LANES).saturating_sub(self.tiles.len()));
+                for v in iter {
+                    self.push(v);
+                }
+            }
+        }
+
+        impl ::std::iter::FromIterator<#ident> for #layout_ident {
+            fn from_iter<I: ::std::iter::IntoIterator<Item = #ident>>(iter: I) -> Self {
+                let mut layout = Self::new();
+                layout.extend(iter);
+                layout
+            }
+        }
+    })
+}
+
+/// `tokens` with every token, including those inside groups, moved to `span`
+fn respanned(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut tree| {
+            if let TokenTree::Group(group) = &tree {
+                let mut inner =
+                    proc_macro2::Group::new(group.delimiter(), respanned(group.stream(), span));
+                inner.set_span(span);
+                tree = TokenTree::Group(inner);
+            }
+            tree.set_span(span);
+            tree
+        })
+        .collect()
+}
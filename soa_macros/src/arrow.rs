@@ -0,0 +1,318 @@
+//! Expansion for `#[derive(SoAArrow)]`.
+//!
+//! On a struct, generates `ArrowSchemaGen` and `ToArrow` for the `*SoA` type produced
+//! by `#[derive(SoA)]`, mapping each stored field (named by `#[soa(rename = "...")]` if
+//! given) through `soa_persistence::ArrowField`. `Option<T>` fields map `T` onto a
+//! nullable column; `String` / `Vec<u8>` columns share their offsets and values buffers
+//! with `LargeUtf8` / `LargeBinary` arrays without copying. Fields packed by
+//! `#[soa(pack(...))]` are still persisted as one Arrow column each.
+//! On a fieldless enum deriving `SoAEnum`, generates `ArrowField` storing the enum as a
+//! `Dictionary(UInt8, Utf8)` column of variant names.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields};
+
+use crate::attrs::{FieldAttrs, StructAttrs};
+use crate::column::ColumnKind;
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    if let Data::Enum(_) = input.data {
+        return expand_enum(input);
+    }
+
+    let ident = input.ident;
+
+    let fields = match input.data {
+        Data::Struct(s) => match s.fields {
+            Fields::Named(named) => named.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    s.fields,
+                    "SoAArrow derive requires a braced struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &ident,
+                "SoAArrow derive works only on structs and fieldless enums",
+            ))
+        }
+    };
+
+    let struct_attrs = StructAttrs::parse(&input.attrs)?;
+    // `#[soa(skip)]` fields have no column in the `*SoA`, so they are not persisted either
+    let (fields, field_attrs): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .map(|f| FieldAttrs::parse(&f.attrs).map(|attrs| (f, attrs)))
+        .collect::<syn::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|(_, attrs)| !attrs.skip)
+        .unzip();
+
+    let field_idents: Vec<_> = fields.iter().map(|f| f.ident.clone().unwrap()).collect();
+    let field_types: Vec<_> = fields.iter().map(|f| f.ty.clone()).collect();
+    let column_names: Vec<_> = field_idents
+        .iter()
+        .zip(field_attrs.iter())
+        .map(|(id, attrs)| attrs.column_name(id))
+        .collect();
+    let column_indices = 0..field_idents.len();
+    let packed: Vec<bool> = field_idents
+        .iter()
+        .map(|id| struct_attrs.pack.contains(id))
+        .collect();
+
+    let soa_ident = format_ident!("{}SoA", ident);
+    // Generic field types need their `ArrowField` bounds on the struct itself
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let kinds: Vec<_> = field_types.iter().map(ColumnKind::of).collect();
+
+    // Arrow columns hold the value type; `Option<T>` fields become nullable `T` columns
+    let value_types: Vec<_> = field_types
+        .iter()
+        .zip(kinds.iter())
+        .map(|(ty, kind)| match kind {
+            ColumnKind::Plain | ColumnKind::Utf8 | ColumnKind::Binary => ty,
+            ColumnKind::Nullable(inner) => *inner,
+        })
+        .collect();
+
+    let schema_fields = column_names
+        .iter()
+        .zip(value_types.iter())
+        .zip(kinds.iter())
+        .map(|((name, ty), kind)| {
+            let nullable = matches!(kind, ColumnKind::Nullable(_));
+            quote! {
+                ::soa_persistence::Field::new(
+                    #name,
+                    <#ty as ::soa_persistence::ArrowField>::data_type(),
+                    #nullable,
+                )
+            }
+        });
+
+    let data_types = value_types.iter().map(|ty| {
+        quote! { <#ty as ::soa_persistence::ArrowField>::data_type() }
+    });
+
+    let to_columns = field_idents
+        .iter()
+        .zip(value_types.iter())
+        .zip(kinds.iter())
+        .zip(packed.iter())
+        .map(|(((id, ty), kind), &is_packed)| match kind {
+            ColumnKind::Plain if is_packed => {
+                let strided = format_ident!("{}_strided", id);
+                quote! {
+                    <#ty as ::soa_persistence::ArrowField>::to_array(&self.#strided().to_vec())
+                }
+            }
+            ColumnKind::Plain => quote! {
+                <#ty as ::soa_persistence::ArrowField>::to_array(&self.#id)
+            },
+            ColumnKind::Nullable(_) => quote! {
+                ::soa_persistence::arrow_conversion::nullable_to_array::<#ty>(&self.#id)?
+            },
+            ColumnKind::Utf8 => quote! {
+                ::soa_persistence::arrow_conversion::string_column_to_array(&self.#id)?
+            },
+            ColumnKind::Binary => quote! {
+                ::soa_persistence::arrow_conversion::binary_column_to_array(&self.#id)?
+            },
+        });
+
+    let read_columns: Vec<_> = value_types
+        .iter()
+        .zip(kinds.iter())
+        .zip(field_attrs.iter())
+        .zip(column_indices)
+        .map(|(((ty, kind), attrs), idx)| {
+            let aliases = &attrs.renamed_from;
+            let read = match kind {
+                ColumnKind::Plain => quote! { read_optional_column::<#ty> },
+                ColumnKind::Nullable(_) => quote! { read_optional_nullable_column::<#ty> },
+                ColumnKind::Utf8 => quote! { read_optional_string_column },
+                ColumnKind::Binary => quote! { read_optional_binary_column },
+            };
+            let missing = match (&attrs.default, kind) {
+                // Column added after the data was written: fill every row with the default
+                (Some(default), _) => quote! {
+                    (0..batch.num_rows()).map(|_| #default).collect()
+                },
+                (None, ColumnKind::Nullable(_)) => quote! {
+                    (0..batch.num_rows()).map(|_| ::std::option::Option::None).collect()
+                },
+                (None, ColumnKind::Plain | ColumnKind::Utf8 | ColumnKind::Binary) => quote! {
+                    return ::std::result::Result::Err(
+                        ::soa_persistence::PersistenceError::ColumnNotFound {
+                            column_name: field.name().to_string(),
+                        },
+                    )
+                },
+            };
+            quote! {
+                {
+                    let field = schema.field(#idx);
+                    match ::soa_persistence::arrow_conversion::#read(
+                        batch,
+                        field,
+                        &[ #( #aliases ),* ],
+                    )? {
+                        // Plain columns are read into a `Vec` and copied into aligned storage
+                        ::std::option::Option::Some(values) => ::std::convert::From::from(values),
+                        ::std::option::Option::None => #missing,
+                    }
+                }
+            }
+        })
+        .collect();
+
+    // Every column is read into a `values_<field>` local; packed fields are then zipped
+    // back into their records
+    let mut from_columns = Vec::new();
+    let mut unpacked_inits = Vec::new();
+    let mut packed_inits = Vec::new();
+    for (((id, ty), read), &is_packed) in field_idents
+        .iter()
+        .zip(field_types.iter())
+        .zip(read_columns.iter())
+        .zip(packed.iter())
+    {
+        let values = format_ident!("values_{}", id);
+        if is_packed {
+            from_columns.push(quote! {
+                let mut #values = {
+                    let values: ::std::vec::Vec<#ty> = #read;
+                    values.into_iter()
+                };
+            });
+            packed_inits.push(quote! { #id: #values.next().unwrap() });
+        } else {
+            from_columns.push(quote! { let #values = #read; });
+            unpacked_inits.push(quote! { #id: #values });
+        }
+    }
+    let packed_column = (!packed_inits.is_empty()).then(|| {
+        let packed_ident = format_ident!("{}Packed", ident);
+        quote! {
+            packed: (0..batch.num_rows())
+                .map(|_| #packed_ident { #( #packed_inits, )* })
+                .collect(),
+        }
+    });
+
+    let schema_version = struct_attrs.version.map(|version| {
+        quote! {
+            fn schema_version() -> u32 {
+                #version
+            }
+        }
+    });
+    let alias_pairs: Vec<_> = column_names
+        .iter()
+        .zip(field_attrs.iter())
+        .flat_map(|(name, attrs)| {
+            attrs
+                .renamed_from
+                .iter()
+                .map(move |former| quote! { (#name, #former) })
+        })
+        .collect();
+    let field_aliases = (!alias_pairs.is_empty()).then(|| {
+        quote! {
+            fn arrow_field_aliases() -> ::std::vec::Vec<(&'static str, &'static str)> {
+                ::std::vec![ #( #alias_pairs, )* ]
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::soa_persistence::ArrowSchemaGen for #soa_ident #ty_generics #where_clause {
+            fn arrow_schema() -> ::std::sync::Arc<::soa_persistence::Schema> {
+                ::std::sync::Arc::new(::soa_persistence::Schema::new(::std::vec![
+                    #( #schema_fields, )*
+                ]))
+            }
+
+            fn arrow_field_names() -> ::std::vec::Vec<&'static str> {
+                ::std::vec![ #( #column_names, )* ]
+            }
+
+            fn arrow_field_types() -> ::std::vec::Vec<::soa_persistence::DataType> {
+                ::std::vec![ #( #data_types, )* ]
+            }
+
+            #schema_version
+            #field_aliases
+        }
+
+        impl #impl_generics ::soa_persistence::ToArrow for #soa_ident #ty_generics #where_clause {
+            fn to_record_batch(&self) -> ::soa_persistence::Result<::soa_persistence::RecordBatch> {
+                let columns: ::std::vec::Vec<::soa_persistence::ArrayRef> = ::std::vec![
+                    #( #to_columns, )*
+                ];
+                ::soa_persistence::RecordBatch::try_new(
+                    <Self as ::soa_persistence::ArrowSchemaGen>::arrow_schema(),
+                    columns,
+                )
+                .map_err(::soa_persistence::PersistenceError::ArrowError)
+            }
+
+            fn from_record_batch(
+                batch: &::soa_persistence::RecordBatch,
+            ) -> ::soa_persistence::Result<Self> {
+                // Columns are resolved by name (or a former name), so their order in the
+                // batch does not matter and columns dropped from the struct are ignored
+                let schema = <Self as ::soa_persistence::ArrowSchemaGen>::arrow_schema();
+                #( #from_columns )*
+                ::std::result::Result::Ok(Self {
+                    #( #unpacked_inits, )*
+                    #packed_column
+                })
+            }
+
+            fn from_record_batches(
+                batches: &[::soa_persistence::RecordBatch],
+            ) -> ::soa_persistence::Result<Self> {
+                let mut soa = Self::new();
+                soa.reserve(batches.iter().map(|batch| batch.num_rows()).sum());
+                for batch in batches {
+                    soa.append(&mut Self::from_record_batch(batch)?);
+                }
+                ::std::result::Result::Ok(soa)
+            }
+        }
+    })
+}
+
+fn expand_enum(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    crate::enums::fieldless_variants(ident, &input.data, "SoAArrow")?;
+
+    Ok(quote! {
+        impl ::soa_persistence::ArrowField for #ident {
+            fn data_type() -> ::soa_persistence::DataType {
+                ::soa_persistence::arrow_conversion::enum_dictionary_type()
+            }
+
+            fn to_array(values: &[Self]) -> ::soa_persistence::ArrayRef {
+                ::soa_persistence::arrow_conversion::enums_to_dictionary_array(values)
+            }
+
+            fn from_array(
+                array: &dyn ::soa_persistence::Array,
+                column_name: &str,
+            ) -> ::soa_persistence::Result<::std::vec::Vec<Self>> {
+                ::soa_persistence::arrow_conversion::enums_from_dictionary_array(array, column_name)
+            }
+
+            fn accepts(data_type: &::soa_persistence::DataType) -> bool {
+                ::soa_persistence::arrow_conversion::enum_accepts_data_type(data_type)
+            }
+        }
+    })
+}
@@ -0,0 +1,229 @@
+//! Parsing of the `#[soa(...)]` helper attribute shared by the SoA derives.
+//!
+//! Every derive that registers `soa` parses the full attribute set, so an unknown
+//! key is reported no matter which derives are applied to the struct.
+
+use syn::{parse_quote, Attribute, Expr, Ident, LitInt, LitStr, Path, Token};
+
+/// Field-level `#[soa(...)]` options.
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    /// `skip`: no column is stored; owned rows get `Default::default()`
+    pub skip: bool,
+    /// `rename = "name"`: column name used when persisting
+    pub rename: Option<LitStr>,
+    /// `group = "hot"` / `group = "cold"`: placement in the generated hot/cold layout
+    pub group: Option<Group>,
+    /// `default = <expr>` (or bare `default`): value used when a persisted column is missing
+    pub default: Option<Expr>,
+    /// `renamed_from = "old"`: former column names accepted when loading
+    pub renamed_from: Vec<LitStr>,
+    /// `index = "hash"` / `"btree"` / `"bitmap"`: secondary index kept by the generated store
+    pub index: Option<IndexKind>,
+}
+
+/// Access-frequency group of a field in the hot/cold layout.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Group {
+    Hot,
+    Cold,
+}
+
+/// Kind of secondary index `#[derive(SoAStore)]` keeps for a field.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IndexKind {
+    /// `HashMap` from value to rows: equality lookups
+    Hash,
+    /// `BTreeMap` from value to rows: equality and range lookups in value order
+    BTree,
+    /// Compressed row bitmap per distinct value, combinable with `&` / `|`
+    Bitmap,
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = FieldAttrs::default();
+
+        for attr in attrs.iter().filter(|a| a.path().is_ident("soa")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    out.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("rename") {
+                    out.rename = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("group") {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    out.group = Some(match lit.value().as_str() {
+                        "hot" => Group::Hot,
+                        "cold" => Group::Cold,
+                        _ => {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "soa group must be \"hot\" or \"cold\"",
+                            ))
+                        }
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    out.default = Some(if meta.input.peek(Token![=]) {
+                        meta.value()?.parse()?
+                    } else {
+                        parse_quote!(::std::default::Default::default())
+                    });
+                    Ok(())
+                } else if meta.path.is_ident("renamed_from") {
+                    out.renamed_from.push(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("index") {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    out.index = Some(match lit.value().as_str() {
+                        "hash" => IndexKind::Hash,
+                        "btree" => IndexKind::BTree,
+                        "bitmap" => IndexKind::Bitmap,
+                        _ => {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "soa index must be \"hash\", \"btree\" or \"bitmap\"",
+                            ))
+                        }
+                    });
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unknown field attribute for soa (expected `skip`, `rename`, `group`, \
+                         `default`, `renamed_from` or `index`)",
+                    ))
+                }
+            })?;
+
+            if out.skip && out.group.is_some() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "a `#[soa(skip)]` field cannot also have a `group`",
+                ));
+            }
+            if out.skip && out.index.is_some() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "a `#[soa(skip)]` field has no column to index",
+                ));
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Column name used when persisting the field `ident`
+    pub fn column_name(&self, ident: &syn::Ident) -> String {
+        self.rename
+            .as_ref()
+            .map_or_else(|| ident.to_string(), LitStr::value)
+    }
+}
+
+/// Struct-level `#[soa(...)]` options.
+#[derive(Default)]
+pub(crate) struct StructAttrs {
+    /// `version = N`: schema version stamped into persisted data
+    pub version: Option<u32>,
+    /// `pack(a, b, ...)`: fields stored interleaved as one record per row
+    pub pack: Vec<Ident>,
+    /// `layout = "aosoa"`: also generate the tiled `{Ident}AoSoA` layout; holds the
+    /// `"aosoa"` literal for errors about it
+    pub aosoa: Option<LitStr>,
+    /// `lanes = N`: rows per AoSoA tile
+    pub lanes: Option<LitInt>,
+    /// `align = N`: byte alignment of every plain column (64 unless given)
+    pub align: Option<usize>,
+    /// `copy`: the hot/cold records also derive `Copy`; holds the `copy` path for errors
+    /// about it
+    pub copy: Option<Path>,
+}
+
+impl StructAttrs {
+    /// Column alignment used when `align` is not given: one cache line
+    pub const DEFAULT_ALIGN: usize = 64;
+
+    pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = StructAttrs::default();
+
+        for attr in attrs.iter().filter(|a| a.path().is_ident("soa")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("version") {
+                    let lit: LitInt = meta.value()?.parse()?;
+                    let version = lit.base10_parse::<u32>()?;
+                    if version == 0 {
+                        return Err(syn::Error::new(lit.span(), "soa version starts at 1"));
+                    }
+                    out.version = Some(version);
+                    Ok(())
+                } else if meta.path.is_ident("pack") {
+                    if !out.pack.is_empty() {
+                        return Err(meta.error("only one `pack(...)` group is supported"));
+                    }
+                    meta.parse_nested_meta(|field| {
+                        let ident = field.path.require_ident()?;
+                        if out.pack.contains(ident) {
+                            return Err(field.error("field listed twice in `pack(...)`"));
+                        }
+                        out.pack.push(ident.clone());
+                        Ok(())
+                    })?;
+                    if out.pack.is_empty() {
+                        return Err(meta.error("`pack(...)` needs at least one field"));
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("layout") {
+                    let lit: LitStr = meta.value()?.parse()?;
+                    out.aosoa = match lit.value().as_str() {
+                        "soa" => None,
+                        "aosoa" => Some(lit),
+                        _ => {
+                            return Err(syn::Error::new(
+                                lit.span(),
+                                "soa layout must be \"soa\" or \"aosoa\"",
+                            ))
+                        }
+                    };
+                    Ok(())
+                } else if meta.path.is_ident("lanes") {
+                    let lit: LitInt = meta.value()?.parse()?;
+                    if lit.base10_parse::<usize>()? == 0 {
+                        return Err(syn::Error::new(lit.span(), "soa lanes must be at least 1"));
+                    }
+                    out.lanes = Some(lit);
+                    Ok(())
+                } else if meta.path.is_ident("align") {
+                    let lit: LitInt = meta.value()?.parse()?;
+                    let align = lit.base10_parse::<usize>()?;
+                    if !align.is_power_of_two() {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "soa align must be a power of two",
+                        ));
+                    }
+                    out.align = Some(align);
+                    Ok(())
+                } else if meta.path.is_ident("copy") {
+                    out.copy = Some(meta.path.clone());
+                    Ok(())
+                } else {
+                    Err(meta.error(
+                        "unknown struct attribute for soa (expected `version`, `pack`, `layout`, \
+                         `lanes`, `align` or `copy`)",
+                    ))
+                }
+            })?;
+        }
+
+        if let (Some(lanes), None) = (&out.lanes, &out.aosoa) {
+            return Err(syn::Error::new(
+                lanes.span(),
+                "`lanes` only applies to `layout = \"aosoa\"`",
+            ));
+        }
+
+        Ok(out)
+    }
+}
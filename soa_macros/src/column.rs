@@ -0,0 +1,104 @@
+//! Classification of struct fields into the column storage used by `#[derive(SoA)]`.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{GenericArgument, PathArguments, Type};
+
+/// How a field of the domain struct is stored in the generated `*SoA`.
+pub(crate) enum ColumnKind<'a> {
+    /// `soa_runtime::AlignedVec<T>`
+    Plain,
+    /// `Option<T>` stored as `soa_runtime::NullableColumn<T>`
+    Nullable(&'a Type),
+    /// `String` stored as `soa_runtime::StringColumn`
+    Utf8,
+    /// `Vec<u8>` stored as `soa_runtime::BinaryColumn`
+    Binary,
+}
+
+impl<'a> ColumnKind<'a> {
+    pub fn of(ty: &'a Type) -> Self {
+        if let Some(inner) = option_inner(ty) {
+            ColumnKind::Nullable(inner)
+        } else if is_string(ty) {
+            ColumnKind::Utf8
+        } else if is_byte_vec(ty) {
+            ColumnKind::Binary
+        } else {
+            ColumnKind::Plain
+        }
+    }
+
+    /// Type of the column holding a field of type `ty`; plain columns are `align`-byte aligned
+    pub fn storage_type(&self, ty: &Type, align: usize) -> TokenStream {
+        match self {
+            ColumnKind::Plain => quote! { soa_runtime::AlignedVec<#ty, #align> },
+            ColumnKind::Nullable(inner) => quote! { soa_runtime::NullableColumn<#inner> },
+            ColumnKind::Utf8 => quote! { soa_runtime::StringColumn },
+            ColumnKind::Binary => quote! { soa_runtime::BinaryColumn },
+        }
+    }
+}
+
+/// Returns `T` for `Option<T>` (also spelled `std::option::Option<T>` / `core::option::Option<T>`)
+fn option_inner(ty: &Type) -> Option<&Type> {
+    single_type_arg(ty, &["std", "core"], "option", "Option")
+}
+
+/// `String`, `std::string::String` or `alloc::string::String`
+fn is_string(ty: &Type) -> bool {
+    matches!(
+        std_path(ty, &["std", "alloc"], "string", "String"),
+        Some(PathArguments::None)
+    )
+}
+
+/// `Vec<u8>`, `std::vec::Vec<u8>` or `alloc::vec::Vec<u8>`
+fn is_byte_vec(ty: &Type) -> bool {
+    matches!(
+        single_type_arg(ty, &["std", "alloc"], "vec", "Vec"),
+        Some(Type::Path(inner)) if inner.qself.is_none() && inner.path.is_ident("u8")
+    )
+}
+
+/// Returns `T` for `Name<T>` spelled as `Name<T>` or `krate::module::Name<T>`
+fn single_type_arg<'a>(
+    ty: &'a Type,
+    krates: &[&str],
+    module: &str,
+    name: &str,
+) -> Option<&'a Type> {
+    match std_path(ty, krates, module, name)? {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Arguments of the last segment when `ty` names `name` from the standard library
+fn std_path<'a>(
+    ty: &'a Type,
+    krates: &[&str],
+    module: &str,
+    name: &str,
+) -> Option<&'a PathArguments> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    if path.qself.is_some() {
+        return None;
+    }
+
+    let segments: Vec<_> = path.path.segments.iter().collect();
+    let matches = match segments.as_slice() {
+        [last] => last.ident == name,
+        [krate, m, last] => {
+            krates.iter().any(|k| krate.ident == k) && m.ident == module && last.ident == name
+        }
+        _ => false,
+    };
+
+    matches.then(|| &segments.last().unwrap().arguments)
+}
@@ -0,0 +1,111 @@
+//! Expansion for `#[derive(SoAEnum)]`.
+//!
+//! Maps a fieldless enum onto `u8` discriminants in declaration order and
+//! records the variant names used by dictionary-encoded Arrow columns. Explicit
+//! discriminants (`Foo = 5`) are rejected, since they would disagree with that order.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DataEnum, DeriveInput, Fields, Ident};
+
+/// Extracts the variants of a fieldless enum without explicit discriminants, rejecting
+/// anything else.
+pub(crate) fn fieldless_variants<'a>(
+    ident: &Ident,
+    data: &'a Data,
+    derive_name: &str,
+) -> syn::Result<Vec<&'a Ident>> {
+    let DataEnum { variants, .. } = match data {
+        Data::Enum(e) => e,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("{} derive works only on enums", derive_name),
+            ))
+        }
+    };
+
+    if variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            ident,
+            format!("{} derive requires at least one variant", derive_name),
+        ));
+    }
+    if variants.len() > 256 {
+        return Err(syn::Error::new_spanned(
+            ident,
+            format!("{} derive supports at most 256 variants", derive_name),
+        ));
+    }
+
+    variants
+        .iter()
+        .map(|v| match (&v.fields, &v.discriminant) {
+            (Fields::Unit, None) => Ok(&v.ident),
+            (Fields::Unit, Some((_, discriminant))) => Err(syn::Error::new_spanned(
+                discriminant,
+                format!(
+                    "{} derive numbers variants in declaration order; \
+                     remove the explicit discriminant",
+                    derive_name
+                ),
+            )),
+            _ => Err(syn::Error::new_spanned(
+                v,
+                format!("{} derive requires fieldless variants", derive_name),
+            )),
+        })
+        .collect()
+}
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let variants = fieldless_variants(ident, &input.data, "SoAEnum")?;
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "SoAEnum derive does not support generic enums",
+        ));
+    }
+
+    let name = ident.to_string();
+    let variant_names: Vec<_> = variants.iter().map(|v| v.to_string()).collect();
+    let discriminants: Vec<_> = (0..variants.len()).map(|i| i as u8).collect();
+
+    Ok(quote! {
+        impl soa_runtime::SoaEnum for #ident {
+            const NAME: &'static str = #name;
+            const VARIANTS: &'static [&'static str] = &[ #( #variant_names, )* ];
+
+            fn to_u8(&self) -> u8 {
+                match self {
+                    #( #ident::#variants => #discriminants, )*
+                }
+            }
+
+            fn from_u8(value: u8) -> ::std::option::Option<Self> {
+                match value {
+                    #( #discriminants => ::std::option::Option::Some(#ident::#variants), )*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+
+        impl ::std::convert::From<#ident> for u8 {
+            fn from(value: #ident) -> Self {
+                soa_runtime::SoaEnum::to_u8(&value)
+            }
+        }
+
+        impl ::std::convert::TryFrom<u8> for #ident {
+            type Error = ::std::string::String;
+
+            fn try_from(value: u8) -> ::std::result::Result<Self, Self::Error> {
+                <Self as soa_runtime::SoaEnum>::from_u8(value).ok_or_else(|| {
+                    ::std::format!("Invalid {} value: {}", #name, value)
+                })
+            }
+        }
+    })
+}
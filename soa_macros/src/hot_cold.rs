@@ -0,0 +1,162 @@
+//! Hot/cold layout emitted by `#[derive(SoA)]` when fields carry `#[soa(group = "...")]`.
+//!
+//! Fields marked `group = "hot"` are packed into one `{Ident}Hot` record per row so scans
+//! over them stay within a few cache lines; every other stored field goes to `{Ident}Cold`.
+//! `{Ident}HotCold` keeps the two record arrays in lockstep. The records derive `Debug` and
+//! `Clone`, and also `Copy` under the struct attribute `#[soa(copy)]`.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Generics, Ident, Path, Type, Visibility};
+
+use crate::attrs::Group;
+
+/// A stored (non-skipped) field of the domain struct
+pub(crate) struct GroupedField<'a> {
+    pub ident: &'a Ident,
+    pub ty: &'a Type,
+    pub group: Option<Group>,
+}
+
+pub(crate) fn expand(
+    ident: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
+    fields: &[GroupedField<'_>],
+    skipped_defaults: &[TokenStream],
+    copy: Option<&Path>,
+) -> syn::Result<TokenStream> {
+    if !generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            generics,
+            "`#[soa(group = ...)]` is not supported on generic structs",
+        ));
+    }
+
+    let (hot, cold): (Vec<_>, Vec<_>) = fields.iter().partition(|f| f.group == Some(Group::Hot));
+    if hot.is_empty() {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "hot/cold layout requires at least one `#[soa(group = \"hot\")]` field",
+        ));
+    }
+
+    let hot_ident = format_ident!("{}Hot", ident);
+    let cold_ident = format_ident!("{}Cold", ident);
+    let layout_ident = format_ident!("{}HotCold", ident);
+    let soa_ident = format_ident!("{}SoA", ident);
+
+    let hot_idents: Vec<_> = hot.iter().map(|f| f.ident).collect();
+    let hot_types: Vec<_> = hot.iter().map(|f| f.ty).collect();
+    let cold_idents: Vec<_> = cold.iter().map(|f| f.ident).collect();
+    let cold_types: Vec<_> = cold.iter().map(|f| f.ty).collect();
+
+    let hot_doc = format!(
+        "Fields of [`{}`] marked `#[soa(group = \"hot\")]`, stored together per row",
+        ident
+    );
+    let cold_doc = format!("Remaining stored fields of [`{}`]", ident);
+    let layout_doc = format!(
+        "Hot/cold split of [`{}`]: hot fields interleaved per row, cold fields kept apart",
+        ident
+    );
+
+    // Spanned at `copy` so a field type that is not `Copy` is reported there
+    let copy = copy.map(|copy| quote_spanned! {copy.span()=> , ::std::marker::Copy });
+
+    Ok(quote! {
+        #[doc = #hot_doc]
+        #[derive(Debug, Clone #copy)]
+        #vis struct #hot_ident {
+            #( pub #hot_idents: #hot_types, )*
+        }
+
+        #[doc = #cold_doc]
+        #[derive(Debug, Clone #copy)]
+        #vis struct #cold_ident {
+            #( pub #cold_idents: #cold_types, )*
+        }
+
+        #[doc = #layout_doc]
+        #[derive(Debug, Clone, Default)]
+        #vis struct #layout_ident {
+            hot: ::std::vec::Vec<#hot_ident>,
+            cold: ::std::vec::Vec<#cold_ident>,
+        }
+
+        impl #layout_ident {
+            #vis fn new() -> Self {
+                Self::default()
+            }
+            #vis fn with_capacity(cap: usize) -> Self {
+                Self {
+                    hot: ::std::vec::Vec::with_capacity(cap),
+                    cold: ::std::vec::Vec::with_capacity(cap),
+                }
+            }
+            #vis fn len(&self) -> usize {
+                debug_assert_eq!(self.hot.len(), self.cold.len(), "hot/cold length mismatch");
+                self.hot.len()
+            }
+            #vis fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+            #vis fn push(&mut self, v: #ident) -> usize {
+                self.hot.push(#hot_ident { #( #hot_idents: v.#hot_idents, )* });
+                self.cold.push(#cold_ident { #( #cold_idents: v.#cold_idents, )* });
+                self.len() - 1
+            }
+            /// Hot records, one per row
+            #vis fn hot(&self) -> &[#hot_ident] {
+                &self.hot
+            }
+            #vis fn hot_mut(&mut self) -> &mut [#hot_ident] {
+                &mut self.hot
+            }
+            /// Cold records, one per row
+            #vis fn cold(&self) -> &[#cold_ident] {
+                &self.cold
+            }
+            #vis fn cold_mut(&mut self) -> &mut [#cold_ident] {
+                &mut self.cold
+            }
+            /// Owned copy of row `i`; panics if `i` is out of bounds
+            #vis fn get_owned(&self, i: usize) -> #ident {
+                let hot = ::std::clone::Clone::clone(&self.hot[i]);
+                let cold = ::std::clone::Clone::clone(&self.cold[i]);
+                #ident {
+                    #( #hot_idents: hot.#hot_idents, )*
+                    #( #cold_idents: cold.#cold_idents, )*
+                    #( #skipped_defaults, )*
+                }
+            }
+        }
+
+        impl ::std::convert::From<&#soa_ident> for #layout_ident {
+            fn from(soa: &#soa_ident) -> Self {
+                (0..soa.len()).map(|i| soa.get_owned(i)).collect()
+            }
+        }
+
+        impl ::std::iter::Extend<#ident> for #layout_ident {
+            fn extend<I: ::std::iter::IntoIterator<Item = #ident>>(&mut self, iter: I) {
+                let iter = iter.into_iter();
+                let additional = iter.size_hint().0;
+                self.hot.reserve(additional);
+                self.cold.reserve(additional);
+                for v in iter {
+                    self.push(v);
+                }
+            }
+        }
+
+        impl ::std::iter::FromIterator<#ident> for #layout_ident {
+            fn from_iter<I: ::std::iter::IntoIterator<Item = #ident>>(iter: I) -> Self {
+                let mut layout = Self::new();
+                layout.extend(iter);
+                layout
+            }
+        }
+    })
+}
@@ -0,0 +1,823 @@
+//! Expansion for `#[derive(SoA)]`: the `*SoA` column store, its `*View` / `*Mut` row views,
+//! owning and mutable iterators, and the `soa_runtime::SoaModel` impl.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Fields, GenericParam, Generics, Ident, Lifetime, LifetimeParam};
+
+use crate::aosoa;
+use crate::attrs::{FieldAttrs, StructAttrs};
+use crate::column::ColumnKind;
+use crate::hot_cold::{self, GroupedField};
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = input.ident;
+    let vis = input.vis;
+    let generics = input.generics;
+
+    let fields = match input.data {
+        Data::Struct(s) => match s.fields {
+            Fields::Named(named) => named.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    s.fields,
+                    "SoA derive requires a braced struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &ident,
+                "SoA derive works only on structs",
+            ))
+        }
+    };
+
+    let struct_attrs = StructAttrs::parse(&input.attrs)?;
+    let field_attrs = fields
+        .iter()
+        .map(|f| FieldAttrs::parse(&f.attrs))
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    // `#[soa(skip)]` fields get no column; owned rows rebuild them with `Default`
+    let (column_fields, skipped_fields): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .zip(field_attrs.iter())
+        .partition(|(_, attrs)| !attrs.skip);
+
+    if column_fields.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &ident,
+            "SoA derive requires at least one field that is not `#[soa(skip)]`",
+        ));
+    }
+
+    let field_idents: Vec<_> = column_fields
+        .iter()
+        .map(|(f, _)| f.ident.clone().unwrap())
+        .collect();
+    let field_types: Vec<_> = column_fields.iter().map(|(f, _)| f.ty.clone()).collect();
+    let skipped_defaults: Vec<_> = skipped_fields
+        .iter()
+        .map(|(f, _)| {
+            let id = &f.ident;
+            let ty = &f.ty;
+            quote_spanned! {ty.span()=> #id: <#ty as ::std::default::Default>::default() }
+        })
+        .collect();
+
+    let hot_cold = if column_fields.iter().any(|(_, attrs)| attrs.group.is_some()) {
+        let grouped: Vec<_> = column_fields
+            .iter()
+            .map(|(f, attrs)| GroupedField {
+                ident: f.ident.as_ref().unwrap(),
+                ty: &f.ty,
+                group: attrs.group,
+            })
+            .collect();
+        Some(hot_cold::expand(
+            &ident,
+            &vis,
+            &generics,
+            &grouped,
+            &skipped_defaults,
+            struct_attrs.copy.as_ref(),
+        )?)
+    } else if let Some(copy) = &struct_attrs.copy {
+        return Err(syn::Error::new_spanned(
+            copy,
+            "`copy` only applies to structs with `#[soa(group = \"hot\")]` fields",
+        ));
+    } else {
+        None
+    };
+
+    let tiled = if let Some(layout) = &struct_attrs.aosoa {
+        let fields: Vec<_> = field_idents.iter().zip(field_types.iter()).collect();
+        Some(aosoa::expand(
+            &ident,
+            &vis,
+            &generics,
+            layout,
+            struct_attrs.lanes.as_ref(),
+            &fields,
+            &skipped_defaults,
+        )?)
+    } else {
+        None
+    };
+
+    let soa_ident = format_ident!("{}SoA", ident);
+    let view_ident = format_ident!("{}View", ident);
+    let view_mut_ident = format_ident!("{}Mut", ident);
+
+    // Every generated type carries the struct's generics; views and borrowing iterators
+    // additionally take a leading lifetime `lt`
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let lt = view_lifetime(&generics);
+    let view_generics = with_lifetime(&generics, &lt);
+    let (view_impl_generics, view_ty_generics, _) = view_generics.split_for_impl();
+    let anon_generics = anonymous_lifetime_args(&generics);
+    let item_ty = quote! { #ident #ty_generics };
+    let soa_ty = quote! { #soa_ident #ty_generics };
+
+    let kinds: Vec<_> = field_types.iter().map(ColumnKind::of).collect();
+    let packed = packed_fields(
+        &ident,
+        &generics,
+        &struct_attrs.pack,
+        &field_idents,
+        &kinds,
+        &skipped_fields,
+    )?;
+    let packed_ident = format_ident!("{}Packed", ident);
+    let pack_column = format_ident!("packed");
+
+    // Storage columns: one per unpacked field, plus a single `packed` column of
+    // `{Ident}Packed` records placed where the first packed field was declared
+    let align = struct_attrs.align.unwrap_or(StructAttrs::DEFAULT_ALIGN);
+    let mut column_idents: Vec<Ident> = Vec::new();
+    let mut storage_types: Vec<TokenStream> = Vec::new();
+    let mut push_moves: Vec<TokenStream> = Vec::new();
+    for ((id, (ty, kind)), &is_packed) in field_idents
+        .iter()
+        .zip(field_types.iter().zip(kinds.iter()))
+        .zip(packed.iter())
+    {
+        if !is_packed {
+            column_idents.push(id.clone());
+            storage_types.push(kind.storage_type(ty, align));
+            // Spanned at the field so a missing `NullPlaceholder` (`Default`) points at it
+            push_moves.push(quote_spanned! {ty.span()=> self.#id.push(v.#id); });
+        } else if !column_idents.contains(&pack_column) {
+            let packed_idents = packed_idents(&field_idents, &packed);
+            column_idents.push(pack_column.clone());
+            storage_types.push(quote! { soa_runtime::AlignedVec<#packed_ident, #align> });
+            push_moves.push(quote! {
+                self.#pack_column.push(#packed_ident { #( #packed_idents: v.#packed_idents, )* });
+            });
+        }
+    }
+
+    let columns = column_idents
+        .iter()
+        .zip(storage_types.iter())
+        .map(|(id, storage)| {
+            quote! { #id: #storage }
+        });
+
+    // A row is rebuilt from one `col_<column>` local per storage column; packed fields
+    // are taken out of `col_packed` (by value, or through `borrow`)
+    let column_locals: Vec<_> = column_idents
+        .iter()
+        .map(|id| format_ident!("col_{}", id))
+        .collect();
+    let row_inits = |borrow: TokenStream| -> Vec<TokenStream> {
+        field_idents
+            .iter()
+            .zip(packed.iter())
+            .map(|(id, &is_packed)| {
+                if is_packed {
+                    let local = format_ident!("col_{}", pack_column);
+                    quote! { #id: #borrow #local.#id }
+                } else {
+                    let local = format_ident!("col_{}", id);
+                    quote! { #id: #local }
+                }
+            })
+            .collect()
+    };
+    let owned_inits = row_inits(quote! {});
+    let mut_inits = row_inits(quote! { &mut });
+
+    let view_fields = field_idents
+        .iter()
+        .zip(field_types.iter())
+        .zip(kinds.iter())
+        .map(|((id, ty), kind)| match kind {
+            ColumnKind::Plain => quote! { pub #id: &#lt #ty },
+            ColumnKind::Nullable(inner) => quote! { pub #id: ::std::option::Option<&#lt #inner> },
+            ColumnKind::Utf8 => quote! { pub #id: &#lt str },
+            ColumnKind::Binary => quote! { pub #id: &#lt [u8] },
+        });
+
+    let view_mut_fields = field_idents
+        .iter()
+        .zip(field_types.iter())
+        .zip(kinds.iter())
+        .map(|((id, ty), kind)| match kind {
+            ColumnKind::Plain => quote! { pub #id: &#lt mut #ty },
+            ColumnKind::Nullable(inner) => {
+                quote! { pub #id: ::std::option::Option<&#lt mut #inner> }
+            }
+            // Variable-length values cannot change size in place; use `set_<field>` instead
+            ColumnKind::Utf8 => quote! { pub #id: &#lt str },
+            ColumnKind::Binary => quote! { pub #id: &#lt [u8] },
+        });
+
+    let view_ctor_bind = field_idents
+        .iter()
+        .zip(kinds.iter())
+        .zip(packed.iter())
+        .map(|((id, kind), &is_packed)| match kind {
+            ColumnKind::Plain if is_packed => quote! { #id: &self.#pack_column[i].#id },
+            ColumnKind::Plain => quote! { #id: &self.#id[i] },
+            ColumnKind::Nullable(_) => quote! { #id: self.#id.value(i) },
+            ColumnKind::Utf8 | ColumnKind::Binary => quote! { #id: self.#id.get(i) },
+        });
+    let view_mut_ctor_bind = field_idents
+        .iter()
+        .zip(kinds.iter())
+        .zip(packed.iter())
+        .map(|((id, kind), &is_packed)| match kind {
+            ColumnKind::Plain if is_packed => quote! { #id: &mut col_packed.#id },
+            ColumnKind::Plain => quote! { #id: &mut self.#id[i] },
+            ColumnKind::Nullable(_) => quote! { #id: self.#id.value_mut(i) },
+            ColumnKind::Utf8 | ColumnKind::Binary => quote! { #id: self.#id.get(i) },
+        });
+
+    // Owned copy of row `i`, for `get_owned` / `to_aos`
+    let owned_fields = field_idents
+        .iter()
+        .zip(kinds.iter())
+        .zip(packed.iter())
+        .map(|((id, kind), &is_packed)| match kind {
+            ColumnKind::Plain if is_packed => {
+                quote! { #id: ::std::clone::Clone::clone(&self.#pack_column[i].#id) }
+            }
+            ColumnKind::Plain => quote! { #id: ::std::clone::Clone::clone(&self.#id[i]) },
+            ColumnKind::Nullable(_) => quote! { #id: self.#id.value(i).cloned() },
+            ColumnKind::Utf8 | ColumnKind::Binary => {
+                quote! { #id: ::std::borrow::ToOwned::to_owned(self.#id.get(i)) }
+            }
+        });
+    // Generic field types are not known to be `Clone`, so the copying methods ask for it
+    let clone_bounds: Vec<_> = field_types
+        .iter()
+        .zip(kinds.iter())
+        .filter_map(|(ty, kind)| match kind {
+            ColumnKind::Plain => Some(quote! { #ty: ::std::clone::Clone }),
+            ColumnKind::Nullable(inner) => Some(quote! { #inner: ::std::clone::Clone }),
+            ColumnKind::Utf8 | ColumnKind::Binary => None,
+        })
+        .collect();
+
+    // Per-column iterators borrowed by `iter_mut`, each from its own field
+    let iter_mut_ident = format_ident!("{}IterMut", ident);
+    let iter_mut_doc = format!("Mutable iterator over the rows of a [`{}`]", soa_ident);
+    let mut iter_mut_columns: Vec<TokenStream> = field_idents
+        .iter()
+        .zip(field_types.iter())
+        .zip(kinds.iter())
+        .zip(packed.iter())
+        .filter(|(_, &is_packed)| !is_packed)
+        .map(|(((id, ty), kind), _)| match kind {
+            ColumnKind::Plain => quote! { #id: ::std::slice::IterMut<#lt, #ty> },
+            ColumnKind::Nullable(inner) => {
+                quote! { #id: soa_runtime::NullableIterMut<#lt, #inner> }
+            }
+            ColumnKind::Utf8 => quote! { #id: soa_runtime::Rows<#lt, soa_runtime::StringColumn> },
+            ColumnKind::Binary => quote! { #id: soa_runtime::Rows<#lt, soa_runtime::BinaryColumn> },
+        })
+        .collect();
+    let mut iter_mut_init: Vec<TokenStream> = field_idents
+        .iter()
+        .zip(kinds.iter())
+        .zip(packed.iter())
+        .filter(|(_, &is_packed)| !is_packed)
+        .map(|((id, kind), _)| match kind {
+            ColumnKind::Plain | ColumnKind::Nullable(_) => quote! { #id: self.#id.iter_mut() },
+            ColumnKind::Utf8 | ColumnKind::Binary => quote! { #id: self.#id.iter() },
+        })
+        .collect();
+    if packed.contains(&true) {
+        iter_mut_columns.push(quote! { #pack_column: ::std::slice::IterMut<#lt, #packed_ident> });
+        iter_mut_init.push(quote! { #pack_column: self.#pack_column.iter_mut() });
+    }
+
+    let into_iter_ident = format_ident!("{}IntoIter", ident);
+    let into_iter_doc = format!("Owning iterator over the rows of a [`{}`]", soa_ident);
+
+    let drained: Vec<_> = column_idents
+        .iter()
+        .map(|id| format_ident!("drained_{}", id))
+        .collect();
+
+    let first_column = &column_idents[0];
+    let equal_len_asserts = column_idents.iter().map(|id| {
+        quote! { debug_assert_eq!(self.#first_column.len(), self.#id.len(), "SoA columns length mismatch"); }
+    });
+
+    let aligned_doc = format!("Column values, starting on a {}-byte boundary", align);
+    let mut raw_array_methods: Vec<TokenStream> = field_idents
+        .iter()
+        .zip(field_types.iter())
+        .zip(kinds.iter())
+        .zip(packed.iter())
+        .map(|(((id, ty), kind), &is_packed)| {
+            let method_name = format_ident!("{}_raw_array", id);
+            match kind {
+                // No contiguous `&[T]` exists for a packed field, so it gets no `_raw_array`
+                ColumnKind::Plain if is_packed => {
+                    let strided_name = format_ident!("{}_strided", id);
+                    quote! {
+                        /// Values read out of the packed records with a stride of one record.
+                        /// Indexed, sliced and iterated like a slice, but not a `&[T]`: the
+                        /// field is not contiguous in memory.
+                        #vis fn #strided_name(&self) -> soa_runtime::Strided<'_, #packed_ident, #ty> {
+                            soa_runtime::Strided::new(&self.#pack_column, |row| &row.#id)
+                        }
+                    }
+                }
+                ColumnKind::Plain => quote! {
+                    #[doc = #aligned_doc]
+                    #vis fn #method_name(&self) -> &[#ty] {
+                        &self.#id
+                    }
+                },
+                ColumnKind::Nullable(inner) => {
+                    let validity_name = format_ident!("{}_validity", id);
+                    let setter_name = format_ident!("set_{}", id);
+                    let set_value = quote_spanned! {ty.span()=> self.#id.set(i, value); };
+                    quote! {
+                        /// Dense values, holding `Default::default()` at null rows
+                        #vis fn #method_name(&self) -> &[#inner] {
+                            self.#id.values()
+                        }
+                        /// Validity bitmap: bit `i` is set when row `i` holds a value
+                        #vis fn #validity_name(&self) -> &soa_runtime::Bitmap {
+                            self.#id.validity()
+                        }
+                        #vis fn #setter_name(&mut self, i: usize, value: #ty) {
+                            #set_value
+                        }
+                    }
+                }
+                ColumnKind::Utf8 | ColumnKind::Binary => {
+                    let column_name = format_ident!("{}_column", id);
+                    let setter_name = format_ident!("set_{}", id);
+                    let (column_ty, value_ty) = match kind {
+                        ColumnKind::Utf8 => (quote! { soa_runtime::StringColumn }, quote! { &str }),
+                        _ => (quote! { soa_runtime::BinaryColumn }, quote! { &[u8] }),
+                    };
+                    quote! {
+                        /// Offsets and contiguous values buffer backing this column
+                        #vis fn #column_name(&self) -> &#column_ty {
+                            &self.#id
+                        }
+                        #vis fn #setter_name(&mut self, i: usize, value: #value_ty) {
+                            self.#id.set(i, value);
+                        }
+                    }
+                }
+            }
+        })
+        .collect();
+
+    // The packed record type, and direct access to its column
+    let packed_record = packed.contains(&true).then(|| {
+        let packed_idents = packed_idents(&field_idents, &packed);
+        let packed_types = field_types
+            .iter()
+            .zip(packed.iter())
+            .filter(|(_, &is_packed)| is_packed)
+            .map(|(ty, _)| ty);
+        let packed_doc = format!(
+            "Fields of [`{}`] listed in `#[soa(pack(...))]`, stored together per row",
+            ident
+        );
+        let method_name = format_ident!("{}_raw_array", pack_column);
+        raw_array_methods.push(quote! {
+            /// Packed records, one per row
+            #vis fn #method_name(&self) -> &[#packed_ident] {
+                &self.#pack_column
+            }
+        });
+        quote! {
+            #[doc = #packed_doc]
+            #[derive(Clone)]
+            #vis struct #packed_ident {
+                #( pub #packed_idents: #packed_types, )*
+            }
+        }
+    });
+    let view_mut_packed_row = packed
+        .contains(&true)
+        .then(|| quote! { let col_packed = &mut self.#pack_column[i]; });
+
+    // Rayon-backed row iteration, generated with `soa_macros/parallel`
+    let parallel = cfg!(feature = "parallel").then(|| {
+        quote! {
+            impl #impl_generics #soa_ty #where_clause {
+                /// Views of all rows, processed in parallel
+                #vis fn par_iter(
+                    &self,
+                ) -> impl soa_runtime::rayon::iter::IndexedParallelIterator<Item = #view_ident #anon_generics> + '_
+                where
+                    // Higher-ranked so they stay bounds on the caller rather than being
+                    // rejected as trivially false for types that are not `Sync`
+                    for<#lt> Self: ::std::marker::Sync,
+                    for<#lt> #view_ident #view_ty_generics: ::std::marker::Send,
+                {
+                    use soa_runtime::rayon::iter::{IntoParallelIterator, ParallelIterator};
+                    (0..self.len()).into_par_iter().map(move |i| self.view(i))
+                }
+                /// Row ranges of `chunk_size` rows (the last may be shorter), processed in
+                /// parallel; index the `*_raw_array()` columns with them
+                #vis fn par_chunks(
+                    &self,
+                    chunk_size: usize,
+                ) -> impl soa_runtime::rayon::iter::IndexedParallelIterator<Item = ::std::ops::Range<usize>> {
+                    soa_runtime::par_row_chunks(self.len(), chunk_size)
+                }
+            }
+        }
+    });
+
+    let expanded = quote! {
+        #[derive(Clone)]
+        #vis struct #soa_ident #generics #where_clause {
+            #( #columns, )*
+        }
+
+        impl #impl_generics #soa_ty #where_clause {
+            #vis fn new() -> Self {
+                Self { #( #column_idents: <#storage_types>::new(), )* }
+            }
+            #vis fn with_capacity(cap: usize) -> Self {
+                Self { #( #column_idents: <#storage_types>::with_capacity(cap), )* }
+            }
+            #vis fn len(&self) -> usize {
+                #( #equal_len_asserts )*
+                self.#first_column.len()
+            }
+            #vis fn is_empty(&self) -> bool { self.len() == 0 }
+            #vis fn push(&mut self, v: #item_ty) -> usize {
+                #( #push_moves )*
+                self.len() - 1
+            }
+            #vis fn view(&self, i: usize) -> #view_ident #anon_generics {
+                #view_ident { #( #view_ctor_bind, )* }
+            }
+            #vis fn view_mut(&mut self, i: usize) -> #view_mut_ident #anon_generics {
+                #view_mut_packed_row
+                #view_mut_ident { #( #view_mut_ctor_bind, )* }
+            }
+            #vis fn iter(
+                &self,
+            ) -> impl ::std::iter::DoubleEndedIterator<Item = #view_ident #anon_generics>
+                   + ::std::iter::ExactSizeIterator
+                   + '_ {
+                (0..self.len()).map(|i| self.view(i))
+            }
+        }
+
+        // Bounds-checked access and mutable iteration
+        impl #impl_generics #soa_ty #where_clause {
+            #vis fn get(&self, i: usize) -> ::std::option::Option<#view_ident #anon_generics> {
+                (i < self.len()).then(|| self.view(i))
+            }
+            #vis fn get_mut(&mut self, i: usize) -> ::std::option::Option<#view_mut_ident #anon_generics> {
+                if i < self.len() {
+                    ::std::option::Option::Some(self.view_mut(i))
+                } else {
+                    ::std::option::Option::None
+                }
+            }
+            #vis fn first(&self) -> ::std::option::Option<#view_ident #anon_generics> {
+                self.get(0)
+            }
+            #vis fn last(&self) -> ::std::option::Option<#view_ident #anon_generics> {
+                self.get(self.len().checked_sub(1)?)
+            }
+            #vis fn first_mut(&mut self) -> ::std::option::Option<#view_mut_ident #anon_generics> {
+                self.get_mut(0)
+            }
+            #vis fn last_mut(&mut self) -> ::std::option::Option<#view_mut_ident #anon_generics> {
+                let last = self.len().checked_sub(1)?;
+                self.get_mut(last)
+            }
+            #vis fn iter_mut(&mut self) -> #iter_mut_ident #anon_generics {
+                #iter_mut_ident { #( #iter_mut_init, )* }
+            }
+            /// Apply `f` to a mutable view of every row
+            #vis fn for_each_mut<F>(&mut self, f: F)
+            where
+                F: FnMut(#view_mut_ident #anon_generics),
+            {
+                self.iter_mut().for_each(f);
+            }
+        }
+
+        #[doc = #iter_mut_doc]
+        #vis struct #iter_mut_ident #view_generics #where_clause {
+            #( #iter_mut_columns, )*
+        }
+
+        impl #view_impl_generics ::std::iter::Iterator for #iter_mut_ident #view_ty_generics #where_clause {
+            type Item = #view_mut_ident #view_ty_generics;
+
+            fn next(&mut self) -> ::std::option::Option<Self::Item> {
+                #( let #column_locals = self.#column_idents.next()?; )*
+                ::std::option::Option::Some(#view_mut_ident { #( #mut_inits, )* })
+            }
+
+            fn size_hint(&self) -> (usize, ::std::option::Option<usize>) {
+                self.#first_column.size_hint()
+            }
+        }
+
+        impl #view_impl_generics ::std::iter::DoubleEndedIterator for #iter_mut_ident #view_ty_generics #where_clause {
+            fn next_back(&mut self) -> ::std::option::Option<Self::Item> {
+                #( let #column_locals = self.#column_idents.next_back()?; )*
+                ::std::option::Option::Some(#view_mut_ident { #( #mut_inits, )* })
+            }
+        }
+
+        impl #view_impl_generics ::std::iter::ExactSizeIterator for #iter_mut_ident #view_ty_generics #where_clause {}
+
+        // Bulk construction and AoS interop
+        impl #impl_generics #soa_ty #where_clause {
+            /// Reserve room for at least `additional` more rows in every column
+            #vis fn reserve(&mut self, additional: usize) {
+                #( soa_runtime::Column::reserve(&mut self.#column_idents, additional); )*
+            }
+            /// Owned copy of row `i`; panics if `i` is out of bounds
+            #vis fn get_owned(&self, i: usize) -> #item_ty
+            where
+                #( #clone_bounds, )*
+            {
+                #ident { #( #owned_fields, )* #( #skipped_defaults, )* }
+            }
+            /// Copy every row back into an array of structs
+            #vis fn to_aos(&self) -> ::std::vec::Vec<#item_ty>
+            where
+                #( #clone_bounds, )*
+            {
+                let mut out = ::std::vec::Vec::with_capacity(self.len());
+                out.extend((0..self.len()).map(|i| self.get_owned(i)));
+                out
+            }
+            /// Move every row back into an array of structs
+            #vis fn into_aos(self) -> ::std::vec::Vec<#item_ty> {
+                self.into_iter().collect()
+            }
+            /// Move an array of structs into columns
+            #vis fn from_aos(items: ::std::vec::Vec<#item_ty>) -> Self {
+                items.into_iter().collect()
+            }
+            // Higher-ranked so the bound is only checked at call sites: the domain struct
+            // itself need not be `Clone`
+            #vis fn extend_from_slice(&mut self, items: &[#item_ty])
+            where
+                for<'__soa> #item_ty: ::std::clone::Clone,
+            {
+                self.extend(items.iter().cloned());
+            }
+        }
+
+        impl #impl_generics ::std::iter::Extend<#item_ty> for #soa_ty #where_clause {
+            fn extend<I: ::std::iter::IntoIterator<Item = #item_ty>>(&mut self, iter: I) {
+                let iter = iter.into_iter();
+                self.reserve(iter.size_hint().0);
+                for v in iter {
+                    self.push(v);
+                }
+            }
+        }
+
+        impl #impl_generics ::std::iter::FromIterator<#item_ty> for #soa_ty #where_clause {
+            fn from_iter<I: ::std::iter::IntoIterator<Item = #item_ty>>(iter: I) -> Self {
+                let mut soa = Self::new();
+                soa.extend(iter);
+                soa
+            }
+        }
+
+        #[doc = #into_iter_doc]
+        #vis struct #into_iter_ident #generics #where_clause {
+            #( #column_idents: <#storage_types as ::std::iter::IntoIterator>::IntoIter, )*
+        }
+
+        impl #impl_generics ::std::iter::Iterator for #into_iter_ident #ty_generics #where_clause {
+            type Item = #item_ty;
+
+            fn next(&mut self) -> ::std::option::Option<#item_ty> {
+                #( let #column_locals = self.#column_idents.next()?; )*
+                ::std::option::Option::Some(#ident { #( #owned_inits, )* #( #skipped_defaults, )* })
+            }
+
+            fn size_hint(&self) -> (usize, ::std::option::Option<usize>) {
+                self.#first_column.size_hint()
+            }
+        }
+
+        impl #impl_generics ::std::iter::DoubleEndedIterator for #into_iter_ident #ty_generics #where_clause {
+            fn next_back(&mut self) -> ::std::option::Option<#item_ty> {
+                #( let #column_locals = self.#column_idents.next_back()?; )*
+                ::std::option::Option::Some(#ident { #( #owned_inits, )* #( #skipped_defaults, )* })
+            }
+        }
+
+        impl #impl_generics ::std::iter::ExactSizeIterator for #into_iter_ident #ty_generics #where_clause {}
+
+        impl #impl_generics ::std::iter::IntoIterator for #soa_ty #where_clause {
+            type Item = #item_ty;
+            type IntoIter = #into_iter_ident #ty_generics;
+
+            fn into_iter(self) -> Self::IntoIter {
+                #into_iter_ident {
+                    #( #column_idents: ::std::iter::IntoIterator::into_iter(self.#column_idents), )*
+                }
+            }
+        }
+
+        // Row removal, applied to every column in lockstep
+        impl #impl_generics #soa_ty #where_clause {
+            /// Remove row `i` and return it, moving the last row into its place
+            #vis fn swap_remove(&mut self, i: usize) -> #item_ty {
+                #( let #column_locals = soa_runtime::Column::swap_remove(&mut self.#column_idents, i); )*
+                #ident { #( #owned_inits, )* #( #skipped_defaults, )* }
+            }
+            /// Remove row `i` and return it, shifting every later row down by one
+            #vis fn remove(&mut self, i: usize) -> #item_ty {
+                #( let #column_locals = soa_runtime::Column::remove(&mut self.#column_idents, i); )*
+                #ident { #( #owned_inits, )* #( #skipped_defaults, )* }
+            }
+            /// Keep only the rows for which `f` returns `true`, preserving their order
+            #vis fn retain<F>(&mut self, mut f: F)
+            where
+                F: FnMut(#view_ident #anon_generics) -> bool,
+            {
+                let keep: ::std::vec::Vec<bool> = (0..self.len()).map(|i| f(self.view(i))).collect();
+                if keep.iter().all(|&k| k) {
+                    return;
+                }
+                #( soa_runtime::Column::retain_mask(&mut self.#column_idents, &keep); )*
+            }
+            /// Keep only the first `len` rows
+            #vis fn truncate(&mut self, len: usize) {
+                #( soa_runtime::Column::truncate(&mut self.#column_idents, len); )*
+            }
+            #vis fn clear(&mut self) {
+                #( soa_runtime::Column::clear(&mut self.#column_idents); )*
+            }
+            /// Move every row of `other` to the end, leaving `other` empty
+            #vis fn append(&mut self, other: &mut Self) {
+                #( soa_runtime::Column::append(&mut self.#column_idents, &mut other.#column_idents); )*
+            }
+            /// Remove the rows in `range` and return them in order
+            #vis fn drain<R>(&mut self, range: R) -> ::std::vec::IntoIter<#item_ty>
+            where
+                R: ::std::ops::RangeBounds<usize>,
+            {
+                let range = soa_runtime::resolve_range(range, self.len());
+                #( let mut #drained =
+                    soa_runtime::Column::drain(&mut self.#column_idents, range.clone()).into_iter(); )*
+                range
+                    .map(|_| {
+                        #( let #column_locals = #drained.next().unwrap(); )*
+                        #ident { #( #owned_inits, )* #( #skipped_defaults, )* }
+                    })
+                    .collect::<::std::vec::Vec<_>>()
+                    .into_iter()
+            }
+        }
+
+        // Raw array accessor methods for performance optimizations
+        impl #impl_generics #soa_ty #where_clause {
+            #( #raw_array_methods )*
+        }
+
+        #vis struct #view_ident #view_generics #where_clause { #( #view_fields, )* }
+        #vis struct #view_mut_ident #view_generics #where_clause { #( #view_mut_fields, )* }
+
+        impl #impl_generics soa_runtime::SoaModel for #item_ty #where_clause {
+            type Soa = #soa_ty;
+            type View<#lt> = #view_ident #view_ty_generics where Self: #lt;
+            type ViewMut<#lt> = #view_mut_ident #view_ty_generics where Self: #lt;
+
+            fn push_into(soa: &mut Self::Soa, v: Self) {
+                soa.push(v);
+            }
+            fn view(soa: &Self::Soa, i: usize) -> Self::View<'_> {
+                soa.view(i)
+            }
+            fn view_mut(soa: &mut Self::Soa, i: usize) -> Self::ViewMut<'_> {
+                soa.view_mut(i)
+            }
+        }
+
+        #packed_record
+
+        #hot_cold
+
+        #tiled
+
+        #parallel
+    };
+
+    Ok(expanded)
+}
+
+/// Which stored fields `#[soa(pack(...))]` moves into the packed record
+fn packed_fields(
+    ident: &Ident,
+    generics: &Generics,
+    pack: &[Ident],
+    field_idents: &[Ident],
+    kinds: &[ColumnKind<'_>],
+    skipped_fields: &[(&syn::Field, &FieldAttrs)],
+) -> syn::Result<Vec<bool>> {
+    if pack.is_empty() {
+        return Ok(vec![false; field_idents.len()]);
+    }
+    if !generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            generics,
+            "`#[soa(pack(...))]` is not supported on generic structs",
+        ));
+    }
+    if let Some(clash) = field_idents.iter().find(|id| *id == "packed") {
+        return Err(syn::Error::new_spanned(
+            clash,
+            "a field named `packed` clashes with the column generated for `#[soa(pack(...))]`",
+        ));
+    }
+
+    for name in pack {
+        if skipped_fields
+            .iter()
+            .any(|(f, _)| f.ident.as_ref() == Some(name))
+        {
+            return Err(syn::Error::new_spanned(
+                name,
+                "a `#[soa(skip)]` field cannot be packed",
+            ));
+        }
+        let Some(i) = field_idents.iter().position(|id| id == name) else {
+            return Err(syn::Error::new_spanned(
+                name,
+                format!("`{}` has no field named `{}`", ident, name),
+            ));
+        };
+        if !matches!(kinds[i], ColumnKind::Plain) {
+            return Err(syn::Error::new_spanned(
+                name,
+                "only plain fields can be packed; `Option`, `String` and `Vec<u8>` fields keep \
+                 their own columns",
+            ));
+        }
+    }
+
+    Ok(field_idents.iter().map(|id| pack.contains(id)).collect())
+}
+
+/// Packed field names, in declaration order
+fn packed_idents<'a>(field_idents: &'a [Ident], packed: &[bool]) -> Vec<&'a Ident> {
+    field_idents
+        .iter()
+        .zip(packed)
+        .filter(|(_, &is_packed)| is_packed)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Lifetime for views and borrowing iterators, avoiding a clash with the struct's own `'a`
+fn view_lifetime(generics: &Generics) -> Lifetime {
+    let name = if generics.lifetimes().any(|l| l.lifetime.ident == "a") {
+        "'__soa"
+    } else {
+        "'a"
+    };
+    Lifetime::new(name, proc_macro2::Span::call_site())
+}
+
+/// `generics` with `lt` prepended, for the view types
+fn with_lifetime(generics: &Generics, lt: &Lifetime) -> Generics {
+    let mut generics = generics.clone();
+    generics
+        .params
+        .insert(0, GenericParam::Lifetime(LifetimeParam::new(lt.clone())));
+    generics
+}
+
+/// `<'_, params...>`: the view type arguments with an elided leading lifetime
+fn anonymous_lifetime_args(generics: &Generics) -> TokenStream {
+    let args = generics.params.iter().map(|param| match param {
+        GenericParam::Lifetime(l) => {
+            let lifetime = &l.lifetime;
+            quote! { #lifetime }
+        }
+        GenericParam::Type(t) => {
+            let ident = &t.ident;
+            quote! { #ident }
+        }
+        GenericParam::Const(c) => {
+            let ident = &c.ident;
+            quote! { #ident }
+        }
+    });
+    quote! { <'_ #( , #args )*> }
+}
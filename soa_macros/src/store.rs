@@ -0,0 +1,1038 @@
+//! Expansion for `#[derive(SoAStore)]`: the copy-on-write `*Store` repository keyed by
+//! `#[soa_store(key = "...")]` with the secondary indexes requested by `#[soa(index = "...")]`,
+//! the hash-sharded `*ShardedStore`, and its lock-per-shard sibling `*ConcurrentStore`.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Comma;
+use syn::{Data, DeriveInput, Field, Fields, Ident, LitInt, LitStr, Path, Type, Visibility};
+
+use crate::attrs::{FieldAttrs, IndexKind};
+use crate::column::ColumnKind;
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = input.ident;
+    let vis = input.vis;
+
+    // Validate struct with named fields
+    let fields = match input.data {
+        Data::Struct(s) => match s.fields {
+            Fields::Named(named) => named.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    s.fields,
+                    "SoAStore derive requires a braced struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &ident,
+                "SoAStore derive works only on structs",
+            ))
+        }
+    };
+
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "SoAStore derive does not support generic structs",
+        ));
+    }
+
+    // Defaults
+    let mut shard_key = Ident::new("id", ident.span());
+    let mut shards_default: usize = 16;
+    let mut shards_attr = None;
+    let mut hasher: Option<Path> = None;
+    let mut partition = Partition::Hash;
+
+    // Parse: #[soa_store(key = "id", shards = 16, hasher = "path::To", partition = "hash")]
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("soa_store"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                let lit: LitStr = meta.value()?.parse()?;
+                shard_key = Ident::new(&lit.value(), lit.span());
+                Ok(())
+            } else if meta.path.is_ident("shards") {
+                let lit: LitInt = meta.value()?.parse()?;
+                shards_default = lit.base10_parse::<usize>()?;
+                if shards_default == 0 {
+                    return Err(syn::Error::new(
+                        lit.span(),
+                        "soa_store shards must be at least 1",
+                    ));
+                }
+                shards_attr = Some(lit);
+                Ok(())
+            } else if meta.path.is_ident("hasher") {
+                let lit: LitStr = meta.value()?.parse()?;
+                hasher = Some(lit.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("partition") {
+                let lit: LitStr = meta.value()?.parse()?;
+                partition = match lit.value().as_str() {
+                    "hash" => Partition::Hash,
+                    "jump" => Partition::Jump,
+                    "range" => Partition::Range,
+                    _ => {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "soa_store partition must be \"hash\", \"jump\" or \"range\"",
+                        ))
+                    }
+                };
+                Ok(())
+            } else {
+                Err(meta.error(
+                    "unknown attribute for soa_store (expected `key`, `shards`, `hasher` or \
+                     `partition`)",
+                ))
+            }
+        })?;
+    }
+
+    // Validate shard key exists and has a column
+    let Some(key_field) = fields.iter().find(|f| f.ident.as_ref() == Some(&shard_key)) else {
+        return Err(syn::Error::new(
+            shard_key.span(),
+            "soa_store key must be a field of the struct",
+        ));
+    };
+    if FieldAttrs::parse(&key_field.attrs)?.skip {
+        return Err(syn::Error::new(
+            shard_key.span(),
+            "soa_store key cannot be `#[soa(skip)]`",
+        ));
+    }
+    let key_ty = key_field.ty.clone();
+
+    // Range-partitioned stores route by the boundaries given to `with_ranges`
+    if partition == Partition::Range {
+        if let Some(path) = &hasher {
+            return Err(syn::Error::new_spanned(
+                path,
+                "`hasher` does not apply to `partition = \"range\"`",
+            ));
+        }
+        if let Some(lit) = &shards_attr {
+            return Err(syn::Error::new(
+                lit.span(),
+                "`partition = \"range\"` stores get one shard per key range; pass the \
+                 boundaries to `with_ranges` instead of `shards`",
+            ));
+        }
+    }
+    let key_kind = ColumnKind::of(&key_ty);
+    if let ColumnKind::Nullable(_) = key_kind {
+        return Err(syn::Error::new(
+            shard_key.span(),
+            "soa_store key cannot be an `Option`",
+        ));
+    }
+
+    let indexes = secondary_indexes(&fields)?;
+
+    let soa_ident = format_ident!("{}SoA", ident);
+    let view_ident = format_ident!("{}View", ident);
+    let view_mut_ident = format_ident!("{}Mut", ident);
+    let store_ident = format_ident!("{}Store", ident);
+    let sharded_ident = format_ident!("{}ShardedStore", ident);
+    let concurrent_ident = format_ident!("{}ConcurrentStore", ident);
+    let partitioning = Partitioning {
+        soa_ident: &soa_ident,
+        key_ty: &key_ty,
+        vis: &vis,
+        mode: partition,
+        hasher: hasher.map(|p| quote! { #p }),
+    };
+    let bounds_decl = partitioning
+        .is_range()
+        .then(|| quote! { bounds: ::std::vec::Vec<#key_ty>, });
+    let bounds_clone = partitioning
+        .is_range()
+        .then(|| quote! { bounds: ::std::clone::Clone::clone(&self.bounds), });
+    let bounds_move = partitioning
+        .is_range()
+        .then(|| quote! { bounds: self.bounds, });
+    let bounds_from = partitioning
+        .is_range()
+        .then(|| quote! { bounds: sharded.bounds, });
+    let sharded_ctors = partitioning.constructors(
+        shards_default,
+        |soa| quote! { soa_runtime::CachePadded(#soa) },
+        quote! { key_indexes: shards.iter().map(|_| ::std::option::Option::None).collect(), },
+    );
+    let concurrent_ctors = partitioning.constructors(
+        shards_default,
+        |soa| quote! { soa_runtime::CachePadded(::std::sync::RwLock::new((#soa, ::std::option::Option::None))) },
+        quote! {},
+    );
+    let shard_index_ty = quote! {
+        ::std::collections::HashMap<#key_ty, ::std::vec::Vec<usize>>
+    };
+    // Remove a row with key `key` from `shard`, finding it through the shard's key index
+    // `slot` (an `&mut Option<map>`, built here on first use)
+    let remove_indexed = quote! {
+        let index = slot.get_or_insert_with(|| {
+            let mut index = <#shard_index_ty>::new();
+            for i in 0..shard.len() {
+                index
+                    .entry(::std::borrow::ToOwned::to_owned(shard.view(i).#shard_key))
+                    .or_insert_with(::std::vec::Vec::new)
+                    .push(i);
+            }
+            index
+        });
+        let rows = index.get_mut(key)?;
+        let i = rows.pop().unwrap();
+        if rows.is_empty() {
+            index.remove(key);
+        }
+        let removed = shard.swap_remove(i);
+        let last = shard.len();
+        if i < last {
+            if let ::std::option::Option::Some(rows) = index.get_mut(shard.view(i).#shard_key) {
+                if let ::std::option::Option::Some(p) = rows.iter().position(|&r| r == last) {
+                    rows[p] = i;
+                }
+            }
+        }
+        ::std::option::Option::Some(removed)
+    };
+    // Record the row about to be pushed for `v` in the shard's key index, if it is built
+    let link_indexed = quote! {
+        if let ::std::option::Option::Some(index) = slot {
+            index
+                .entry(::std::clone::Clone::clone(&v.#shard_key))
+                .or_insert_with(::std::vec::Vec::new)
+                .push(shard.len());
+        }
+    };
+    let concurrent_default = if partitioning.is_range() {
+        quote! { Self::with_ranges(::std::vec::Vec::new(), 0) }
+    } else {
+        quote! { Self::with_shards(Self::DEFAULT_SHARDS, 0) }
+    };
+    let route = partitioning.route(quote! { self.shards.len() }, quote! { self.bounds });
+    // Range routing compares with owned boundaries, so string and byte keys read from a view
+    // are converted first; hashing a `&str` matches hashing its `String`
+    let view_key = if partitioning.is_range() && !matches!(key_kind, ColumnKind::Plain) {
+        quote! { &::std::borrow::ToOwned::to_owned(shard.view(i).#shard_key) }
+    } else {
+        quote! { shard.view(i).#shard_key }
+    };
+    let rebalance_route = partitioning.route(quote! { n }, quote! { bounds });
+    let bind_bounds = partitioning
+        .is_range()
+        .then(|| quote! { let bounds = &self.bounds; });
+    let reshard = if partitioning.is_range() {
+        quote! {
+            /// Replace the range boundaries (see `with_ranges`) and move every row to its new
+            /// shard; returns the number of rows moved
+            #vis fn reshard_ranges(&mut self, bounds: ::std::vec::Vec<#key_ty>) -> usize {
+                assert!(
+                    bounds.windows(2).all(|w| w[0] < w[1]),
+                    "shard range boundaries must be strictly ascending"
+                );
+                let n = bounds.len() + 1;
+                self.bounds = bounds;
+                self.rebalance(n)
+            }
+        }
+    } else {
+        quote! {
+            /// Change the number of shards to `n` and move every row whose shard changed;
+            /// returns the number of rows moved. With `partition = "jump"` only about
+            /// `|n - shard_count()| / max(n, shard_count())` of the rows move
+            #vis fn reshard(&mut self, n: usize) -> usize {
+                assert!(n > 0, "a sharded store needs at least one shard");
+                self.rebalance(n)
+            }
+        }
+    };
+    // Rayon-backed shard iteration, generated with `soa_macros/parallel`
+    let par_shards = cfg!(feature = "parallel").then(|| {
+        quote! {
+            /// Shards, processed in parallel
+            #vis fn par_shards(
+                &self,
+            ) -> impl soa_runtime::rayon::iter::IndexedParallelIterator<Item = &#soa_ident> + '_
+            where
+                // Higher-ranked so rows that are not `Sync` only fail at the call site
+                for<'s> #soa_ident: ::std::marker::Sync,
+            {
+                use soa_runtime::rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+                self.shards.par_iter().map(|shard| &shard.0)
+            }
+            /// Shards, mutated in parallel
+            #vis fn par_shards_mut(
+                &mut self,
+            ) -> impl soa_runtime::rayon::iter::IndexedParallelIterator<Item = &mut #soa_ident> + '_
+            where
+                for<'s> #soa_ident: ::std::marker::Send,
+            {
+                use soa_runtime::rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+                self.key_indexes.iter_mut().for_each(|slot| *slot = ::std::option::Option::None);
+                self.shards.par_iter_mut().map(|shard| &mut shard.0)
+            }
+        }
+    });
+    let concurrent_doc = format!(
+        "[`{}`] writable from many threads through `&self`: each shard sits behind its own \
+         `RwLock`, so writers to different shards never contend",
+        sharded_ident
+    );
+
+    // Only plain keys are writable through a mutable view. If `f` moved the row to a key
+    // already in use, every field `f` could write is put back before any index is touched;
+    // otherwise the row is re-indexed under its new key
+    let rekeyable = matches!(key_kind, ColumnKind::Plain);
+    let writable = writable_fields(&fields)?;
+    let rollback_restores = writable.iter().map(|(field, plain)| {
+        let before = format_ident!("before_{}", field);
+        if *plain {
+            quote! { *inner.view_mut(i).#field = #before; }
+        } else {
+            let setter = format_ident!("set_{}", field);
+            quote! { inner.#setter(i, #before); }
+        }
+    });
+    let check_rekey = rekeyable.then(|| {
+        quote! {
+            if *inner.view(i).#shard_key != *key {
+                if let ::std::option::Option::Some(&row) = self.key_index.get().unwrap().get(inner.view(i).#shard_key) {
+                    let rejected = ::std::clone::Clone::clone(inner.view(i).#shard_key);
+                    #( #rollback_restores )*
+                    return ::std::result::Result::Err(soa_runtime::DuplicateKeyError { key: rejected, row });
+                }
+            }
+        }
+    });
+    let rekey = rekeyable.then(|| {
+        quote! {
+            if *inner.view(i).#shard_key != *key {
+                let index = ::std::sync::Arc::make_mut(self.key_index.get_mut().unwrap());
+                index.remove(key);
+                index.insert(::std::clone::Clone::clone(inner.view(i).#shard_key), i);
+            }
+        }
+    });
+
+    let index_idents: Vec<_> = indexes
+        .iter()
+        .map(|ix| format_ident!("index_{}", ix.field))
+        .collect();
+    let index_maps: Vec<_> = indexes.iter().map(SecondaryIndex::map_type).collect();
+    let index_methods = indexes.iter().zip(&index_idents).map(|(ix, index_ident)| {
+        let field = &ix.field;
+        let ty = &ix.ty;
+        let find = format_ident!("find_by_{}", field);
+        let order = match ix.kind {
+            IndexKind::Bitmap => "in row order",
+            IndexKind::Hash | IndexKind::BTree => "in no particular order",
+        };
+        let find_doc = format!("Rows whose `{}` equals `value`, {}", field, order);
+        let bound = match ix.kind {
+            IndexKind::Hash | IndexKind::Bitmap => quote! { ::std::hash::Hash + ::std::cmp::Eq },
+            IndexKind::BTree => quote! { ::std::cmp::Ord },
+        };
+        let rows_iter = ix.rows_iter();
+        let bitmap = (ix.kind == IndexKind::Bitmap).then(|| {
+            let bitmap = format_ident!("{}_bitmap", field);
+            let bitmap_doc = format!(
+                "Rows whose `{}` equals `value`, to combine with `&` / `|` and pass to [`Self::select`]",
+                field
+            );
+            quote! {
+                #[doc = #bitmap_doc]
+                #vis fn #bitmap<Q>(&self, value: &Q) -> &soa_runtime::RowSet
+                where
+                    #ty: ::std::borrow::Borrow<Q>,
+                    Q: #bound + ?::std::marker::Sized,
+                {
+                    static EMPTY: soa_runtime::RowSet = soa_runtime::RowSet::new();
+                    self.#index_ident().get(value).unwrap_or(&EMPTY)
+                }
+            }
+        });
+        let range = (ix.kind == IndexKind::BTree).then(|| {
+            let range = format_ident!("range_by_{}", field);
+            let range_doc = format!("Rows whose `{}` lies in `range`, in `{}` order", field, field);
+            quote! {
+                #[doc = #range_doc]
+                #vis fn #range<R>(&self, range: R) -> impl ::std::iter::Iterator<Item = #view_ident<'_>> + '_
+                where
+                    R: ::std::ops::RangeBounds<#ty>,
+                {
+                    self.#index_ident()
+                        .range(range)
+                        .flat_map(|(_, rows)| #rows_iter)
+                        .map(move |i| self.inner.view(i))
+                }
+            }
+        });
+        quote! {
+            #[doc = #find_doc]
+            #vis fn #find<Q>(&self, value: &Q) -> impl ::std::iter::Iterator<Item = #view_ident<'_>> + '_
+            where
+                #ty: ::std::borrow::Borrow<Q>,
+                Q: #bound + ?::std::marker::Sized,
+            {
+                self.#index_ident()
+                    .get(value)
+                    .into_iter()
+                    .flat_map(|rows| #rows_iter)
+                    .map(move |i| self.inner.view(i))
+            }
+            #range
+            #bitmap
+        }
+    });
+
+    // Values before `f` ran: every writable field when a duplicate key must be rolled back,
+    // otherwise only the indexed plain fields `reindex_updates` compares
+    let reindex_snapshots = writable
+        .iter()
+        .filter(|(field, plain)| {
+            rekeyable || (*plain && indexes.iter().any(|ix| ix.plain && ix.field == *field))
+        })
+        .map(|(field, plain)| {
+            let before = format_ident!("before_{}", field);
+            if *plain {
+                quote! { let #before = ::std::clone::Clone::clone(inner.view(i).#field); }
+            } else {
+                quote! { let #before = inner.view(i).#field.cloned(); }
+            }
+        });
+    let reindex_updates = indexes
+        .iter()
+        .zip(&index_idents)
+        .filter(|(ix, _)| ix.plain)
+        .map(|(ix, index_ident)| {
+            let field = &ix.field;
+            let before = format_ident!("before_{}", field);
+            let unlink = ix.unlink(quote! { &#before }, quote! { i });
+            let link = ix.link(
+                quote! { ::std::clone::Clone::clone(inner.view(i).#field) },
+                quote! { i },
+            );
+            quote! {
+                if *inner.view(i).#field != #before {
+                    if let ::std::option::Option::Some(index) = self.#index_ident.get_mut() {
+                        let index = ::std::sync::Arc::make_mut(index);
+                        #unlink
+                        #link
+                    }
+                }
+            }
+        });
+
+    let link_added = indexes.iter().map(|ix| {
+        let field = &ix.field;
+        ix.link(
+            quote! { ::std::borrow::ToOwned::to_owned(inner.view(row).#field) },
+            quote! { row },
+        )
+    });
+    // The removed row leaves its bucket; the last row, moved into slot `i`, changes buckets
+    let relink_removed = indexes.iter().map(|ix| {
+        let field = &ix.field;
+        let unlink_removed = ix.unlink(quote! { &removed.#field }, quote! { i });
+        let unlink_moved = ix.unlink(quote! { inner.view(i).#field }, quote! { last });
+        let link_moved = ix.link(
+            quote! { ::std::borrow::ToOwned::to_owned(inner.view(i).#field) },
+            quote! { i },
+        );
+        quote! {
+            #unlink_removed
+            if i < last {
+                #unlink_moved
+                #link_moved
+            }
+        }
+    });
+    let link_all = indexes.iter().map(|ix| {
+        let field = &ix.field;
+        ix.link(
+            quote! { ::std::borrow::ToOwned::to_owned(self.inner.view(i).#field) },
+            quote! { i },
+        )
+    });
+
+    Ok(quote! {
+        #vis struct #store_ident {
+            inner: ::std::sync::Arc<#soa_ident>,
+            // key -> row, built on first use and kept current by the store's own mutations.
+            // Shared between clones like `inner` and copied on their first write
+            key_index: ::std::sync::OnceLock<::std::sync::Arc<::std::collections::HashMap<#key_ty, usize>>>,
+            // value -> rows for each `#[soa(index = "...")]` field, built and kept the same way
+            #( #index_idents: ::std::sync::OnceLock<::std::sync::Arc<#index_maps>>, )*
+        }
+
+        // Clones share the rows and every built index, so cloning is O(1)
+        impl ::std::clone::Clone for #store_ident {
+            fn clone(&self) -> Self {
+                Self {
+                    inner: self.inner.clone(),
+                    key_index: self.key_index.clone(),
+                    #( #index_idents: self.#index_idents.clone(), )*
+                }
+            }
+        }
+        impl ::std::default::Default for #store_ident {
+            fn default() -> Self {
+                Self {
+                    inner: ::std::sync::Arc::new(#soa_ident::new()),
+                    key_index: ::std::sync::OnceLock::new(),
+                    #( #index_idents: ::std::sync::OnceLock::new(), )*
+                }
+            }
+        }
+
+        impl #store_ident {
+            #vis fn new() -> Self { Self::default() }
+            /// Add a row, rejecting it if its key is already present
+            #vis fn add(&mut self, v: #ident) -> ::std::result::Result<usize, soa_runtime::DuplicateKeyError<#key_ty>> {
+                if let ::std::option::Option::Some(&row) = self.key_index().get(&v.#shard_key) {
+                    return ::std::result::Result::Err(soa_runtime::DuplicateKeyError { key: v.#shard_key, row });
+                }
+                let key = ::std::clone::Clone::clone(&v.#shard_key);
+                let inner = ::std::sync::Arc::make_mut(&mut self.inner);
+                let row = inner.push(v);
+                ::std::sync::Arc::make_mut(self.key_index.get_mut().unwrap()).insert(key, row);
+                #(
+                    if let ::std::option::Option::Some(index) = self.#index_idents.get_mut() {
+                        let index = ::std::sync::Arc::make_mut(index);
+                        #link_added
+                    }
+                )*
+                ::std::result::Result::Ok(row)
+            }
+            #vis fn contains_key(&self, key: &#key_ty) -> bool {
+                self.key_index().contains_key(key)
+            }
+            #vis fn get_by_key(&self, key: &#key_ty) -> ::std::option::Option<#view_ident<'_>> {
+                let &i = self.key_index().get(key)?;
+                ::std::option::Option::Some(self.inner.view(i))
+            }
+            /// Apply `f` to a mutable view of the row whose key equals `key`; `Ok(None)` if
+            /// there is no such row. If `f` changes the key the row is re-indexed; when the new
+            /// key is already in use every change `f` made is undone and `Err` is returned.
+            #vis fn update_by_key<R, F>(
+                &mut self,
+                key: &#key_ty,
+                f: F,
+            ) -> ::std::result::Result<::std::option::Option<R>, soa_runtime::DuplicateKeyError<#key_ty>>
+            where
+                F: ::std::ops::FnOnce(#view_mut_ident<'_>) -> R,
+            {
+                let ::std::option::Option::Some(&i) = self.key_index().get(key) else {
+                    return ::std::result::Result::Ok(::std::option::Option::None);
+                };
+                let inner = ::std::sync::Arc::make_mut(&mut self.inner);
+                #( #reindex_snapshots )*
+                let out = f(inner.view_mut(i));
+                #check_rekey
+                #( #reindex_updates )*
+                #rekey
+                ::std::result::Result::Ok(::std::option::Option::Some(out))
+            }
+            /// Remove the row whose key equals `key` and return it.
+            /// The last row is moved into the freed slot, so its row index changes.
+            #vis fn remove_by_key(&mut self, key: &#key_ty) -> ::std::option::Option<#ident> {
+                let &i = self.key_index().get(key)?;
+                let inner = ::std::sync::Arc::make_mut(&mut self.inner);
+                let removed = inner.swap_remove(i);
+                let last = inner.len();
+                let index = ::std::sync::Arc::make_mut(self.key_index.get_mut().unwrap());
+                index.remove(key);
+                if i < last {
+                    if let ::std::option::Option::Some(row) = index.get_mut(inner.view(i).#shard_key) {
+                        *row = i;
+                    }
+                }
+                #(
+                    if let ::std::option::Option::Some(index) = self.#index_idents.get_mut() {
+                        let index = ::std::sync::Arc::make_mut(index);
+                        #relink_removed
+                    }
+                )*
+                ::std::option::Option::Some(removed)
+            }
+            #vis fn kernel(&self) -> &#soa_ident { &self.inner }
+            /// Mutable access to the rows; the key and secondary indexes are rebuilt on next use
+            #vis fn kernel_mut(&mut self) -> &mut #soa_ident {
+                self.key_index.take();
+                #( self.#index_idents.take(); )*
+                ::std::sync::Arc::make_mut(&mut self.inner)
+            }
+            /// Views of the rows in `rows`, in row order
+            #vis fn select<'a>(&'a self, rows: &'a soa_runtime::RowSet) -> impl ::std::iter::Iterator<Item = #view_ident<'a>> + 'a {
+                rows.iter().map(move |i| self.inner.view(i))
+            }
+            #( #index_methods )*
+
+            fn key_index(&self) -> &::std::collections::HashMap<#key_ty, usize> {
+                self.key_index.get_or_init(|| {
+                    ::std::sync::Arc::new(
+                        (0..self.inner.len())
+                            .map(|i| (::std::borrow::ToOwned::to_owned(self.inner.view(i).#shard_key), i))
+                            .collect(),
+                    )
+                })
+            }
+            #(
+                fn #index_idents(&self) -> &#index_maps {
+                    self.#index_idents.get_or_init(|| {
+                        let mut index = <#index_maps>::new();
+                        for i in 0..self.inner.len() {
+                            #link_all
+                        }
+                        ::std::sync::Arc::new(index)
+                    })
+                }
+            )*
+        }
+
+        #vis struct #sharded_ident {
+            shards: ::std::vec::Vec<soa_runtime::CachePadded<#soa_ident>>,
+            // key -> rows of each shard, built on the first `remove_by_key` and dropped by
+            // writes the store cannot see (`shard_mut`, `par_shards_mut`, resharding)
+            key_indexes: ::std::vec::Vec<::std::option::Option<#shard_index_ty>>,
+            #bounds_decl
+        }
+
+        impl #sharded_ident {
+            #sharded_ctors
+
+            /// Shard that holds (or would hold) the row with key `key`
+            #[inline]
+            #vis fn shard_for_key(&self, key: &#key_ty) -> usize {
+                #route
+            }
+
+            #vis fn add(&mut self, v: #ident) -> (usize, usize) {
+                let si = self.shard_for_key(&v.#shard_key);
+                let (shard, slot) = (&mut self.shards[si].0, &mut self.key_indexes[si]);
+                #link_indexed
+                let row = shard.push(v);
+                (si, row)
+            }
+
+            /// Remove a row whose key equals `key` from its shard and return it, found through
+            /// the shard's key index. The shard's last row is moved into the freed slot, so its
+            /// row index changes.
+            #vis fn remove_by_key(&mut self, key: &#key_ty) -> ::std::option::Option<#ident> {
+                let si = self.shard_for_key(key);
+                let (shard, slot) = (&mut self.shards[si].0, &mut self.key_indexes[si]);
+                #remove_indexed
+            }
+
+            #vis fn shard_count(&self) -> usize { self.shards.len() }
+            #vis fn shard(&self, i: usize) -> &#soa_ident { &self.shards[i].0 }
+            /// Mutable access to shard `i`; its key index is rebuilt on next use
+            #vis fn shard_mut(&mut self, i: usize) -> &mut #soa_ident {
+                self.key_indexes[i] = ::std::option::Option::None;
+                &mut self.shards[i].0
+            }
+
+            /// Rows per shard
+            #vis fn shard_sizes(&self) -> ::std::vec::Vec<usize> {
+                self.shards.iter().map(|shard| shard.0.len()).collect()
+            }
+            /// Largest shard relative to the mean shard size: `1.0` when balanced (or empty),
+            /// `shard_count()` when every row sits in one shard
+            #vis fn skew(&self) -> f64 {
+                soa_runtime::shard_skew(&self.shard_sizes())
+            }
+
+            #reshard
+            #par_shards
+
+            /// Grow or shrink to `n` shards and move each row to the shard `route` picks for it
+            fn rebalance(&mut self, n: usize) -> usize {
+                while self.shards.len() < n {
+                    self.shards.push(soa_runtime::CachePadded(#soa_ident::new()));
+                }
+                #bind_bounds
+                let mut moved = ::std::vec::Vec::new();
+                for (s, shard) in self.shards.iter_mut().enumerate() {
+                    let shard = &mut shard.0;
+                    // Back to front, so `swap_remove` only pulls in rows already visited
+                    for i in (0..shard.len()).rev() {
+                        let key = #view_key;
+                        let to = #rebalance_route;
+                        if to != s {
+                            moved.push((to, shard.swap_remove(i)));
+                        }
+                    }
+                }
+                self.shards.truncate(n);
+                self.key_indexes = self.shards.iter().map(|_| ::std::option::Option::None).collect();
+                let count = moved.len();
+                for (to, v) in moved {
+                    self.shards[to].0.push(v);
+                }
+                count
+            }
+        }
+
+        #[doc = #concurrent_doc]
+        #vis struct #concurrent_ident {
+            // Each shard is locked together with its key index, kept as in the sharded store
+            shards: ::std::vec::Vec<
+                soa_runtime::CachePadded<
+                    ::std::sync::RwLock<(#soa_ident, ::std::option::Option<#shard_index_ty>)>,
+                >,
+            >,
+            #bounds_decl
+        }
+
+        impl ::std::default::Default for #concurrent_ident {
+            fn default() -> Self {
+                #concurrent_default
+            }
+        }
+
+        impl #concurrent_ident {
+            #concurrent_ctors
+
+            #vis fn new() -> Self { Self::default() }
+
+            // A panic while a shard was locked leaves its columns consistent (every mutation
+            // goes through the SoA's own methods), so poisoned locks are not treated as errors
+            fn read(
+                &self,
+                i: usize,
+            ) -> ::std::sync::RwLockReadGuard<'_, (#soa_ident, ::std::option::Option<#shard_index_ty>)> {
+                self.shards[i].0.read().unwrap_or_else(::std::sync::PoisonError::into_inner)
+            }
+            fn write(
+                &self,
+                i: usize,
+            ) -> ::std::sync::RwLockWriteGuard<'_, (#soa_ident, ::std::option::Option<#shard_index_ty>)> {
+                self.shards[i].0.write().unwrap_or_else(::std::sync::PoisonError::into_inner)
+            }
+
+            /// Shard that holds (or would hold) the row with key `key`; the same shard as in
+            /// the single-threaded sharded store
+            #[inline]
+            #vis fn shard_for_key(&self, key: &#key_ty) -> usize {
+                #route
+            }
+
+            /// Add a row to the shard owning its key; returns `(shard, row)`
+            #vis fn add(&self, v: #ident) -> (usize, usize) {
+                let si = self.shard_for_key(&v.#shard_key);
+                let mut guard = self.write(si);
+                let (shard, slot) = &mut *guard;
+                #link_indexed
+                let row = shard.push(v);
+                (si, row)
+            }
+
+            /// Remove a row whose key equals `key` from its shard and return it, found through
+            /// the shard's key index. The shard's last row is moved into the freed slot, so its
+            /// row index changes.
+            #vis fn remove_by_key(&self, key: &#key_ty) -> ::std::option::Option<#ident> {
+                let mut guard = self.write(self.shard_for_key(key));
+                let (shard, slot) = &mut *guard;
+                #remove_indexed
+            }
+
+            #vis fn shard_count(&self) -> usize { self.shards.len() }
+
+            /// Total rows; shards are counted one after another, so concurrent writes may
+            /// or may not be included
+            #vis fn len(&self) -> usize {
+                self.shard_sizes().into_iter().sum()
+            }
+            /// Rows per shard, counted one shard after another
+            #vis fn shard_sizes(&self) -> ::std::vec::Vec<usize> {
+                (0..self.shards.len()).map(|i| self.read(i).0.len()).collect()
+            }
+            /// Largest shard relative to the mean shard size: `1.0` when balanced (or empty),
+            /// `shard_count()` when every row sits in one shard
+            #vis fn skew(&self) -> f64 {
+                soa_runtime::shard_skew(&self.shard_sizes())
+            }
+            #vis fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Run `f` on shard `i` under its read lock
+            #vis fn with_shard<R>(&self, i: usize, f: impl ::std::ops::FnOnce(&#soa_ident) -> R) -> R {
+                f(&self.read(i).0)
+            }
+            /// Run `f` on shard `i` under its write lock; the shard's key index is rebuilt on
+            /// next use
+            #vis fn with_shard_mut<R>(&self, i: usize, f: impl ::std::ops::FnOnce(&mut #soa_ident) -> R) -> R {
+                let mut guard = self.write(i);
+                guard.1 = ::std::option::Option::None;
+                f(&mut guard.0)
+            }
+
+            /// Copy of every shard taken while all of them are read-locked, so it reflects
+            /// one point in time across shards
+            #vis fn snapshot(&self) -> #sharded_ident {
+                let guards: ::std::vec::Vec<_> = (0..self.shards.len()).map(|i| self.read(i)).collect();
+                #sharded_ident {
+                    shards: guards
+                        .iter()
+                        .map(|shard| soa_runtime::CachePadded(::std::clone::Clone::clone(&shard.0)))
+                        .collect(),
+                    key_indexes: guards.iter().map(|shard| ::std::clone::Clone::clone(&shard.1)).collect(),
+                    #bounds_clone
+                }
+            }
+
+            /// Unwrap into the single-threaded store without copying, e.g. to `reshard` it
+            #vis fn into_sharded(self) -> #sharded_ident {
+                let (shards, key_indexes) = self
+                    .shards
+                    .into_iter()
+                    .map(|shard| {
+                        let (soa, index) = shard.0.into_inner().unwrap_or_else(::std::sync::PoisonError::into_inner);
+                        (soa_runtime::CachePadded(soa), index)
+                    })
+                    .unzip();
+                #sharded_ident {
+                    shards,
+                    key_indexes,
+                    #bounds_move
+                }
+            }
+        }
+
+        impl ::std::convert::From<#sharded_ident> for #concurrent_ident {
+            fn from(sharded: #sharded_ident) -> Self {
+                Self {
+                    shards: sharded
+                        .shards
+                        .into_iter()
+                        .zip(sharded.key_indexes)
+                        .map(|(shard, index)| soa_runtime::CachePadded(::std::sync::RwLock::new((shard.0, index))))
+                        .collect(),
+                    #bounds_from
+                }
+            }
+        }
+    })
+}
+
+/// A field declared with `#[soa(index = "...")]`.
+struct SecondaryIndex {
+    field: Ident,
+    ty: Type,
+    kind: IndexKind,
+    /// Writable through the `*Mut` view, so `update_by_key` may change it
+    plain: bool,
+}
+
+/// Stored fields a `*Mut` view can write, and whether each is plain (`&mut T`) rather than
+/// nullable (`Option<&mut T>`)
+fn writable_fields(fields: &Punctuated<Field, Comma>) -> syn::Result<Vec<(Ident, bool)>> {
+    let mut out = Vec::new();
+    for f in fields {
+        if FieldAttrs::parse(&f.attrs)?.skip {
+            continue;
+        }
+        match ColumnKind::of(&f.ty) {
+            ColumnKind::Plain => out.push((f.ident.clone().unwrap(), true)),
+            ColumnKind::Nullable(_) => out.push((f.ident.clone().unwrap(), false)),
+            ColumnKind::Utf8 | ColumnKind::Binary => {}
+        }
+    }
+    Ok(out)
+}
+
+fn secondary_indexes(fields: &Punctuated<Field, Comma>) -> syn::Result<Vec<SecondaryIndex>> {
+    let mut out = Vec::new();
+    for f in fields {
+        let Some(kind) = FieldAttrs::parse(&f.attrs)?.index else {
+            continue;
+        };
+        let field = f.ident.clone().unwrap();
+        let column = ColumnKind::of(&f.ty);
+        if let ColumnKind::Nullable(_) = column {
+            return Err(syn::Error::new_spanned(
+                &field,
+                "`#[soa(index)]` is not supported on `Option` fields",
+            ));
+        }
+        out.push(SecondaryIndex {
+            field,
+            ty: f.ty.clone(),
+            kind,
+            plain: matches!(column, ColumnKind::Plain),
+        });
+    }
+    Ok(out)
+}
+
+impl SecondaryIndex {
+    /// Map from field value to the rows holding it
+    fn map_type(&self) -> TokenStream {
+        let ty = &self.ty;
+        match self.kind {
+            IndexKind::Hash => quote! { ::std::collections::HashMap<#ty, ::std::vec::Vec<usize>> },
+            IndexKind::BTree => {
+                quote! { ::std::collections::BTreeMap<#ty, ::std::vec::Vec<usize>> }
+            }
+            IndexKind::Bitmap => quote! { ::std::collections::HashMap<#ty, soa_runtime::RowSet> },
+        }
+    }
+
+    /// Iterator over the `usize` rows of one bucket `rows`
+    fn rows_iter(&self) -> TokenStream {
+        match self.kind {
+            IndexKind::Hash | IndexKind::BTree => quote! { rows.iter().copied() },
+            IndexKind::Bitmap => quote! { rows.iter() },
+        }
+    }
+
+    /// Statement adding `row` to the bucket of the owned `value` in `index`
+    fn link(&self, value: TokenStream, row: TokenStream) -> TokenStream {
+        match self.kind {
+            IndexKind::Hash | IndexKind::BTree => {
+                quote! { index.entry(#value).or_default().push(#row); }
+            }
+            IndexKind::Bitmap => quote! { index.entry(#value).or_default().insert(#row); },
+        }
+    }
+
+    /// Statements dropping `row` from the bucket of `value` (a reference) in `index`, and the
+    /// bucket with it once it is empty
+    fn unlink(&self, value: TokenStream, row: TokenStream) -> TokenStream {
+        let remove = match self.kind {
+            IndexKind::Hash | IndexKind::BTree => quote! {
+                if let ::std::option::Option::Some(p) = rows.iter().position(|&r| r == #row) {
+                    rows.swap_remove(p);
+                }
+            },
+            IndexKind::Bitmap => quote! { rows.remove(#row); },
+        };
+        quote! {
+            if let ::std::option::Option::Some(rows) = index.get_mut(#value) {
+                #remove
+                if rows.is_empty() {
+                    index.remove(#value);
+                }
+            }
+        }
+    }
+}
+
+/// How the sharded stores assign keys to shards.
+struct Partitioning<'a> {
+    soa_ident: &'a Ident,
+    key_ty: &'a Type,
+    vis: &'a Visibility,
+    mode: Partition,
+    /// `Hasher + Default` type of the hash-based modes, fed through `std::hash::Hash`; `None`
+    /// routes with `soa_runtime::StableHasher` fed through `soa_runtime::StableHash`
+    hasher: Option<TokenStream>,
+}
+
+/// `#[soa_store(partition = "...")]`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Partition {
+    /// Key hash modulo the shard count
+    Hash,
+    /// Jump consistent hash of the key hash: resharding moves a minimal share of rows
+    Jump,
+    /// Sorted key boundaries
+    Range,
+}
+
+impl Partitioning<'_> {
+    fn is_range(&self) -> bool {
+        self.mode == Partition::Range
+    }
+
+    /// Shard index of the row with key `key` (a `&K` in scope) among `n` shards, or under the
+    /// range boundaries `bounds`
+    fn route(&self, n: TokenStream, bounds: TokenStream) -> TokenStream {
+        let hash = match &self.hasher {
+            Some(hasher) => quote! {
+                let mut h = <#hasher as ::std::default::Default>::default();
+                ::std::hash::Hash::hash(key, &mut h);
+            },
+            None => {
+                // Spanned at the key type so a missing `StableHash` impl points at it
+                let stable_hash = quote_spanned! {self.key_ty.span()=>
+                    soa_runtime::StableHash::stable_hash(key, &mut h);
+                };
+                quote! {
+                    let mut h = <soa_runtime::StableHasher as ::std::default::Default>::default();
+                    #stable_hash
+                }
+            }
+        };
+        match self.mode {
+            Partition::Hash => quote! {{
+                #hash
+                (::std::hash::Hasher::finish(&h) % #n as u64) as usize
+            }},
+            Partition::Jump => quote! {{
+                #hash
+                soa_runtime::jump_hash(::std::hash::Hasher::finish(&h), #n)
+            }},
+            Partition::Range => quote! { #bounds.partition_point(|bound| bound <= key) },
+        }
+    }
+
+    /// Constructors of a sharded store whose shards are built by `wrap(<SoA expr>)`; `extra`
+    /// initializes further fields from the `shards` vector
+    fn constructors(
+        &self,
+        shards_default: usize,
+        wrap: impl Fn(TokenStream) -> TokenStream,
+        extra: TokenStream,
+    ) -> TokenStream {
+        let Self {
+            soa_ident,
+            key_ty,
+            vis,
+            ..
+        } = self;
+        let shard = wrap(quote! { #soa_ident::with_capacity(cap_per) });
+        if self.is_range() {
+            quote! {
+                /// One shard per key range: shard 0 holds keys below `bounds[0]`, shard `i`
+                /// keys in `bounds[i - 1]..bounds[i]`, and the last shard every key from the
+                /// last boundary up. Panics unless `bounds` is strictly ascending.
+                #vis fn with_ranges(bounds: ::std::vec::Vec<#key_ty>, cap_per: usize) -> Self {
+                    assert!(
+                        bounds.windows(2).all(|w| w[0] < w[1]),
+                        "shard range boundaries must be strictly ascending"
+                    );
+                    let shards: ::std::vec::Vec<_> = (0..=bounds.len()).map(|_| #shard).collect();
+                    Self { #extra shards, bounds }
+                }
+
+                /// Lower key boundary of every shard but the first
+                #vis fn bounds(&self) -> &[#key_ty] {
+                    &self.bounds
+                }
+            }
+        } else {
+            quote! {
+                #vis const DEFAULT_SHARDS: usize = #shards_default;
+
+                #vis fn with_shards(n: usize, cap_per: usize) -> Self {
+                    assert!(n > 0, "a sharded store needs at least one shard");
+                    let shards: ::std::vec::Vec<_> = (0..n).map(|_| #shard).collect();
+                    Self { #extra shards }
+                }
+            }
+        }
+    }
+}
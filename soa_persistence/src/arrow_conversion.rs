@@ -1,6 +1,16 @@
 use crate::arrow_schema::ArrowSchemaGen;
 use crate::errors::{PersistenceError, Result};
-use arrow_array::{Array, RecordBatch};
+use arrow::buffer::{BooleanBuffer, Buffer, NullBuffer, OffsetBuffer, ScalarBuffer};
+use arrow_array::types::UInt8Type;
+use arrow_array::{
+    make_array, Array, ArrayRef, BooleanArray, DictionaryArray, Float32Array, Float64Array,
+    Int16Array, Int32Array, Int64Array, Int8Array, LargeBinaryArray, LargeStringArray, RecordBatch,
+    StringArray, UInt16Array, UInt32Array, UInt64Array, UInt8Array,
+};
+use arrow_schema::{DataType, Field};
+use soa_runtime::{BinaryColumn, Bitmap, NullPlaceholder, NullableColumn, SoaEnum, StringColumn};
+use std::ptr::NonNull;
+use std::sync::Arc;
 
 /// Trait for converting between SoA structures and Arrow RecordBatch
 pub trait ToArrow: ArrowSchemaGen {
@@ -8,6 +18,327 @@ pub trait ToArrow: ArrowSchemaGen {
     fn from_record_batch(batch: &RecordBatch) -> Result<Self>
     where
         Self: Sized;
+
+    /// Decode several batches, e.g. one per Parquet row group, into one value without
+    /// concatenating them into a single batch first
+    fn from_record_batches(batches: &[RecordBatch]) -> Result<Self>
+    where
+        Self: Sized;
+}
+
+/// Trait for field types that can be stored as a single Arrow column.
+///
+/// `#[derive(SoAArrow)]` maps every field of the domain struct through this trait,
+/// so a field whose type does not implement it is rejected at compile time.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be stored as an Arrow column",
+    label = "unsupported field type for `#[derive(SoAArrow)]`",
+    note = "implement `soa_persistence::ArrowField` for `{Self}` to persist it"
+)]
+pub trait ArrowField: Sized {
+    /// Arrow data type of the column holding this field
+    fn data_type() -> DataType;
+
+    /// Build an Arrow array from a column of values
+    fn to_array(values: &[Self]) -> ArrayRef;
+
+    /// Decode a column of values from an Arrow array
+    fn from_array(array: &dyn Array, column_name: &str) -> Result<Vec<Self>>;
+
+    /// Whether a stored column of `data_type` can be decoded by `from_array`
+    fn accepts(data_type: &DataType) -> bool {
+        data_type == &Self::data_type()
+    }
+}
+
+macro_rules! impl_primitive_arrow_field {
+    ($($ty:ty => $array:ty, $data_type:expr;)*) => {
+        $(
+            impl ArrowField for $ty {
+                fn data_type() -> DataType {
+                    $data_type
+                }
+
+                fn to_array(values: &[Self]) -> ArrayRef {
+                    Arc::new(<$array>::from(values.to_vec()))
+                }
+
+                fn from_array(array: &dyn Array, column_name: &str) -> Result<Vec<Self>> {
+                    Ok(downcast_array::<$array>(array, column_name)?.values().to_vec())
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive_arrow_field! {
+    u8 => UInt8Array, DataType::UInt8;
+    u16 => UInt16Array, DataType::UInt16;
+    u32 => UInt32Array, DataType::UInt32;
+    u64 => UInt64Array, DataType::UInt64;
+    i8 => Int8Array, DataType::Int8;
+    i16 => Int16Array, DataType::Int16;
+    i32 => Int32Array, DataType::Int32;
+    i64 => Int64Array, DataType::Int64;
+    f32 => Float32Array, DataType::Float32;
+    f64 => Float64Array, DataType::Float64;
+}
+
+impl ArrowField for bool {
+    fn data_type() -> DataType {
+        DataType::Boolean
+    }
+
+    fn to_array(values: &[Self]) -> ArrayRef {
+        Arc::new(BooleanArray::from(values.to_vec()))
+    }
+
+    fn from_array(array: &dyn Array, column_name: &str) -> Result<Vec<Self>> {
+        Ok(downcast_array::<BooleanArray>(array, column_name)?
+            .values()
+            .iter()
+            .collect())
+    }
+}
+
+impl ArrowField for String {
+    fn data_type() -> DataType {
+        DataType::LargeUtf8
+    }
+
+    fn to_array(values: &[Self]) -> ArrayRef {
+        Arc::new(LargeStringArray::from_iter_values(values))
+    }
+
+    fn from_array(array: &dyn Array, column_name: &str) -> Result<Vec<Self>> {
+        let strings = arrow::compute::cast(array, &DataType::LargeUtf8)?;
+        Ok(
+            downcast_array::<LargeStringArray>(strings.as_ref(), column_name)?
+                .iter()
+                .map(|value| value.unwrap_or_default().to_string())
+                .collect(),
+        )
+    }
+
+    fn accepts(data_type: &DataType) -> bool {
+        matches!(
+            data_type,
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View
+        )
+    }
+}
+
+impl ArrowField for Vec<u8> {
+    fn data_type() -> DataType {
+        DataType::LargeBinary
+    }
+
+    fn to_array(values: &[Self]) -> ArrayRef {
+        Arc::new(LargeBinaryArray::from_iter_values(values))
+    }
+
+    fn from_array(array: &dyn Array, column_name: &str) -> Result<Vec<Self>> {
+        let blobs = arrow::compute::cast(array, &DataType::LargeBinary)?;
+        Ok(
+            downcast_array::<LargeBinaryArray>(blobs.as_ref(), column_name)?
+                .iter()
+                .map(|value| value.unwrap_or_default().to_vec())
+                .collect(),
+        )
+    }
+
+    fn accepts(data_type: &DataType) -> bool {
+        matches!(
+            data_type,
+            DataType::Binary | DataType::LargeBinary | DataType::BinaryView
+        )
+    }
+}
+
+/// Helper function to decode the column described by `field` from a batch.
+///
+/// The column is looked up by name, then by each former name in `aliases`, and its data
+/// type is checked against `field` before decoding, so reordered or extra columns are
+/// tolerated. Returns `Ok(None)` when the batch has no such column (e.g. data written before
+/// it was added).
+pub fn read_optional_column<T: ArrowField>(
+    batch: &RecordBatch,
+    field: &Field,
+    aliases: &[&str],
+) -> Result<Option<Vec<T>>> {
+    let Some((name, array)) = find_column::<T>(batch, field, aliases)? else {
+        return Ok(None);
+    };
+
+    reject_nulls(array, name, field)?;
+    T::from_array(array, name).map(Some)
+}
+
+/// [`read_optional_column`] for `String` fields, copying the offsets and values buffers
+/// straight into a [`StringColumn`]. `Utf8` and `Utf8View` columns are accepted too.
+pub fn read_optional_string_column(
+    batch: &RecordBatch,
+    field: &Field,
+    aliases: &[&str],
+) -> Result<Option<StringColumn>> {
+    let Some((name, array)) = find_column::<String>(batch, field, aliases)? else {
+        return Ok(None);
+    };
+    reject_nulls(array, name, field)?;
+
+    let strings = arrow::compute::cast(array, &DataType::LargeUtf8)?;
+    let strings = downcast_array::<LargeStringArray>(strings.as_ref(), name)?;
+    let (offsets, values) = rebase_offsets(strings.value_offsets(), strings.value_data());
+    Ok(Some(StringColumn::from_parts(offsets, values)))
+}
+
+/// [`read_optional_column`] for `Vec<u8>` fields, copying the offsets and values buffers
+/// straight into a [`BinaryColumn`]. `Binary` and `BinaryView` columns are accepted too.
+pub fn read_optional_binary_column(
+    batch: &RecordBatch,
+    field: &Field,
+    aliases: &[&str],
+) -> Result<Option<BinaryColumn>> {
+    let Some((name, array)) = find_column::<Vec<u8>>(batch, field, aliases)? else {
+        return Ok(None);
+    };
+    reject_nulls(array, name, field)?;
+
+    let blobs = arrow::compute::cast(array, &DataType::LargeBinary)?;
+    let blobs = downcast_array::<LargeBinaryArray>(blobs.as_ref(), name)?;
+    let (offsets, values) = rebase_offsets(blobs.value_offsets(), blobs.value_data());
+    Ok(Some(BinaryColumn::from_parts(offsets, values)))
+}
+
+/// Helper function to build a `LargeUtf8` Arrow column sharing the buffers of a `String`
+/// field column; nothing is copied
+pub fn string_column_to_array(column: &StringColumn) -> Result<ArrayRef> {
+    let (offsets, values) = column.shared_buffers();
+    let offsets = OffsetBuffer::new(ScalarBuffer::from(shared_buffer(offsets)));
+    let values = shared_buffer(values);
+    Ok(Arc::new(LargeStringArray::try_new(offsets, values, None)?))
+}
+
+/// Helper function to build a `LargeBinary` Arrow column sharing the buffers of a `Vec<u8>`
+/// field column; nothing is copied
+pub fn binary_column_to_array(column: &BinaryColumn) -> Result<ArrayRef> {
+    let (offsets, values) = column.shared_buffers();
+    let offsets = OffsetBuffer::new(ScalarBuffer::from(shared_buffer(offsets)));
+    let values = shared_buffer(values);
+    Ok(Arc::new(LargeBinaryArray::try_new(offsets, values, None)?))
+}
+
+/// Wrap a shared `Vec` in an Arrow buffer that keeps it alive instead of copying it
+fn shared_buffer<T: Send + Sync + std::panic::RefUnwindSafe + 'static>(
+    data: Arc<Vec<T>>,
+) -> Buffer {
+    let ptr = NonNull::new(data.as_ptr() as *mut u8).expect("Vec pointers are never null");
+    let len = std::mem::size_of_val(data.as_slice());
+    // SAFETY: `ptr` points at `len` initialized bytes owned by `data`, which the buffer
+    // keeps alive. The varlen columns never write through an `Arc` that is shared (they
+    // go through `Arc::make_mut`), so the bytes stay unchanged while Arrow reads them.
+    unsafe { Buffer::from_custom_allocation(ptr, len, data) }
+}
+
+/// Nullable counterpart of [`read_optional_column`] for `Option<T>` fields
+pub fn read_optional_nullable_column<T: ArrowField + NullPlaceholder>(
+    batch: &RecordBatch,
+    field: &Field,
+    aliases: &[&str],
+) -> Result<Option<NullableColumn<T>>> {
+    let Some((name, array)) = find_column::<T>(batch, field, aliases)? else {
+        return Ok(None);
+    };
+
+    let mut values = T::from_array(array, name)?;
+    let validity = match array.logical_nulls() {
+        Some(nulls) => {
+            // Keep the documented placeholder at null rows regardless of what the writer stored
+            for i in nulls
+                .iter()
+                .enumerate()
+                .filter(|(_, valid)| !valid)
+                .map(|(i, _)| i)
+            {
+                values[i] = T::default();
+            }
+            nulls.iter().collect()
+        }
+        None => Bitmap::filled(values.len(), true),
+    };
+
+    Ok(Some(NullableColumn::from_parts(values, validity)))
+}
+
+/// Helper function to build a nullable Arrow column from an `Option<T>` field column
+pub fn nullable_to_array<T: ArrowField>(column: &NullableColumn<T>) -> Result<ArrayRef> {
+    let nulls = NullBuffer::new(bitmap_to_boolean_buffer(column.validity()));
+    let data = T::to_array(column.values())
+        .into_data()
+        .into_builder()
+        .nulls(Some(nulls))
+        .build()?;
+    Ok(make_array(data))
+}
+
+/// Convert a packed bitmap to Arrow's LSB-first boolean buffer
+pub fn bitmap_to_boolean_buffer(bitmap: &Bitmap) -> BooleanBuffer {
+    let bytes: Vec<u8> = bitmap
+        .words()
+        .iter()
+        .flat_map(|w| w.to_le_bytes())
+        .collect();
+    BooleanBuffer::new(Buffer::from_vec(bytes), 0, bitmap.len())
+}
+
+fn reject_nulls(array: &dyn Array, name: &str, field: &Field) -> Result<()> {
+    if array.logical_null_count() > 0 {
+        return Err(PersistenceError::TypeConversion {
+            message: format!(
+                "Column '{}' contains nulls but field '{}' is not an Option",
+                name,
+                field.name()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Offsets of a sliced array start past zero; shift them to index a copy of just its bytes
+fn rebase_offsets(offsets: &[i64], data: &[u8]) -> (Vec<i64>, Vec<u8>) {
+    let start = offsets.first().copied().unwrap_or(0);
+    let end = offsets.last().copied().unwrap_or(0);
+    let values = data[start as usize..end as usize].to_vec();
+    let offsets = if offsets.is_empty() {
+        vec![0]
+    } else {
+        offsets.iter().map(|&o| o - start).collect()
+    };
+    (offsets, values)
+}
+
+/// Look up the column for `field` (or one of its former names) and check its data type
+fn find_column<'a, T: ArrowField>(
+    batch: &'a RecordBatch,
+    field: &'a Field,
+    aliases: &[&'a str],
+) -> Result<Option<(&'a str, &'a dyn Array)>> {
+    let found = std::iter::once(field.name().as_str())
+        .chain(aliases.iter().copied())
+        .find_map(|name| batch.column_by_name(name).map(|array| (name, array)));
+
+    let Some((name, array)) = found else {
+        return Ok(None);
+    };
+
+    if !T::accepts(array.data_type()) {
+        return Err(PersistenceError::SchemaMismatch {
+            expected: format!("column '{}' of type {}", field.name(), field.data_type()),
+            found: format!("column '{}' of type {}", name, array.data_type()),
+        });
+    }
+
+    Ok(Some((name, array.as_ref())))
 }
 
 /// Helper function to safely downcast Arrow array to specific type
@@ -18,26 +349,126 @@ pub fn downcast_array<'a, T: Array + 'static>(
     array
         .as_any()
         .downcast_ref::<T>()
-        .ok_or_else(|| PersistenceError::ColumnNotFound {
-            column_name: column_name.to_string(),
+        .ok_or_else(|| PersistenceError::SchemaMismatch {
+            expected: format!("column '{}' as {}", column_name, std::any::type_name::<T>()),
+            found: format!("column '{}' of type {}", column_name, array.data_type()),
         })
 }
 
-/// Helper function to convert enum to u8 for Arrow storage
-pub fn enum_to_u8<T>(value: T) -> u8
-where
-    T: Copy + Into<u8>,
-{
-    value.into()
+/// Arrow data type used for `#[derive(SoAEnum)]` columns: `u8` keys into the variant names
+pub fn enum_dictionary_type() -> DataType {
+    DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8))
+}
+
+/// Stored column types `enums_from_dictionary_array` can decode: `u8` discriminants,
+/// or variant names as plain or dictionary-encoded strings
+pub fn enum_accepts_data_type(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::UInt8 | DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => true,
+        DataType::Dictionary(key, value) => {
+            key.is_dictionary_key_type()
+                && matches!(value.as_ref(), DataType::Utf8 | DataType::LargeUtf8)
+        }
+        _ => false,
+    }
+}
+
+/// Helper function to build a dictionary-encoded Arrow column from enum values.
+///
+/// The dictionary holds every variant name in declaration order, so keys equal the
+/// `u8` discriminants and the column is readable without knowing the Rust enum.
+pub fn enums_to_dictionary_array<T: SoaEnum>(values: &[T]) -> ArrayRef {
+    let keys = UInt8Array::from_iter_values(values.iter().map(|v| v.to_u8()));
+    let names = Arc::new(StringArray::from(T::VARIANTS.to_vec()));
+    Arc::new(DictionaryArray::<UInt8Type>::new(keys, names))
+}
+
+/// Helper function to decode enum values from a dictionary-encoded Arrow column.
+///
+/// Variants are matched by name, so dictionaries re-encoded by other writers decode
+/// correctly. Plain `UInt8` discriminant columns and `Utf8` name columns are accepted too.
+/// Null slots decode to the first variant; nullability is tracked by the caller.
+pub fn enums_from_dictionary_array<T: SoaEnum>(
+    array: &dyn Array,
+    column_name: &str,
+) -> Result<Vec<T>> {
+    let unknown_variant = |name: &str| PersistenceError::TypeConversion {
+        message: format!(
+            "Unknown {} variant '{}' in column '{}'",
+            T::NAME,
+            name,
+            column_name
+        ),
+    };
+
+    match array.data_type() {
+        DataType::UInt8 => downcast_array::<UInt8Array>(array, column_name)?
+            .values()
+            .iter()
+            .map(|&v| {
+                T::from_u8(v).ok_or_else(|| PersistenceError::TypeConversion {
+                    message: format!(
+                        "Invalid {} discriminant {} in column '{}'",
+                        T::NAME,
+                        v,
+                        column_name
+                    ),
+                })
+            })
+            .collect(),
+        DataType::Dictionary(key, value)
+            if key.as_ref() == &DataType::UInt8 && value.as_ref() == &DataType::Utf8 =>
+        {
+            let dict = downcast_array::<DictionaryArray<UInt8Type>>(array, column_name)?;
+            let names = downcast_array::<StringArray>(dict.values().as_ref(), column_name)?;
+
+            // Resolve each dictionary entry to a discriminant once, then map keys through it
+            let lookup = (0..names.len())
+                .map(|i| {
+                    if names.is_null(i) {
+                        return Ok(None);
+                    }
+                    let name = names.value(i);
+                    T::from_variant_name(name)
+                        .map(|v| Some(v.to_u8()))
+                        .ok_or_else(|| unknown_variant(name))
+                })
+                .collect::<Result<Vec<Option<u8>>>>()?;
+
+            dict.keys()
+                .iter()
+                .map(|key| match key {
+                    Some(k) => lookup
+                        .get(k as usize)
+                        .copied()
+                        .flatten()
+                        .and_then(T::from_u8)
+                        .ok_or_else(|| PersistenceError::TypeConversion {
+                            message: format!(
+                                "Dictionary key {} has no {} variant in column '{}'",
+                                k,
+                                T::NAME,
+                                column_name
+                            ),
+                        }),
+                    None => Ok(first_variant::<T>()),
+                })
+                .collect()
+        }
+        _ => {
+            let strings = arrow::compute::cast(array, &DataType::Utf8)?;
+            let names = downcast_array::<StringArray>(strings.as_ref(), column_name)?;
+            names
+                .iter()
+                .map(|name| match name {
+                    Some(name) => T::from_variant_name(name).ok_or_else(|| unknown_variant(name)),
+                    None => Ok(first_variant::<T>()),
+                })
+                .collect()
+        }
+    }
 }
 
-/// Helper function to convert u8 back to enum from Arrow storage
-pub fn u8_to_enum<T>(value: u8) -> Result<T>
-where
-    T: TryFrom<u8>,
-    T::Error: std::fmt::Display,
-{
-    T::try_from(value).map_err(|e| PersistenceError::TypeConversion {
-        message: format!("Failed to convert u8 to enum: {}", e),
-    })
+fn first_variant<T: SoaEnum>() -> T {
+    T::from_u8(0).expect("SoaEnum has at least one variant")
 }
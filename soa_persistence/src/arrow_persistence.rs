@@ -1,6 +1,8 @@
 use crate::arrow_conversion::ToArrow;
 use crate::errors::Result;
 use crate::persistence::{SoABatchPersistence, SoAPersistence};
+use crate::query::Expr;
+use crate::stream::{self, SoAStream};
 use arrow_array::RecordBatch;
 use arrow_schema::Schema;
 use async_trait::async_trait;
@@ -121,6 +123,33 @@ where
         }
     }
 
+    async fn load_columns(&self, columns: &[&str]) -> Result<Option<RecordBatch>> {
+        let batches = self.get_batches()?;
+        if batches.is_empty() {
+            return Ok(None);
+        }
+
+        let indices = columns
+            .iter()
+            .map(|&name| {
+                self.schema.index_of(name).map_err(|_| {
+                    crate::errors::PersistenceError::ColumnNotFound {
+                        column_name: name.to_string(),
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let projected = batches
+            .iter()
+            .map(|batch| batch.project(&indices))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let schema = self.schema.project(&indices)?;
+        Ok(Some(arrow::compute::concat_batches(
+            &Arc::new(schema),
+            projected.iter(),
+        )?))
+    }
+
     async fn append(&mut self, data: &T) -> Result<()> {
         let batch = data.to_record_batch()?;
 
@@ -134,16 +163,39 @@ where
         Ok(())
     }
 
-    async fn query<F>(&self, predicate: F) -> Result<Option<T>>
+    async fn query(&self, filter: &Expr) -> Result<Option<T>> {
+        let batches = self.get_batches()?;
+        if batches.is_empty() {
+            return Ok(None);
+        }
+
+        // Filter each batch with the compute kernels before concatenating the survivors
+        let matching = batches
+            .iter()
+            .map(|batch| filter.filter(batch))
+            .collect::<Result<Vec<_>>>()?;
+        let merged = arrow::compute::concat_batches(&self.schema, matching.iter())?;
+        Ok(Some(T::from_record_batch(&merged)?))
+    }
+
+    fn stream(&self, batch_size: usize) -> SoAStream<T>
     where
-        F: Fn(&T) -> bool + Send + Sync,
+        T: Send + 'static,
     {
-        let data = self.load().await?;
-
-        match data {
-            Some(d) if predicate(&d) => Ok(Some(d)),
-            _ => Ok(None),
-        }
+        assert!(
+            batch_size > 0,
+            "stream needs a batch size of at least one row"
+        );
+        let batches = self.get_batches();
+
+        SoAStream::spawn(move |sender| match batches {
+            Ok(batches) => {
+                stream::send_chunks(&sender, row_chunks(&batches, batch_size).map(Ok));
+            }
+            Err(e) => {
+                let _ = sender.blocking_send(Err(e));
+            }
+        })
     }
 
     async fn count(&self) -> Result<usize> {
@@ -188,22 +240,15 @@ where
     }
 
     async fn load_batches(&self, batch_size: usize) -> Result<Vec<T>> {
+        assert!(
+            batch_size > 0,
+            "load_batches needs a batch size of at least one row"
+        );
         let stored_batches = self.get_batches()?;
-        let mut result = Vec::new();
 
-        for batch in stored_batches {
-            // If batch is larger than requested size, we could split it
-            // For now, return the whole batch as-is
-            let data = T::from_record_batch(&batch)?;
-            result.push(data);
-
-            // Could implement batch splitting logic here if needed
-            if result.len() >= batch_size {
-                break;
-            }
-        }
-
-        Ok(result)
+        row_chunks(&stored_batches, batch_size)
+            .map(|chunk| T::from_record_batch(&chunk))
+            .collect()
     }
 
     async fn append_batches(&mut self, batches_data: &[T]) -> Result<()> {
@@ -241,6 +286,18 @@ impl<T> Clone for ArrowPersistence<T> {
     }
 }
 
+/// Zero-copy slices of at most `batch_size` rows; chunks never span two stored batches
+fn row_chunks(
+    batches: &[RecordBatch],
+    batch_size: usize,
+) -> impl Iterator<Item = RecordBatch> + '_ {
+    batches.iter().flat_map(move |batch| {
+        (0..batch.num_rows())
+            .step_by(batch_size)
+            .map(move |offset| batch.slice(offset, batch_size.min(batch.num_rows() - offset)))
+    })
+}
+
 /// Memory usage statistics for Arrow persistence
 #[derive(Debug, Clone)]
 pub struct MemoryStats {
@@ -6,28 +6,15 @@ pub trait ArrowSchemaGen {
     fn arrow_schema() -> Arc<Schema>;
     fn arrow_field_names() -> Vec<&'static str>;
     fn arrow_field_types() -> Vec<DataType>;
-}
-
-/// Helper macro to implement ArrowSchemaGen for enum types
-#[macro_export]
-macro_rules! impl_enum_arrow_type {
-    ($enum_type:ty) => {
-        impl ArrowSchemaGen for $enum_type {
-            fn arrow_schema() -> Arc<Schema> {
-                Arc::new(Schema::new(vec![Field::new(
-                    "value",
-                    DataType::UInt8,
-                    false,
-                )]))
-            }
 
-            fn arrow_field_names() -> Vec<&'static str> {
-                vec!["value"]
-            }
+    /// Version of the persisted schema, bumped with `#[soa(version = N)]` when fields change
+    fn schema_version() -> u32 {
+        1
+    }
 
-            fn arrow_field_types() -> Vec<DataType> {
-                vec![DataType::UInt8]
-            }
-        }
-    };
+    /// Former names of renamed columns, as `(column, former name)` pairs from
+    /// `#[soa(renamed_from = "...")]`
+    fn arrow_field_aliases() -> Vec<(&'static str, &'static str)> {
+        Vec::new()
+    }
 }
@@ -22,6 +22,9 @@ pub enum PersistenceError {
 
     #[error("Task join error: {0}")]
     TaskJoin(String),
+
+    #[error("Duplicate key: {key}")]
+    DuplicateKey { key: String },
 }
 
 pub type Result<T> = std::result::Result<T, PersistenceError>;
@@ -2,15 +2,22 @@ pub mod arrow_conversion;
 pub mod arrow_persistence;
 pub mod arrow_schema;
 pub mod errors;
+pub mod manifest;
 pub mod parquet_persistence;
 pub mod persistence;
+pub mod query;
+pub mod stream;
 
-pub use arrow_conversion::ToArrow;
+pub use arrow_conversion::{ArrowField, ToArrow};
 pub use arrow_persistence::{ArrowPersistence, MemoryStats};
 pub use arrow_schema::ArrowSchemaGen;
 pub use errors::{PersistenceError, Result};
-pub use parquet_persistence::ParquetPersistence;
+pub use manifest::{Manifest, Part};
+pub use parquet_persistence::{ParquetPersistence, ScanPlan};
 pub use persistence::{SoABatchPersistence, SoAPersistence};
+pub use query::{col, CmpOp, Column, Expr};
+pub use stream::SoAStream;
 
 // Re-export commonly used types
-pub use arrow_array::RecordBatch;
+pub use ::arrow_schema::{DataType, Field, Schema};
+pub use arrow_array::{Array, ArrayRef, RecordBatch};
@@ -0,0 +1,300 @@
+//! On-disk layout of a [`ParquetPersistence`](crate::ParquetPersistence) directory.
+//!
+//! Rows live in immutable `part-NNNNN.parquet` files, listed in row order by a small text
+//! manifest:
+//!
+//! ```text
+//! soa-manifest 1
+//! next 3
+//! part-00000.parquet 100000
+//! part-00002.parquet 250
+//! ```
+//!
+//! A part is synced to disk before the manifest lists it, and the manifest is replaced by
+//! renaming a temporary file over it, so a crash at any point leaves either the old or the
+//! new list of parts. Files the manifest does not list are ignored.
+//!
+//! Writers hold an exclusive lock on `_manifest.lock` from reading the manifest until its
+//! replacement is in place, so concurrent writers (in this process or another) cannot drop
+//! each other's parts. The operating system releases the lock if its holder dies.
+
+use crate::errors::{PersistenceError, Result};
+use parquet::file::metadata::ParquetMetaDataReader;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// Name of the manifest inside the store directory
+pub const MANIFEST_FILE: &str = "_manifest";
+
+/// Single file written before part files existed; read as the only part of a directory
+/// without a manifest, and replaced by parts on the next write
+pub const LEGACY_FILE: &str = "data.parquet";
+
+/// Lock file serializing manifest updates of the store directory
+pub const LOCK_FILE: &str = "_manifest.lock";
+
+const HEADER: &str = "soa-manifest 1";
+
+/// Live part files of a store directory, in row order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// Sequence number of the next part file; never reused, so a crashed write cannot
+    /// collide with a later one
+    pub next_part: u64,
+    pub parts: Vec<Part>,
+}
+
+/// One immutable Parquet file of a store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    /// File name, relative to the store directory
+    pub file: String,
+    pub rows: usize,
+}
+
+impl Manifest {
+    /// Read the manifest of `dir`; a directory without one is empty or holds the legacy file
+    pub fn read(dir: &Path) -> Result<Manifest> {
+        let path = dir.join(MANIFEST_FILE);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::read_legacy(dir),
+            Err(e) => return Err(PersistenceError::Io(e)),
+        };
+
+        let invalid = |line: &str| {
+            PersistenceError::Serialization(format!(
+                "Invalid manifest {}: unexpected '{}'",
+                path.display(),
+                line
+            ))
+        };
+        let mut lines = text.lines();
+        match lines.next() {
+            Some(HEADER) => {}
+            other => return Err(invalid(other.unwrap_or(""))),
+        }
+
+        let mut manifest = Manifest::default();
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("next", n)) => manifest.next_part = n.parse().map_err(|_| invalid(line))?,
+                Some((file, rows)) => manifest.parts.push(Part {
+                    file: file.to_string(),
+                    rows: rows.parse().map_err(|_| invalid(line))?,
+                }),
+                None => return Err(invalid(line)),
+            }
+        }
+        Ok(manifest)
+    }
+
+    fn read_legacy(dir: &Path) -> Result<Manifest> {
+        let file = match File::open(dir.join(LEGACY_FILE)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Manifest::default()),
+            Err(e) => return Err(PersistenceError::Io(e)),
+        };
+        let metadata = ParquetMetaDataReader::new()
+            .parse_and_finish(&file)
+            .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+
+        Ok(Manifest {
+            next_part: 0,
+            parts: vec![Part {
+                file: LEGACY_FILE.to_string(),
+                rows: metadata.file_metadata().num_rows() as usize,
+            }],
+        })
+    }
+
+    /// Atomically replace the manifest of `dir` with this one
+    pub(crate) fn write(&self, dir: &Path) -> Result<()> {
+        let mut text = format!("{}\nnext {}\n", HEADER, self.next_part);
+        for part in &self.parts {
+            text.push_str(&format!("{} {}\n", part.file, part.rows));
+        }
+
+        let tmp = dir.join(format!("{}.tmp", MANIFEST_FILE));
+        let mut file = File::create(&tmp).map_err(PersistenceError::Io)?;
+        file.write_all(text.as_bytes())
+            .and_then(|_| file.sync_all())
+            .map_err(PersistenceError::Io)?;
+        std::fs::rename(&tmp, dir.join(MANIFEST_FILE)).map_err(PersistenceError::Io)?;
+        sync_dir(dir)
+    }
+
+    /// Total rows across all parts
+    pub fn rows(&self) -> usize {
+        self.parts.iter().map(|part| part.rows).sum()
+    }
+
+    /// Reserve the file name of a new part
+    pub(crate) fn next_part_file(&mut self) -> String {
+        let file = format!("part-{:05}.parquet", self.next_part);
+        self.next_part += 1;
+        file
+    }
+}
+
+/// Exclusive right to update the manifest of a store directory, released on drop
+pub(crate) struct ManifestLock {
+    _file: File,
+}
+
+impl ManifestLock {
+    /// Block until no other writer holds the lock of `dir`, creating the directory if needed
+    pub(crate) fn acquire(dir: &Path) -> Result<ManifestLock> {
+        std::fs::create_dir_all(dir).map_err(PersistenceError::Io)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK_FILE))
+            .map_err(PersistenceError::Io)?;
+        file.lock().map_err(PersistenceError::Io)?;
+        Ok(ManifestLock { _file: file })
+    }
+}
+
+/// Make renames and new files in `dir` durable. Only Unix can open a directory for syncing.
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(PersistenceError::Io)?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Int64Array, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    fn write_manifest_text(dir: &Path, text: &str) {
+        std::fs::write(dir.join(MANIFEST_FILE), text).unwrap();
+    }
+
+    fn assert_invalid(dir: &Path, text: &str) {
+        write_manifest_text(dir, text);
+        assert!(
+            matches!(Manifest::read(dir), Err(PersistenceError::Serialization(_))),
+            "accepted {:?}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        write_manifest_text(
+            dir.path(),
+            "soa-manifest 1\nnext 3\npart-00000.parquet 100000\npart-00002.parquet 250\n",
+        );
+        let manifest = Manifest::read(dir.path()).unwrap();
+        assert_eq!(manifest.next_part, 3);
+        assert_eq!(
+            manifest.parts,
+            vec![
+                Part {
+                    file: "part-00000.parquet".to_string(),
+                    rows: 100_000,
+                },
+                Part {
+                    file: "part-00002.parquet".to_string(),
+                    rows: 250,
+                },
+            ]
+        );
+        assert_eq!(manifest.rows(), 100_250);
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manifest = Manifest::default();
+        for rows in [10, 0, 7] {
+            let file = manifest.next_part_file();
+            manifest.parts.push(Part { file, rows });
+        }
+        manifest.parts.remove(1);
+        manifest.write(dir.path()).unwrap();
+
+        let read = Manifest::read(dir.path()).unwrap();
+        assert_eq!(read, manifest);
+        assert_eq!(read.next_part, 3);
+        assert_eq!(read.parts[1].file, "part-00002.parquet");
+        assert!(!dir.path().join(format!("{}.tmp", MANIFEST_FILE)).exists());
+
+        // A manifest without parts still records the next part number
+        Manifest {
+            next_part: 3,
+            parts: Vec::new(),
+        }
+        .write(dir.path())
+        .unwrap();
+        assert_eq!(Manifest::read(dir.path()).unwrap().next_part, 3);
+    }
+
+    #[test]
+    fn test_corrupt_manifest_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_invalid(dir.path(), "");
+        assert_invalid(dir.path(), "soa-manifest 2\nnext 0\n");
+        assert_invalid(dir.path(), "PAR1\0\0garbage");
+        assert_invalid(dir.path(), "soa-manifest 1\nnext x\n");
+        assert_invalid(
+            dir.path(),
+            "soa-manifest 1\nnext 1\npart-00000.parquet -5\n",
+        );
+        assert_invalid(dir.path(), "soa-manifest 1\nnext 1\npart-00000.parquet\n");
+        // A truncated write leaves a line without its row count
+        assert_invalid(
+            dir.path(),
+            "soa-manifest 1\nnext 2\npart-00000.parquet 10\npart-0",
+        );
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Manifest::read(dir.path()).unwrap(), Manifest::default());
+        let missing = dir.path().join("missing");
+        assert_eq!(Manifest::read(&missing).unwrap(), Manifest::default());
+    }
+
+    #[test]
+    fn test_legacy_file_is_the_only_part() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from_iter_values(0..42))],
+        )
+        .unwrap();
+        let file = File::create(dir.path().join(LEGACY_FILE)).unwrap();
+        let mut writer = ArrowWriter::try_new(file, schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+
+        let manifest = Manifest::read(dir.path()).unwrap();
+        assert_eq!(manifest.next_part, 0);
+        assert_eq!(
+            manifest.parts,
+            vec![Part {
+                file: LEGACY_FILE.to_string(),
+                rows: 42,
+            }]
+        );
+
+        // A corrupt legacy file is an error, not an empty store
+        std::fs::write(dir.path().join(LEGACY_FILE), b"not parquet").unwrap();
+        assert!(Manifest::read(dir.path()).is_err());
+    }
+}
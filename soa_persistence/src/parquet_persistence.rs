@@ -1,19 +1,49 @@
 use crate::arrow_conversion::ToArrow;
 use crate::errors::{PersistenceError, Result};
+use crate::manifest::{Manifest, ManifestLock, Part};
 use crate::persistence::SoAPersistence;
+use crate::query::{self, CmpOp, Expr};
+use crate::stream::{self, SoAStream};
+use arrow::compute::kernels::boolean;
+use arrow::error::ArrowError;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Float64Type};
+use arrow_array::{Array, ArrayRef, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Schema};
 use async_trait::async_trait;
-use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
-use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::statistics::StatisticsConverter;
+use parquet::arrow::arrow_reader::{
+    ArrowPredicateFn, ArrowReaderOptions, ParquetRecordBatchReaderBuilder, RowFilter, RowSelection,
+    RowSelector,
+};
+use parquet::arrow::{ArrowWriter, ProjectionMask};
 use parquet::basic::Compression;
-use parquet::file::metadata::ParquetMetaDataReader;
+use parquet::file::metadata::{FileMetaData, KeyValue, ParquetMetaData};
 use parquet::file::properties::WriterProperties;
+use parquet::schema::types::SchemaDescriptor;
 use std::fs::File;
+use std::future::Future;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Parquet key-value metadata entry holding `ArrowSchemaGen::schema_version()`
+pub const SCHEMA_VERSION_KEY: &str = "soa.schema_version";
+
+/// Default part size [`ParquetPersistence::compact`] merges up to, Parquet's default row
+/// group size
+pub const DEFAULT_TARGET_PART_ROWS: usize = 1024 * 1024;
+
+/// Parquet storage in a directory of immutable part files listed by a manifest (see
+/// [`manifest`](crate::manifest)). Appends write a new part; [`compact`](Self::compact)
+/// merges small parts in the background. Several instances, in one process or several, may
+/// write the same directory: manifest updates take the directory's lock file.
 pub struct ParquetPersistence<T> {
     base_path: PathBuf,
     compression: Compression,
+    page_size: Option<usize>,
+    row_group_size: Option<usize>,
+    target_part_rows: usize,
     writer_properties: Arc<WriterProperties>,
     _phantom: std::marker::PhantomData<T>,
 }
@@ -30,6 +60,9 @@ impl<T> ParquetPersistence<T> {
         Self {
             base_path: base_path.as_ref().to_path_buf(),
             compression,
+            page_size: None,
+            row_group_size: None,
+            target_part_rows: DEFAULT_TARGET_PART_ROWS,
             writer_properties,
             _phantom: std::marker::PhantomData,
         }
@@ -37,27 +70,352 @@ impl<T> ParquetPersistence<T> {
 
     pub fn with_compression(mut self, compression: Compression) -> Self {
         self.compression = compression;
-        self.writer_properties = Arc::new(
-            WriterProperties::builder()
-                .set_compression(compression)
-                .build(),
-        );
+        self.rebuild_writer_properties();
         self
     }
 
     pub fn with_page_size(mut self, page_size: usize) -> Self {
-        self.writer_properties = Arc::new(
-            WriterProperties::builder()
-                .set_compression(self.compression)
-                .set_data_page_size_limit(page_size)
-                .build(),
-        );
+        self.page_size = Some(page_size);
+        self.rebuild_writer_properties();
         self
     }
 
-    fn file_path(&self) -> PathBuf {
-        self.base_path.join("data.parquet")
+    /// Maximum rows per row group. Smaller row groups let filtered queries skip more data
+    /// using row group statistics, at the cost of more metadata.
+    pub fn with_row_group_size(mut self, rows: usize) -> Self {
+        self.row_group_size = Some(rows);
+        self.rebuild_writer_properties();
+        self
+    }
+
+    /// Size up to which [`compact`](Self::compact) merges adjacent parts
+    pub fn with_target_part_rows(mut self, rows: usize) -> Self {
+        self.target_part_rows = rows;
+        self
+    }
+
+    fn rebuild_writer_properties(&mut self) {
+        let mut builder = WriterProperties::builder().set_compression(self.compression);
+        if let Some(page_size) = self.page_size {
+            builder = builder.set_data_page_size_limit(page_size);
+        }
+        if let Some(rows) = self.row_group_size {
+            builder = builder.set_max_row_group_size(rows);
+        }
+        self.writer_properties = Arc::new(builder.build());
+    }
+}
+
+impl<T> ParquetPersistence<T>
+where
+    T: ToArrow + Send + Sync + 'static,
+{
+    /// Row groups and rows `query(filter)` decodes after statistics pruning; `None` if
+    /// nothing is stored. Row groups are numbered across parts in manifest order.
+    pub async fn scan_plan(&self, filter: &Expr) -> Result<Option<ScanPlan>> {
+        let dir = self.base_path.clone();
+        let filter = filter.clone();
+
+        run_blocking(move || {
+            let manifest = Manifest::read(&dir)?;
+            if manifest.parts.is_empty() {
+                return Ok(None);
+            }
+
+            let mut total = ScanPlan {
+                row_groups: Vec::new(),
+                total_row_groups: 0,
+                selected_rows: 0,
+                total_rows: 0,
+            };
+            for part in &manifest.parts {
+                let (_, _, plan, _) = open_filtered::<T>(&dir.join(&part.file), &filter)?;
+                let first = total.total_row_groups;
+                total
+                    .row_groups
+                    .extend(plan.row_groups.iter().map(|rg| first + rg));
+                total.total_row_groups += plan.total_row_groups;
+                total.selected_rows += plan.selected_rows;
+                total.total_rows += plan.total_rows;
+            }
+            Ok(Some(total))
+        })
+        .await
+    }
+
+    /// Live parts, in row order
+    pub async fn manifest(&self) -> Result<Manifest> {
+        let dir = self.base_path.clone();
+        run_blocking(move || Manifest::read(&dir)).await
+    }
+
+    /// Merge runs of adjacent parts smaller than the target part size into single parts,
+    /// keeping row order; resolves to how many parts were merged away.
+    ///
+    /// The merge starts on the blocking pool right away and finishes even if the returned
+    /// future is dropped, so it must be called within a Tokio runtime. The lock is only held
+    /// to reserve part names and to swap the manifest, so appends and reads go on while the
+    /// parts are rewritten. Runs that a concurrent `save` or `clear` removed are left out.
+    pub fn compact(&self) -> impl Future<Output = Result<usize>> + Send + 'static {
+        let dir = self.base_path.clone();
+        let props = (*self.writer_properties).clone();
+        let target = self.target_part_rows;
+
+        let task = tokio::task::spawn_blocking(move || compact_parts::<T>(&dir, target, props));
+        async move {
+            task.await
+                .map_err(|e| PersistenceError::TaskJoin(e.to_string()))?
+        }
+    }
+}
+
+/// Body of [`ParquetPersistence::compact`]
+fn compact_parts<T: ToArrow>(dir: &Path, target: usize, props: WriterProperties) -> Result<usize> {
+    let runs = reserve_runs(dir, target)?;
+    if runs.is_empty() {
+        return Ok(0);
+    }
+    merge_runs::<T>(dir, runs, props)
+}
+
+/// Pick the runs of parts to merge and reserve a part name for each
+fn reserve_runs(dir: &Path, target: usize) -> Result<Vec<(Vec<Part>, String)>> {
+    let _lock = ManifestLock::acquire(dir)?;
+    let mut manifest = Manifest::read(dir)?;
+    let runs: Vec<(Vec<Part>, String)> = compaction_runs(&manifest.parts, target)
+        .into_iter()
+        .filter(|run| run.len() > 1)
+        .map(|run| (manifest.parts[run].to_vec(), manifest.next_part_file()))
+        .collect();
+    if !runs.is_empty() {
+        manifest.write(dir)?;
+    }
+    Ok(runs)
+}
+
+/// Merge each reserved run into its part and swap the merged parts into the manifest; runs
+/// a concurrent `save` or `clear` removed in the meantime are skipped
+fn merge_runs<T: ToArrow>(
+    dir: &Path,
+    runs: Vec<(Vec<Part>, String)>,
+    props: WriterProperties,
+) -> Result<usize> {
+    let mut merged = Vec::with_capacity(runs.len());
+    for (run, file) in runs {
+        match merge_run::<T>(dir, &run, file.clone(), props.clone()) {
+            Ok(Some(part)) => merged.push((run, part)),
+            Ok(None) => {}
+            Err(e) => {
+                // No manifest lists the merged parts yet, nor the half-written one
+                let written: Vec<Part> = merged
+                    .into_iter()
+                    .map(|(_, part)| part)
+                    .chain([Part { file, rows: 0 }])
+                    .collect();
+                let _ = remove_parts(dir, &written);
+                return Err(e);
+            }
+        }
+    }
+
+    // Swap in each merged part where its run is still listed
+    let _lock = ManifestLock::acquire(dir)?;
+    let mut manifest = Manifest::read(dir)?;
+    let (mut replaced, mut unused) = (Vec::new(), Vec::new());
+    let mut merged_away = 0;
+    for (run, part) in merged {
+        let position = manifest
+            .parts
+            .windows(run.len())
+            .position(|window| window == run.as_slice());
+        match position {
+            Some(i) => {
+                manifest.parts.splice(i..i + run.len(), [part]);
+                merged_away += run.len() - 1;
+                replaced.extend(run);
+            }
+            None => unused.push(part),
+        }
+    }
+    if !replaced.is_empty() {
+        manifest.write(dir)?;
+    }
+    remove_parts(dir, &replaced)?;
+    remove_parts(dir, &unused)?;
+    Ok(merged_away)
+}
+
+/// Stream the parts of `run` into one part named `file`; parts of older schema versions are
+/// decoded into `T` and written with its columns. `None` if a part of the run is gone.
+fn merge_run<T: ToArrow>(
+    dir: &Path,
+    run: &[Part],
+    file: String,
+    props: WriterProperties,
+) -> Result<Option<Part>> {
+    // Every part is opened before writing, so a run removed meanwhile writes nothing
+    let mut readers = Vec::new();
+    for part in run {
+        let builder = match open_part::<T>(dir, part, ArrowReaderOptions::new()) {
+            Ok(builder) => builder,
+            Err(PersistenceError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+        readers.push(
+            builder
+                .build()
+                .map_err(|e| PersistenceError::ArrowError(e.into()))?,
+        );
+    }
+    let batches = readers.into_iter().flatten().map(|batch| {
+        batch
+            .map_err(PersistenceError::ArrowError)
+            .and_then(to_schema_of::<T>)
+    });
+    write_part::<T>(dir, file, batches, props).map(Some)
+}
+
+/// Index ranges of adjacent parts that together stay within `target` rows. A part at or
+/// above the target is a run of its own.
+fn compaction_runs(parts: &[Part], target: usize) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let (mut start, mut rows) = (0, 0);
+    for (i, part) in parts.iter().enumerate() {
+        if i > start && rows + part.rows > target {
+            runs.push(start..i);
+            (start, rows) = (i, 0);
+        }
+        rows += part.rows;
+    }
+    if start < parts.len() {
+        runs.push(start..parts.len());
     }
+    runs
+}
+
+/// Row groups and rows a filtered Parquet scan decodes after ruling out data with column
+/// statistics. Rows outside the selected pages are never read from disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanPlan {
+    /// Row groups that may hold matching rows, in file order
+    pub row_groups: Vec<usize>,
+    pub total_row_groups: usize,
+    /// Rows in the pages that may hold matching rows
+    pub selected_rows: usize,
+    pub total_rows: usize,
+}
+
+/// Run blocking file work on Tokio's blocking pool
+async fn run_blocking<R: Send + 'static>(
+    f: impl FnOnce() -> Result<R> + Send + 'static,
+) -> Result<R> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| PersistenceError::TaskJoin(e.to_string()))?
+}
+
+/// Write `batches` to the part file `file` of `dir` (a name reserved with
+/// [`Manifest::next_part_file`]) with `T`'s schema and sync it to disk. The manifest is not
+/// updated; an empty part is still written.
+fn write_part<T: ToArrow>(
+    dir: &Path,
+    file: String,
+    batches: impl IntoIterator<Item = Result<RecordBatch>>,
+    props: WriterProperties,
+) -> Result<Part> {
+    std::fs::create_dir_all(dir).map_err(PersistenceError::Io)?;
+
+    let out = File::create(dir.join(&file)).map_err(PersistenceError::Io)?;
+    let mut writer = ArrowWriter::try_new(out, T::arrow_schema(), Some(props))
+        .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+    writer.append_key_value_metadata(KeyValue::new(
+        SCHEMA_VERSION_KEY.to_string(),
+        T::schema_version().to_string(),
+    ));
+    let mut rows = 0;
+    for batch in batches {
+        let batch = batch?;
+        rows += batch.num_rows();
+        writer
+            .write(&batch)
+            .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+    }
+    writer
+        .finish()
+        .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+    writer
+        .inner_mut()
+        .sync_all()
+        .map_err(PersistenceError::Io)?;
+
+    Ok(Part { file, rows })
+}
+
+/// Delete part files no longer listed by the manifest
+fn remove_parts(dir: &Path, parts: &[Part]) -> Result<()> {
+    for part in parts {
+        match std::fs::remove_file(dir.join(&part.file)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(PersistenceError::Io(e))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Open a part for reading, rejecting parts written by a newer schema version
+fn open_part<T: ToArrow>(
+    dir: &Path,
+    part: &Part,
+    options: ArrowReaderOptions,
+) -> Result<ParquetRecordBatchReaderBuilder<File>> {
+    let file = File::open(dir.join(&part.file)).map_err(PersistenceError::Io)?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new_with_options(file, options)
+        .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+    check_schema_version::<T>(builder.metadata().file_metadata())?;
+    Ok(builder)
+}
+
+/// `batch` with exactly `T`'s columns: batches of parts written by another schema version
+/// are decoded into `T` and encoded again
+fn to_schema_of<T: ToArrow>(batch: RecordBatch) -> Result<RecordBatch> {
+    if batch.schema().fields() == T::arrow_schema().fields() {
+        Ok(batch)
+    } else {
+        T::from_record_batch(&batch)?.to_record_batch()
+    }
+}
+
+/// Schema version stamped into a Parquet file; files written before versioning count as 1
+pub fn file_schema_version(metadata: &FileMetaData) -> Result<u32> {
+    let stamped = metadata
+        .key_value_metadata()
+        .and_then(|kvs| kvs.iter().find(|kv| kv.key == SCHEMA_VERSION_KEY))
+        .and_then(|kv| kv.value.as_deref());
+
+    match stamped {
+        None => Ok(1),
+        Some(value) => value.parse().map_err(|_| PersistenceError::SchemaMismatch {
+            expected: format!("numeric {}", SCHEMA_VERSION_KEY),
+            found: value.to_string(),
+        }),
+    }
+}
+
+/// Reject files written by a newer schema than `T` knows how to decode
+fn check_schema_version<T: ToArrow>(metadata: &FileMetaData) -> Result<()> {
+    let found = file_schema_version(metadata)?;
+    let supported = T::schema_version();
+    if found > supported {
+        return Err(PersistenceError::SchemaMismatch {
+            expected: format!("schema version <= {}", supported),
+            found: format!("schema version {}", found),
+        });
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -67,152 +425,865 @@ where
 {
     async fn save(&mut self, data: &T) -> Result<()> {
         let batch = data.to_record_batch()?;
-        let file_path = self.file_path();
+        let dir = self.base_path.clone();
         let props = (*self.writer_properties).clone();
 
-        tokio::task::spawn_blocking(move || {
-            let file = File::create(&file_path).map_err(PersistenceError::Io)?;
-            let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(props))
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            writer
-                .write(&batch)
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            writer
-                .close()
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            Ok::<(), PersistenceError>(())
+        run_blocking(move || {
+            let _lock = ManifestLock::acquire(&dir)?;
+            let mut manifest = Manifest::read(&dir)?;
+            let mut parts = Vec::new();
+            if batch.num_rows() > 0 {
+                let file = manifest.next_part_file();
+                parts.push(write_part::<T>(&dir, file, [Ok(batch)], props)?);
+            }
+            if parts.is_empty() && manifest.parts.is_empty() {
+                return Ok(());
+            }
+
+            // The old parts are deleted only once the new manifest no longer lists them
+            let replaced = std::mem::replace(&mut manifest.parts, parts);
+            manifest.write(&dir)?;
+            remove_parts(&dir, &replaced)
         })
         .await
-        .map_err(|e| PersistenceError::TaskJoin(e.to_string()))??;
-
-        Ok(())
     }
 
     async fn load(&self) -> Result<Option<T>> {
-        let file_path = self.file_path();
-
-        if !tokio::fs::try_exists(&file_path)
-            .await
-            .map_err(PersistenceError::Io)?
-        {
-            return Ok(None);
-        }
-
-        tokio::task::spawn_blocking(move || -> Result<Option<T>> {
-            let file = File::open(&file_path).map_err(PersistenceError::Io)?;
-            let builder = ParquetRecordBatchReaderBuilder::try_new(file)
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            let reader = builder
-                .build()
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+        let dir = self.base_path.clone();
 
+        run_blocking(move || {
+            let manifest = Manifest::read(&dir)?;
             let mut batches = Vec::new();
-            for maybe_batch in reader {
-                let batch = maybe_batch.map_err(PersistenceError::ArrowError)?;
-                batches.push(batch);
+            for part in &manifest.parts {
+                let builder = open_part::<T>(&dir, part, ArrowReaderOptions::new())?;
+                batches.extend(read_batches(builder)?);
             }
-
             if batches.is_empty() {
                 return Ok(None);
             }
 
-            let combined_batch = if batches.len() == 1 {
-                batches.into_iter().next().unwrap()
-            } else {
-                let schema = batches[0].schema();
-                arrow::compute::concat_batches(&schema, &batches)
-                    .map_err(PersistenceError::ArrowError)?
-            };
+            // Each batch is decoded on its own, so parts written by another schema version
+            // need no conversion and no combined copy of the data is built
+            Ok(Some(T::from_record_batches(&batches)?))
+        })
+        .await
+    }
+
+    async fn load_columns(&self, columns: &[&str]) -> Result<Option<RecordBatch>> {
+        let dir = self.base_path.clone();
+        let columns: Vec<String> = columns.iter().map(|name| name.to_string()).collect();
 
-            let data = T::from_record_batch(&combined_batch)?;
-            Ok(Some(data))
+        run_blocking(move || {
+            let schema = T::arrow_schema();
+            let indices = column_indices(&schema, &columns)?;
+            let projected = Arc::new(schema.project(&indices)?);
+
+            let manifest = Manifest::read(&dir)?;
+            let mut batches = Vec::new();
+            for part in &manifest.parts {
+                let builder = open_part::<T>(&dir, part, ArrowReaderOptions::new())?;
+                let stored: Option<Vec<String>> = columns
+                    .iter()
+                    .map(|column| stored_name::<T>(builder.schema(), column))
+                    .collect();
+                let Some(stored) = stored else {
+                    // The part predates a requested column: decode it into `T`, which fills
+                    // the column in
+                    for batch in read_batches(builder)? {
+                        batches.push(to_schema_of::<T>(batch)?.project(&indices)?);
+                    }
+                    continue;
+                };
+
+                // Column chunks outside the mask are never read from disk
+                let roots = column_indices(builder.schema(), &stored)?;
+                let projection = ProjectionMask::roots(builder.parquet_schema(), roots);
+                for batch in read_batches(builder.with_projection(projection))? {
+                    // The reader returns projected columns in file order and under their
+                    // stored names; restore the requested order and `T`'s names
+                    let order = column_indices(&batch.schema(), &stored)?;
+                    let columns = batch.project(&order)?.columns().to_vec();
+                    batches.push(RecordBatch::try_new(projected.clone(), columns)?);
+                }
+            }
+
+            match batches.first() {
+                Some(first) => Ok(Some(arrow::compute::concat_batches(
+                    &first.schema(),
+                    &batches,
+                )?)),
+                None => Ok(None),
+            }
         })
         .await
-        .map_err(|e| PersistenceError::TaskJoin(e.to_string()))?
     }
 
     async fn append(&mut self, data: &T) -> Result<()> {
-        let existing = self.load().await?;
-        let new_batch = data.to_record_batch()?;
-
-        let combined_batch = if let Some(existing_data) = existing {
-            let existing_batch = existing_data.to_record_batch()?;
-            let schema = existing_batch.schema();
-            arrow::compute::concat_batches(&schema, &[existing_batch, new_batch])
-                .map_err(PersistenceError::ArrowError)?
-        } else {
-            new_batch
-        };
-
-        let file_path = self.file_path();
+        let batch = data.to_record_batch()?;
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let dir = self.base_path.clone();
         let props = (*self.writer_properties).clone();
 
-        tokio::task::spawn_blocking(move || {
-            let file = File::create(&file_path).map_err(PersistenceError::Io)?;
-            let mut writer = ArrowWriter::try_new(file, combined_batch.schema(), Some(props))
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            writer
-                .write(&combined_batch)
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            writer
-                .close()
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            Ok::<(), PersistenceError>(())
+        // Only the new rows are written; existing parts are left untouched
+        run_blocking(move || {
+            let _lock = ManifestLock::acquire(&dir)?;
+            let mut manifest = Manifest::read(&dir)?;
+            let file = manifest.next_part_file();
+            let part = write_part::<T>(&dir, file, [Ok(batch)], props)?;
+            manifest.parts.push(part);
+            manifest.write(&dir)
         })
         .await
-        .map_err(|e| PersistenceError::TaskJoin(e.to_string()))??;
+    }
+
+    async fn query(&self, filter: &Expr) -> Result<Option<T>> {
+        let dir = self.base_path.clone();
+        let filter = filter.clone();
 
-        Ok(())
+        run_blocking(move || {
+            let manifest = Manifest::read(&dir)?;
+            if manifest.parts.is_empty() {
+                return Ok(None);
+            }
+            let mut batches = Vec::new();
+            for part in &manifest.parts {
+                batches.extend(query_part::<T>(&dir.join(&part.file), &filter)?);
+            }
+
+            Ok(Some(T::from_record_batches(&batches)?))
+        })
+        .await
     }
 
-    async fn query<F>(&self, predicate: F) -> Result<Option<T>>
+    fn stream(&self, batch_size: usize) -> SoAStream<T>
     where
-        F: Fn(&T) -> bool + Send + Sync,
+        T: Send + 'static,
     {
-        if let Some(data) = self.load().await? {
-            if predicate(&data) {
-                Ok(Some(data))
-            } else {
-                Ok(None)
+        assert!(
+            batch_size > 0,
+            "stream needs a batch size of at least one row"
+        );
+        let dir = self.base_path.clone();
+
+        SoAStream::spawn(move |sender| {
+            let manifest = match Manifest::read(&dir) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    let _ = sender.blocking_send(Err(e));
+                    return;
+                }
+            };
+
+            for part in &manifest.parts {
+                let reader =
+                    open_part::<T>(&dir, part, ArrowReaderOptions::new()).and_then(|builder| {
+                        builder
+                            .with_batch_size(batch_size)
+                            .build()
+                            .map_err(|e| PersistenceError::ArrowError(e.into()))
+                    });
+                let sent = match reader {
+                    Ok(reader) => stream::send_chunks(
+                        &sender,
+                        reader.map(|batch| batch.map_err(PersistenceError::ArrowError)),
+                    ),
+                    Err(e) => {
+                        let _ = sender.blocking_send(Err(e));
+                        false
+                    }
+                };
+                if !sent {
+                    return;
+                }
             }
-        } else {
-            Ok(None)
-        }
+        })
     }
 
     async fn count(&self) -> Result<usize> {
-        let file_path = self.file_path();
+        // Part sizes are recorded in the manifest; no part file is opened
+        let dir = self.base_path.clone();
+        run_blocking(move || Ok(Manifest::read(&dir)?.rows())).await
+    }
 
-        if !tokio::fs::try_exists(&file_path)
-            .await
-            .map_err(PersistenceError::Io)?
-        {
-            return Ok(0);
-        }
+    async fn clear(&mut self) -> Result<()> {
+        let dir = self.base_path.clone();
 
-        tokio::task::spawn_blocking(move || -> Result<usize> {
-            let file = File::open(&file_path).map_err(PersistenceError::Io)?;
-            let reader = ParquetMetaDataReader::new()
-                .parse_and_finish(&file)
-                .map_err(|e| PersistenceError::ArrowError(e.into()))?;
-            Ok(reader.file_metadata().num_rows() as usize)
+        run_blocking(move || {
+            let _lock = ManifestLock::acquire(&dir)?;
+            let mut manifest = Manifest::read(&dir)?;
+            if manifest.parts.is_empty() {
+                return Ok(());
+            }
+
+            let replaced = std::mem::take(&mut manifest.parts);
+            manifest.write(&dir)?;
+            remove_parts(&dir, &replaced)
         })
         .await
-        .map_err(|e| PersistenceError::TaskJoin(e.to_string()))?
     }
+}
 
-    async fn clear(&mut self) -> Result<()> {
-        let file_path = self.file_path();
+/// Batches of matching rows of one part, read with statistics pruning and a row filter
+fn query_part<T: ToArrow>(path: &Path, filter: &Expr) -> Result<Vec<RecordBatch>> {
+    let (builder, stored, plan, selection) = open_filtered::<T>(path, filter)?;
+    let Some(filter) = stored else {
+        // The part predates a filtered column: decode it into `T`, which fills the column in,
+        // and filter the decoded rows
+        return read_batches(builder)?
+            .into_iter()
+            .map(|batch| filter.filter(&to_schema_of::<T>(batch)?))
+            .collect();
+    };
+    let schema = builder.schema().clone();
+
+    // Decode the filter columns of the selected pages first; the remaining columns are only
+    // decoded for rows the filter matches
+    let roots = filter
+        .columns()
+        .into_iter()
+        .filter_map(|name| schema.index_of(name).ok());
+    let projection = ProjectionMask::roots(builder.parquet_schema(), roots);
+    let filter = filter.clone();
+    let predicate = ArrowPredicateFn::new(projection, move |batch: RecordBatch| {
+        filter
+            .evaluate(&batch)
+            .map_err(|e| ArrowError::ExternalError(Box::new(e)))
+    });
+
+    let reader = builder
+        .with_row_groups(plan.row_groups)
+        .with_row_selection(selection)
+        .with_row_filter(RowFilter::new(vec![Box::new(predicate)]))
+        .build()
+        .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+
+    reader
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(PersistenceError::ArrowError)
+}
+
+/// Every batch of `builder`, as the reader decodes them row group by row group
+fn read_batches(builder: ParquetRecordBatchReaderBuilder<File>) -> Result<Vec<RecordBatch>> {
+    builder
+        .build()
+        .map_err(|e| PersistenceError::ArrowError(e.into()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(PersistenceError::ArrowError)
+}
+
+/// Position of each of `columns` in `schema`
+fn column_indices(schema: &Schema, columns: &[String]) -> Result<Vec<usize>> {
+    columns
+        .iter()
+        .map(|name| {
+            schema
+                .index_of(name)
+                .map_err(|_| PersistenceError::ColumnNotFound {
+                    column_name: name.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Open `file_path` with its page index and plan a scan for the rows `filter` may match.
+/// Also returns `filter` over the part's stored column names, or `None` with a plan of every
+/// row if the part predates one of the filtered columns.
+fn open_filtered<T: ToArrow>(
+    file_path: &Path,
+    filter: &Expr,
+) -> Result<(
+    ParquetRecordBatchReaderBuilder<File>,
+    Option<Expr>,
+    ScanPlan,
+    RowSelection,
+)> {
+    let file = File::open(file_path).map_err(PersistenceError::Io)?;
+    let options = ArrowReaderOptions::new().with_page_index(true);
+    let builder = ParquetRecordBatchReaderBuilder::try_new_with_options(file, options)
+        .map_err(|e| PersistenceError::ArrowError(e.into()))?;
+    check_schema_version::<T>(builder.metadata().file_metadata())?;
+
+    let Some(stored) = stored_filter::<T>(builder.schema(), filter)? else {
+        let metadata = builder.metadata();
+        let total_rows = metadata.file_metadata().num_rows() as usize;
+        let plan = ScanPlan {
+            row_groups: (0..metadata.num_row_groups()).collect(),
+            total_row_groups: metadata.num_row_groups(),
+            selected_rows: total_rows,
+            total_rows,
+        };
+        let selection = RowSelection::from(vec![RowSelector::select(total_rows)]);
+        return Ok((builder, None, plan, selection));
+    };
+    let pruner = Pruner::new(
+        builder.metadata(),
+        builder.schema(),
+        builder.parquet_schema(),
+    );
+    let (plan, selection) = pruner.plan(&stored);
+    Ok((builder, Some(stored), plan, selection))
+}
+
+/// `filter` over the names a part with `schema` stores `T`'s columns under, or `None` if the
+/// part predates one of the filtered columns
+fn stored_filter<T: ToArrow>(schema: &Schema, filter: &Expr) -> Result<Option<Expr>> {
+    filter.check_columns(&T::arrow_schema())?;
+    let columns = filter.columns();
+    if columns
+        .iter()
+        .any(|column| stored_name::<T>(schema, column).is_none())
+    {
+        return Ok(None);
+    }
+    Ok(Some(filter.map_columns(&|column| {
+        stored_name::<T>(schema, column).unwrap_or_else(|| column.to_string())
+    })))
+}
+
+/// Name a part with `schema` stores `T`'s column `column` under: the column itself or one of
+/// its `#[soa(renamed_from = "...")]` names; `None` if the part predates the column
+fn stored_name<T: ToArrow>(schema: &Schema, column: &str) -> Option<String> {
+    if schema.column_with_name(column).is_some() {
+        return Some(column.to_string());
+    }
+    T::arrow_field_aliases()
+        .into_iter()
+        .filter(|(name, _)| *name == column)
+        .map(|(_, former)| former)
+        .find(|former| schema.column_with_name(former).is_some())
+        .map(str::to_string)
+}
+
+/// Sorted, disjoint ranges of row numbers within the file
+type RowRanges = Vec<Range<usize>>;
+
+fn intersect_ranges(a: &[Range<usize>], b: &[Range<usize>]) -> RowRanges {
+    let mut ranges = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].start.max(b[j].start);
+        let end = a[i].end.min(b[j].end);
+        if start < end {
+            ranges.push(start..end);
+        }
+        if a[i].end < b[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    ranges
+}
+
+fn union_ranges(a: &[Range<usize>], b: &[Range<usize>]) -> RowRanges {
+    let mut all: Vec<_> = a.iter().chain(b).cloned().collect();
+    all.sort_by_key(|r| r.start);
+    let mut ranges: RowRanges = Vec::with_capacity(all.len());
+    for r in all {
+        match ranges.last_mut() {
+            Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+            _ => ranges.push(r),
+        }
+    }
+    ranges
+}
+
+/// Push `range`, merging it into the previous one when they touch
+fn push_range(ranges: &mut RowRanges, range: Range<usize>) {
+    match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    }
+}
+
+/// Rules out rows of a Parquet file that cannot match a filter, first per row group from the
+/// column chunk statistics, then per page from the page index
+struct Pruner<'a> {
+    metadata: &'a ParquetMetaData,
+    arrow_schema: &'a Schema,
+    parquet_schema: &'a SchemaDescriptor,
+    /// First row of each row group, followed by the file's row count
+    offsets: Vec<usize>,
+    /// `0..num_row_groups`, borrowed one at a time for the per-page statistics
+    row_group_indices: Vec<usize>,
+}
+
+impl<'a> Pruner<'a> {
+    fn new(
+        metadata: &'a ParquetMetaData,
+        arrow_schema: &'a Schema,
+        parquet_schema: &'a SchemaDescriptor,
+    ) -> Self {
+        let mut offsets = vec![0];
+        for rg in metadata.row_groups() {
+            offsets.push(offsets[offsets.len() - 1] + rg.num_rows() as usize);
+        }
+        Self {
+            metadata,
+            arrow_schema,
+            parquet_schema,
+            offsets,
+            row_group_indices: (0..metadata.num_row_groups()).collect(),
+        }
+    }
+
+    fn plan(&self, filter: &Expr) -> (ScanPlan, RowSelection) {
+        let ranges = self.rows(filter);
+
+        let mut row_groups = Vec::new();
+        let mut selectors = Vec::new();
+        let mut next = 0;
+        for rg in 0..self.metadata.num_row_groups() {
+            let (start, end) = (self.offsets[rg], self.offsets[rg + 1]);
+            let row_group = start..end;
+            let selected = intersect_ranges(&ranges[next..], std::slice::from_ref(&row_group));
+            // Ranges ending inside this row group are not needed for the following ones
+            while next < ranges.len() && ranges[next].end <= end {
+                next += 1;
+            }
+            if selected.is_empty() {
+                continue;
+            }
+
+            // Selections are relative to the rows of the row groups being read
+            row_groups.push(rg);
+            let mut cursor = start;
+            for range in selected {
+                if range.start > cursor {
+                    selectors.push(RowSelector::skip(range.start - cursor));
+                }
+                selectors.push(RowSelector::select(range.len()));
+                cursor = range.end;
+            }
+            if cursor < end {
+                selectors.push(RowSelector::skip(end - cursor));
+            }
+        }
+
+        let plan = ScanPlan {
+            row_groups,
+            total_row_groups: self.metadata.num_row_groups(),
+            selected_rows: ranges.iter().map(|r| r.len()).sum(),
+            total_rows: self.offsets[self.offsets.len() - 1],
+        };
+        (plan, RowSelection::from(selectors))
+    }
+
+    /// Rows that may match `filter`
+    fn rows(&self, filter: &Expr) -> RowRanges {
+        match filter {
+            Expr::And(l, r) => intersect_ranges(&self.rows(l), &self.rows(r)),
+            Expr::Or(l, r) => union_ranges(&self.rows(l), &self.rows(r)),
+            Expr::Compare { column, .. } | Expr::IsNull(column) | Expr::IsNotNull(column) => {
+                self.leaf_rows(filter, column)
+            }
+        }
+    }
+
+    fn leaf_rows(&self, leaf: &Expr, column: &str) -> RowRanges {
+        let total_rows = self.offsets[self.offsets.len() - 1];
+        let Ok(converter) =
+            StatisticsConverter::try_new(column, self.arrow_schema, self.parquet_schema)
+        else {
+            return std::iter::once(0..total_rows).collect();
+        };
+
+        let row_groups = self.metadata.row_groups();
+        let stats = (|| {
+            Some(Statistics {
+                mins: converter.row_group_mins(row_groups).ok()?,
+                maxes: converter.row_group_maxes(row_groups).ok()?,
+                null_counts: converter.row_group_null_counts(row_groups).ok()?,
+                row_counts: converter.row_group_row_counts(row_groups).ok()?,
+            })
+        })();
+        let keep = match stats {
+            Some(stats) => stats.may_match(leaf),
+            None => vec![true; row_groups.len()],
+        };
+
+        let mut ranges = Vec::new();
+        for (rg, _) in keep.iter().enumerate().filter(|(_, keep)| **keep) {
+            match self.page_rows(&converter, leaf, rg) {
+                Some(pages) => pages
+                    .into_iter()
+                    .for_each(|page| push_range(&mut ranges, page)),
+                None => push_range(&mut ranges, self.offsets[rg]..self.offsets[rg + 1]),
+            }
+        }
+        ranges
+    }
+
+    /// Rows of the pages of row group `rg` that may match `leaf`, or `None` without a page
+    /// index for the column
+    fn page_rows(
+        &self,
+        converter: &StatisticsConverter<'_>,
+        leaf: &Expr,
+        rg: usize,
+    ) -> Option<RowRanges> {
+        let column_index = self.metadata.column_index()?;
+        let offset_index = self.metadata.offset_index()?;
+        let leaf_column = converter.parquet_column_index()?;
+        column_index.get(rg)?.get(leaf_column)?;
+        offset_index.get(rg)?.get(leaf_column)?;
+
+        let rg_index = &self.row_group_indices[rg..=rg];
+        let row_counts = converter
+            .data_page_row_counts(offset_index, self.metadata.row_groups(), rg_index)
+            .ok()??;
+        let stats = Statistics {
+            mins: converter
+                .data_page_mins(column_index, offset_index, rg_index)
+                .ok()?,
+            maxes: converter
+                .data_page_maxes(column_index, offset_index, rg_index)
+                .ok()?,
+            null_counts: converter
+                .data_page_null_counts(column_index, offset_index, rg_index)
+                .ok()?,
+            row_counts: Some(row_counts.clone()),
+        };
+
+        let mut ranges = Vec::new();
+        let mut start = self.offsets[rg];
+        for (rows, keep) in row_counts.values().iter().zip(stats.may_match(leaf)) {
+            let end = start + *rows as usize;
+            if keep {
+                push_range(&mut ranges, start..end);
+            }
+            start = end;
+        }
+        Some(ranges)
+    }
+}
+
+/// Statistics of one column over a list of containers (row groups or pages); unknown
+/// entries are null
+struct Statistics {
+    mins: ArrayRef,
+    maxes: ArrayRef,
+    null_counts: UInt64Array,
+    row_counts: Option<UInt64Array>,
+}
+
+impl Statistics {
+    /// Per container, `false` if no row in it can satisfy the comparison `leaf`
+    fn may_match(&self, leaf: &Expr) -> Vec<bool> {
+        let unknown = vec![true; self.null_counts.len()];
+
+        match leaf {
+            Expr::Compare { op, value, .. } => {
+                // Statistics order enum columns by variant name, not by discriminant
+                if op.is_ordering() && query::enum_variants(value).is_some() {
+                    return unknown;
+                }
+                let Some(value) = prunable_literal(*op, value, self.mins.data_type()) else {
+                    return unknown;
+                };
+                let (mins, maxes) = (self.mins.as_ref(), self.maxes.as_ref());
+                let result = match op {
+                    CmpOp::Eq => query::compare(mins, CmpOp::LtEq, &value).and_then(|low| {
+                        let high = query::compare(maxes, CmpOp::GtEq, &value)?;
+                        Ok(boolean::and_kleene(&low, &high)?)
+                    }),
+                    // Only a container holding nothing but `value` can be ruled out
+                    CmpOp::NotEq => query::compare(mins, CmpOp::Eq, &value).and_then(|low| {
+                        let high = query::compare(maxes, CmpOp::Eq, &value)?;
+                        Ok(boolean::not(&boolean::and_kleene(&low, &high)?)?)
+                    }),
+                    CmpOp::Lt | CmpOp::LtEq => query::compare(mins, *op, &value),
+                    CmpOp::Gt | CmpOp::GtEq => query::compare(maxes, *op, &value),
+                };
+                match result {
+                    Ok(result) => (0..result.len())
+                        .map(|i| result.is_null(i) || result.value(i))
+                        .collect(),
+                    Err(_) => unknown,
+                }
+            }
+            Expr::IsNull(_) => self.null_counts.iter().map(|n| n != Some(0)).collect(),
+            Expr::IsNotNull(_) => match &self.row_counts {
+                Some(row_counts) => self
+                    .null_counts
+                    .iter()
+                    .zip(row_counts.iter())
+                    .map(|(nulls, rows)| match (nulls, rows) {
+                        (Some(nulls), Some(rows)) => nulls < rows,
+                        _ => true,
+                    })
+                    .collect(),
+                None => unknown,
+            },
+            Expr::And(..) | Expr::Or(..) => unknown,
+        }
+    }
+}
+
+/// The literal of a comparison cast to the statistics type, or `None` if min/max cannot rule
+/// out any container for it.
+///
+/// Parquet leaves NaN out of float statistics, while Arrow sorts NaN above every value: a
+/// page whose max is below `v` may still hold a NaN matching `> v` or `!= v`.
+fn prunable_literal(op: CmpOp, value: &ArrayRef, data_type: &DataType) -> Option<ArrayRef> {
+    let value = query::literal_as(value, data_type, "").ok()?;
+    let nan = match data_type {
+        DataType::Float16 => return None,
+        DataType::Float32 => value.as_primitive::<Float32Type>().value(0).is_nan(),
+        DataType::Float64 => value.as_primitive::<Float64Type>().value(0).is_nan(),
+        _ => return Some(value),
+    };
+    if nan || matches!(op, CmpOp::Gt | CmpOp::GtEq | CmpOp::NotEq) {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+// Single-range arrays are row range lists here, not ranges meant as `Vec`s
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+    use crate::arrow_schema::ArrowSchemaGen;
+    use crate::query::col;
+    use arrow_array::{Float64Array, Int64Array};
+    use arrow_schema::Field;
+
+    fn part(rows: usize) -> Part {
+        Part {
+            file: String::new(),
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_intersect_ranges() {
+        assert!(intersect_ranges(&[], &[0..10]).is_empty());
+        assert!(intersect_ranges(&[0..10], &[]).is_empty());
+        // Ranges that only touch share no row
+        assert!(intersect_ranges(&[0..5], &[5..10]).is_empty());
+        assert_eq!(
+            intersect_ranges(&[0..10, 20..30], &[5..25, 28..40]),
+            vec![5..10, 20..25, 28..30]
+        );
+        assert_eq!(
+            intersect_ranges(&[0..100], &[10..20, 30..40]),
+            vec![10..20, 30..40]
+        );
+    }
+
+    #[test]
+    fn test_union_ranges() {
+        assert!(union_ranges(&[], &[]).is_empty());
+        assert_eq!(union_ranges(&[], &[3..4]), vec![3..4]);
+        assert_eq!(
+            union_ranges(&[0..10], &[5..15, 20..25]),
+            vec![0..15, 20..25]
+        );
+        // Touching and nested ranges merge into one
+        assert_eq!(union_ranges(&[0..5, 8..9], &[5..8, 1..2]), vec![0..9]);
+        assert_eq!(union_ranges(&[30..40], &[0..10]), vec![0..10, 30..40]);
+    }
+
+    #[test]
+    fn test_push_range() {
+        let mut ranges = Vec::new();
+        push_range(&mut ranges, 0..5);
+        push_range(&mut ranges, 5..10);
+        push_range(&mut ranges, 12..15);
+        assert_eq!(ranges, vec![0..10, 12..15]);
+    }
+
+    #[test]
+    fn test_compaction_runs() {
+        assert!(compaction_runs(&[], 100).is_empty());
+        let parts: Vec<_> = [40, 60, 10, 200, 50, 50, 1].map(part).into();
+        assert_eq!(
+            compaction_runs(&parts, 100),
+            vec![0..2, 2..3, 3..4, 4..6, 6..7]
+        );
+        // A part at or above the target stays on its own
+        assert_eq!(compaction_runs(&[part(100)], 100), vec![0..1]);
+    }
+
+    /// 100 rows in four row groups of 25 rows and pages of 5 rows. `id` is the row number;
+    /// `score` is null for the first 20 rows and the row number after that.
+    fn write_test_file(path: &Path) {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("score", DataType::Float64, true),
+        ]));
+        let ids = Int64Array::from_iter_values(0..100);
+        let scores: Float64Array = (0..100).map(|i| (i >= 20).then_some(i as f64)).collect();
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(ids), Arc::new(scores)]).unwrap();
+
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(25)
+            .set_data_page_row_count_limit(5)
+            .set_write_batch_size(5)
+            .build();
+        let mut writer =
+            ArrowWriter::try_new(File::create(path).unwrap(), schema, Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn plan(path: &Path, filter: &Expr) -> ScanPlan {
+        let options = ArrowReaderOptions::new().with_page_index(true);
+        let builder = ParquetRecordBatchReaderBuilder::try_new_with_options(
+            File::open(path).unwrap(),
+            options,
+        )
+        .unwrap();
+        let pruner = Pruner::new(
+            builder.metadata(),
+            builder.schema(),
+            builder.parquet_schema(),
+        );
+        let (plan, selection) = pruner.plan(filter);
+        assert_eq!(selection.row_count(), plan.selected_rows);
+        assert_eq!(
+            selection.row_count() + selection.skipped_row_count(),
+            plan.row_groups.len() * 25
+        );
+        assert_eq!((plan.total_row_groups, plan.total_rows), (4, 100));
+        plan
+    }
+
+    #[test]
+    fn test_pruner_scan_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.parquet");
+        write_test_file(&path);
+        let selected = |filter: Expr| {
+            let plan = plan(&path, &filter);
+            (plan.row_groups, plan.selected_rows)
+        };
+
+        // Row group statistics rule out whole row groups, the page index single pages
+        assert_eq!(selected(col("id").gt_eq(90i64)), (vec![3], 10));
+        assert_eq!(selected(col("id").eq(42i64)), (vec![1], 5));
+        assert_eq!(selected(col("id").lt(0i64)), (vec![], 0));
+
+        // Overlapping conditions intersect; disjoint alternatives are united
+        assert_eq!(
+            selected(col("id").gt_eq(10i64).and(col("id").lt(30i64))),
+            (vec![0, 1], 20)
+        );
+        assert_eq!(
+            selected(col("id").gt_eq(10i64).and(col("id").lt(5i64))),
+            (vec![], 0)
+        );
+        assert_eq!(
+            selected(col("id").lt(10i64).or(col("id").gt_eq(95i64))),
+            (vec![0, 3], 15)
+        );
+        assert_eq!(
+            selected(col("id").lt(10i64).or(col("id").lt(20i64))),
+            (vec![0], 20)
+        );
+
+        // Null counts prune `IS NULL` / `IS NOT NULL`
+        assert_eq!(selected(col("score").is_null()), (vec![0], 20));
+        assert_eq!(selected(col("score").is_not_null()), (vec![0, 1, 2, 3], 80));
+        // Pages holding only nulls have no min/max, so a comparison keeps them
+        assert_eq!(selected(col("score").lt(30.0)), (vec![0, 1], 30));
+        // Float `>` cannot prune: statistics leave NaN out
+        assert_eq!(selected(col("score").gt(90.0)).1, 100);
+
+        // A column without statistics keeps every row
+        assert_eq!(selected(col("missing").eq(1i64)), (vec![0, 1, 2, 3], 100));
+    }
+
+    /// One `id` column, standing in for a generated SoA type
+    struct Ids(RecordBatch);
+
+    impl Ids {
+        fn new(ids: std::ops::Range<i64>) -> Self {
+            let ids = Arc::new(Int64Array::from_iter_values(ids));
+            Ids(RecordBatch::try_new(Self::arrow_schema(), vec![ids]).unwrap())
+        }
+    }
+
+    impl ArrowSchemaGen for Ids {
+        fn arrow_schema() -> Arc<Schema> {
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]))
+        }
+
+        fn arrow_field_names() -> Vec<&'static str> {
+            vec!["id"]
+        }
+
+        fn arrow_field_types() -> Vec<DataType> {
+            vec![DataType::Int64]
+        }
+    }
+
+    impl ToArrow for Ids {
+        fn to_record_batch(&self) -> Result<RecordBatch> {
+            Ok(self.0.clone())
+        }
+
+        fn from_record_batch(batch: &RecordBatch) -> Result<Self> {
+            Ok(Ids(batch.clone()))
+        }
+
+        fn from_record_batches(batches: &[RecordBatch]) -> Result<Self> {
+            Ok(Ids(arrow::compute::concat_batches(
+                &Self::arrow_schema(),
+                batches,
+            )?))
+        }
+    }
+
+    fn parquet_files(dir: &Path) -> Vec<String> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".parquet"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_compact_after_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ParquetPersistence::<Ids>::new(dir.path());
+        for start in [0, 10, 20] {
+            store.append(&Ids::new(start..start + 10)).await.unwrap();
+        }
+
+        // The run's parts are deleted between reserving its name and merging it
+        let runs = reserve_runs(dir.path(), 100).unwrap();
+        assert_eq!(runs.len(), 1);
+        store.clear().await.unwrap();
+        let merged = merge_runs::<Ids>(dir.path(), runs, WriterProperties::default()).unwrap();
+        assert_eq!(merged, 0);
+        assert!(parquet_files(dir.path()).is_empty());
+        assert!(store.load().await.unwrap().is_none());
+    }
 
-        if tokio::fs::try_exists(&file_path)
-            .await
-            .map_err(PersistenceError::Io)?
-        {
-            tokio::fs::remove_file(&file_path)
-                .await
-                .map_err(PersistenceError::Io)?;
+    #[tokio::test]
+    async fn test_compact_failure_removes_merged_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = ParquetPersistence::<Ids>::new(dir.path());
+        for start in [0, 10, 20, 30] {
+            store.append(&Ids::new(start..start + 10)).await.unwrap();
         }
+        let parts = store.manifest().await.unwrap().parts;
+        std::fs::write(dir.path().join(&parts[3].file), b"not parquet").unwrap();
 
-        Ok(())
+        // The first run merges, the second fails on the damaged part
+        let runs = reserve_runs(dir.path(), 20).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(merge_runs::<Ids>(dir.path(), runs, WriterProperties::default()).is_err());
+        let files: Vec<_> = parts.into_iter().map(|part| part.file).collect();
+        assert_eq!(parquet_files(dir.path()), files);
+        assert_eq!(store.manifest().await.unwrap().parts.len(), 4);
     }
 }
@@ -1,4 +1,8 @@
+use crate::arrow_conversion::ToArrow;
 use crate::errors::Result;
+use crate::query::Expr;
+use crate::stream::SoAStream;
+use arrow_array::RecordBatch;
 use async_trait::async_trait;
 
 /// Core persistence trait for SoA structures enabling storage backend abstraction.
@@ -25,13 +29,41 @@ pub trait SoAPersistence<T> {
     /// Load data from storage
     async fn load(&self) -> Result<Option<T>>;
 
+    /// Load only `columns` (stored names, in the order given); `None` if storage is empty.
+    ///
+    /// Columnar backends skip the other columns entirely, so a report over three fields
+    /// reads three columns' worth of data.
+    async fn load_columns(&self, columns: &[&str]) -> Result<Option<RecordBatch>>;
+
+    /// Load a projection of the stored data: `P` is a `#[derive(SoA, SoAArrow)]` type whose
+    /// fields are a subset of `T`'s, and only its columns are read
+    async fn load_as<P>(&self) -> Result<Option<P>>
+    where
+        P: ToArrow + Send,
+    {
+        let columns = P::arrow_field_names();
+        match self.load_columns(&columns).await? {
+            Some(batch) => Ok(Some(P::from_record_batch(&batch)?)),
+            None => Ok(None),
+        }
+    }
+
     /// Append data to existing storage
     async fn append(&mut self, data: &T) -> Result<()>;
 
-    /// Query data with a predicate function
-    async fn query<F>(&self, predicate: F) -> Result<Option<T>>
+    /// Load only the rows matching `filter`; `None` if storage is empty.
+    ///
+    /// Column names in `filter` are the stored (Arrow) names. Backends evaluate the filter
+    /// next to the data, so rows that do not match are never decoded into `T`.
+    async fn query(&self, filter: &Expr) -> Result<Option<T>>;
+
+    /// Stream the stored data as chunks of at most `batch_size` rows, so memory use is
+    /// bounded by the chunk size instead of the dataset size.
+    ///
+    /// Must be called within a Tokio runtime. Panics if `batch_size` is zero.
+    fn stream(&self, batch_size: usize) -> SoAStream<T>
     where
-        F: Fn(&T) -> bool + Send + Sync;
+        T: Send + 'static;
 
     /// Get the count of records in storage
     async fn count(&self) -> Result<usize>;
@@ -67,7 +99,10 @@ pub trait SoABatchPersistence<T>: SoAPersistence<T> {
     /// Save multiple data batches efficiently
     async fn save_batches(&mut self, batches: &[T]) -> Result<()>;
 
-    /// Load data in batches with a specified batch size
+    /// Load all data as chunks of at most `batch_size` rows each; see
+    /// [`SoAPersistence::stream`] to avoid holding every chunk at once.
+    ///
+    /// Panics if `batch_size` is zero.
     async fn load_batches(&self, batch_size: usize) -> Result<Vec<T>>;
 
     /// Append multiple batches efficiently
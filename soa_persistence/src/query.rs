@@ -0,0 +1,328 @@
+//! Row-level filters for [`SoAPersistence::query`](crate::SoAPersistence::query).
+//!
+//! An [`Expr`] compares stored columns against literals and combines the comparisons with
+//! `and`, `or` and `!`:
+//!
+//! ```ignore
+//! let filter = col("status")
+//!     .eq(OrderStatus::Delivered)
+//!     .and(col("total_amount").gt(100.0));
+//! ```
+//!
+//! Backends evaluate it with Arrow compute kernels, so comparisons follow Arrow semantics: a
+//! null on either side never matches, and floats use the IEEE total order (NaN sorts above
+//! every other value). `#[derive(SoAEnum)]` values compare by their `u8` discriminant, i.e.
+//! in declaration order, not by variant name.
+
+use crate::arrow_conversion::ArrowField;
+use crate::errors::{PersistenceError, Result};
+use arrow::compute::kernels::{boolean, cmp};
+use arrow::compute::CastOptions;
+use arrow_array::cast::AsArray;
+use arrow_array::types::UInt8Type;
+use arrow_array::{Array, ArrayRef, BooleanArray, RecordBatch, Scalar, UInt8Array};
+use arrow_schema::{DataType, Schema};
+use std::collections::HashMap;
+use std::ops::Not;
+use std::sync::Arc;
+
+/// Comparison operator of [`Expr::Compare`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl CmpOp {
+    /// `true` for `<`, `<=`, `>` and `>=`
+    pub(crate) fn is_ordering(self) -> bool {
+        matches!(self, CmpOp::Lt | CmpOp::LtEq | CmpOp::Gt | CmpOp::GtEq)
+    }
+
+    /// Operator matching the non-null rows this one rejects
+    fn negate(self) -> CmpOp {
+        match self {
+            CmpOp::Eq => CmpOp::NotEq,
+            CmpOp::NotEq => CmpOp::Eq,
+            CmpOp::Lt => CmpOp::GtEq,
+            CmpOp::LtEq => CmpOp::Gt,
+            CmpOp::Gt => CmpOp::LtEq,
+            CmpOp::GtEq => CmpOp::Lt,
+        }
+    }
+}
+
+/// Row filter over stored columns, built with [`col`].
+///
+/// Negation is pushed down to the comparisons (`!(a < 1)` becomes `a >= 1`), so every leaf
+/// compares a single column with a constant and can be checked against column statistics.
+#[derive(Clone, Debug)]
+pub enum Expr {
+    /// `column <op> value`, where `value` is a one-element array
+    Compare {
+        column: String,
+        op: CmpOp,
+        value: ArrayRef,
+    },
+    IsNull(String),
+    IsNotNull(String),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// Start a filter on the stored column `name`
+pub fn col(name: impl Into<String>) -> Column {
+    Column(name.into())
+}
+
+/// A stored column, compared with values of any [`ArrowField`] type
+#[derive(Clone, Debug)]
+pub struct Column(String);
+
+impl Column {
+    fn compare<V: ArrowField>(self, op: CmpOp, value: V) -> Expr {
+        Expr::Compare {
+            column: self.0,
+            op,
+            value: V::to_array(&[value]),
+        }
+    }
+
+    pub fn eq<V: ArrowField>(self, value: V) -> Expr {
+        self.compare(CmpOp::Eq, value)
+    }
+
+    pub fn not_eq<V: ArrowField>(self, value: V) -> Expr {
+        self.compare(CmpOp::NotEq, value)
+    }
+
+    pub fn lt<V: ArrowField>(self, value: V) -> Expr {
+        self.compare(CmpOp::Lt, value)
+    }
+
+    pub fn lt_eq<V: ArrowField>(self, value: V) -> Expr {
+        self.compare(CmpOp::LtEq, value)
+    }
+
+    pub fn gt<V: ArrowField>(self, value: V) -> Expr {
+        self.compare(CmpOp::Gt, value)
+    }
+
+    pub fn gt_eq<V: ArrowField>(self, value: V) -> Expr {
+        self.compare(CmpOp::GtEq, value)
+    }
+
+    pub fn is_null(self) -> Expr {
+        Expr::IsNull(self.0)
+    }
+
+    pub fn is_not_null(self) -> Expr {
+        Expr::IsNotNull(self.0)
+    }
+}
+
+impl Expr {
+    pub fn and(self, other: Expr) -> Expr {
+        Expr::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Expr) -> Expr {
+        Expr::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Names of the columns the filter reads, without duplicates
+    pub fn columns(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.visit_columns(&mut |name| {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        });
+        names
+    }
+
+    fn visit_columns<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Expr::Compare { column, .. } | Expr::IsNull(column) | Expr::IsNotNull(column) => {
+                f(column)
+            }
+            Expr::And(l, r) | Expr::Or(l, r) => {
+                l.visit_columns(f);
+                r.visit_columns(f);
+            }
+        }
+    }
+
+    /// The same filter reading column `rename(name)` in place of each column `name`
+    pub(crate) fn map_columns(&self, rename: &impl Fn(&str) -> String) -> Expr {
+        match self {
+            Expr::Compare { column, op, value } => Expr::Compare {
+                column: rename(column),
+                op: *op,
+                value: value.clone(),
+            },
+            Expr::IsNull(column) => Expr::IsNull(rename(column)),
+            Expr::IsNotNull(column) => Expr::IsNotNull(rename(column)),
+            Expr::And(l, r) => Expr::And(
+                Box::new(l.map_columns(rename)),
+                Box::new(r.map_columns(rename)),
+            ),
+            Expr::Or(l, r) => Expr::Or(
+                Box::new(l.map_columns(rename)),
+                Box::new(r.map_columns(rename)),
+            ),
+        }
+    }
+
+    /// Fail with `ColumnNotFound` if the filter reads a column `schema` does not have
+    pub fn check_columns(&self, schema: &Schema) -> Result<()> {
+        match self
+            .columns()
+            .into_iter()
+            .find(|name| schema.column_with_name(name).is_none())
+        {
+            Some(name) => Err(PersistenceError::ColumnNotFound {
+                column_name: name.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluate the filter over `batch`; null entries mean "unknown" and are not matches
+    pub fn evaluate(&self, batch: &RecordBatch) -> Result<BooleanArray> {
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| PersistenceError::ColumnNotFound {
+                    column_name: name.to_string(),
+                })
+        };
+
+        Ok(match self {
+            Expr::Compare {
+                column: name,
+                op,
+                value,
+            } => {
+                let array = column(name)?;
+                if let Some(variants) = enum_variants(value) {
+                    let ranks = enum_ranks(array.as_ref(), &variants, name)?;
+                    let rank: ArrayRef =
+                        Arc::new(value.as_dictionary::<UInt8Type>().keys().clone());
+                    return compare(&ranks, *op, &rank);
+                }
+                let value = literal_as(value, array.data_type(), name)?;
+                compare(array, *op, &value)?
+            }
+            Expr::IsNull(name) => arrow::compute::is_null(column(name)?)?,
+            Expr::IsNotNull(name) => arrow::compute::is_not_null(column(name)?)?,
+            Expr::And(l, r) => boolean::and_kleene(&l.evaluate(batch)?, &r.evaluate(batch)?)?,
+            Expr::Or(l, r) => boolean::or_kleene(&l.evaluate(batch)?, &r.evaluate(batch)?)?,
+        })
+    }
+
+    /// Rows of `batch` the filter matches
+    pub fn filter(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let mask = self.evaluate(batch)?;
+        Ok(arrow::compute::filter_record_batch(batch, &mask)?)
+    }
+}
+
+impl Not for Expr {
+    type Output = Expr;
+
+    fn not(self) -> Expr {
+        match self {
+            Expr::Compare { column, op, value } => Expr::Compare {
+                column,
+                op: op.negate(),
+                value,
+            },
+            Expr::IsNull(column) => Expr::IsNotNull(column),
+            Expr::IsNotNull(column) => Expr::IsNull(column),
+            Expr::And(l, r) => Expr::Or(Box::new(!*l), Box::new(!*r)),
+            Expr::Or(l, r) => Expr::And(Box::new(!*l), Box::new(!*r)),
+        }
+    }
+}
+
+/// Variant names in declaration order if `value` is an enum literal: `ArrowField` for a
+/// `#[derive(SoAEnum)]` type builds a `Dictionary(UInt8, Utf8)` of every variant name, keyed
+/// by the discriminant
+pub(crate) fn enum_variants(value: &ArrayRef) -> Option<HashMap<&str, u8>> {
+    let dictionary = value.as_dictionary_opt::<UInt8Type>()?;
+    let names = dictionary.values().as_string_opt::<i32>()?;
+    Some(
+        names
+            .iter()
+            .enumerate()
+            .filter_map(|(i, name)| Some((name?, i as u8)))
+            .collect(),
+    )
+}
+
+/// Discriminant of every row of the enum column `array`, stored as `u8` discriminants or as
+/// variant names (plain or dictionary-encoded); names that are not variants are null
+fn enum_ranks(array: &dyn Array, variants: &HashMap<&str, u8>, column: &str) -> Result<UInt8Array> {
+    if array.data_type() == &DataType::UInt8 {
+        return Ok(array.as_primitive::<UInt8Type>().clone());
+    }
+
+    let names = arrow::compute::cast(array, &DataType::Utf8).map_err(|e| {
+        PersistenceError::TypeConversion {
+            message: format!(
+                "Cannot compare column '{}' of type {} with an enum literal: {}",
+                column,
+                array.data_type(),
+                e
+            ),
+        }
+    })?;
+    Ok(names
+        .as_string::<i32>()
+        .iter()
+        .map(|name| variants.get(name?).copied())
+        .collect())
+}
+
+/// Compare every element of `array` with the one-element `value` of the same type
+pub(crate) fn compare(array: &dyn Array, op: CmpOp, value: &ArrayRef) -> Result<BooleanArray> {
+    let value = Scalar::new(value);
+    let result = match op {
+        CmpOp::Eq => cmp::eq(&array, &value),
+        CmpOp::NotEq => cmp::neq(&array, &value),
+        CmpOp::Lt => cmp::lt(&array, &value),
+        CmpOp::LtEq => cmp::lt_eq(&array, &value),
+        CmpOp::Gt => cmp::gt(&array, &value),
+        CmpOp::GtEq => cmp::gt_eq(&array, &value),
+    };
+    Ok(result?)
+}
+
+/// Cast a literal to the type of the column it is compared with, so `col("quantity").gt(2)`
+/// works on a `u32` column. Values the column type cannot hold are an error.
+pub(crate) fn literal_as(value: &ArrayRef, data_type: &DataType, column: &str) -> Result<ArrayRef> {
+    if value.data_type() == data_type {
+        return Ok(value.clone());
+    }
+
+    let options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    arrow::compute::cast_with_options(value, data_type, &options).map_err(|e| {
+        PersistenceError::TypeConversion {
+            message: format!(
+                "Cannot compare column '{}' of type {} with a {} literal: {}",
+                column,
+                data_type,
+                value.data_type(),
+                e
+            ),
+        }
+    })
+}
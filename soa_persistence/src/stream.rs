@@ -0,0 +1,56 @@
+//! Reading persisted data in bounded memory, as a stream of SoA chunks.
+//!
+//! A blocking task decodes chunks into a bounded channel. It waits while the consumer is
+//! [`READ_AHEAD`] chunks behind, and stops reading as soon as the stream is dropped.
+
+use crate::arrow_conversion::ToArrow;
+use crate::errors::Result;
+use arrow_array::RecordBatch;
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+/// Chunks decoded ahead of the consumer before the reader waits
+pub const READ_AHEAD: usize = 2;
+
+/// Stream of SoA chunks returned by [`SoAPersistence::stream`](crate::SoAPersistence::stream).
+///
+/// Ends after the last chunk or after the first error. Dropping it cancels the read: the
+/// background task notices when it next hands over a chunk and stops.
+pub struct SoAStream<T> {
+    chunks: mpsc::Receiver<Result<T>>,
+}
+
+impl<T: Send + 'static> SoAStream<T> {
+    /// Run `produce` on Tokio's blocking pool, feeding the stream through `sender`
+    pub(crate) fn spawn(produce: impl FnOnce(mpsc::Sender<Result<T>>) + Send + 'static) -> Self {
+        let (sender, chunks) = mpsc::channel(READ_AHEAD);
+        tokio::task::spawn_blocking(move || produce(sender));
+        Self { chunks }
+    }
+}
+
+impl<T> Stream for SoAStream<T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<T>>> {
+        self.chunks.poll_recv(cx)
+    }
+}
+
+/// Decode `batches` and send them in order, until the first error or until the stream is
+/// dropped; returns `false` if it stopped early
+pub(crate) fn send_chunks<T: ToArrow>(
+    sender: &mpsc::Sender<Result<T>>,
+    batches: impl IntoIterator<Item = Result<RecordBatch>>,
+) -> bool {
+    for batch in batches {
+        let chunk = batch.and_then(|batch| T::from_record_batch(&batch));
+        let failed = chunk.is_err();
+        if sender.blocking_send(chunk).is_err() || failed {
+            return false;
+        }
+    }
+    true
+}
@@ -0,0 +1,546 @@
+//! Growable column whose buffer starts on an `ALIGN`-byte boundary.
+//!
+//! `#[derive(SoA)]` stores plain fields in an [`AlignedVec`] (64-byte aligned unless the
+//! struct sets `#[soa(align = N)]`), so no two columns share a cache line and SIMD kernels
+//! can use aligned loads from the start of any raw array.
+
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut, Range};
+use std::ptr::{self, NonNull};
+use std::slice;
+
+/// `Vec<T>` work-alike whose allocation is aligned to `ALIGN` bytes (a power of two).
+///
+/// Dereferences to `[T]`, so slicing, indexing and iteration work as for a `Vec`.
+pub struct AlignedVec<T, const ALIGN: usize = 64> {
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+    _marker: PhantomData<T>,
+}
+
+// SAFETY: `AlignedVec` owns its elements like `Vec<T>` does
+unsafe impl<T: Send, const ALIGN: usize> Send for AlignedVec<T, ALIGN> {}
+unsafe impl<T: Sync, const ALIGN: usize> Sync for AlignedVec<T, ALIGN> {}
+
+impl<T, const ALIGN: usize> AlignedVec<T, ALIGN> {
+    const ALIGN_IS_POWER_OF_TWO: () =
+        assert!(ALIGN.is_power_of_two(), "ALIGN must be a power of two");
+
+    pub fn new() -> Self {
+        let () = Self::ALIGN_IS_POWER_OF_TWO;
+        Self {
+            ptr: Self::dangling(),
+            len: 0,
+            cap: if mem::size_of::<T>() == 0 {
+                usize::MAX
+            } else {
+                0
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        let mut v = Self::new();
+        v.reserve(cap);
+        v
+    }
+
+    /// Alignment of the buffer in bytes: `ALIGN`, or more if `T` itself requires it
+    pub const fn alignment() -> usize {
+        if mem::align_of::<T>() > ALIGN {
+            mem::align_of::<T>()
+        } else {
+            ALIGN
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialized and `ptr` is non-null and aligned
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: as in `as_slice`, and `&mut self` guarantees unique access
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Reserve room for at least `additional` more elements
+    pub fn reserve(&mut self, additional: usize) {
+        let required = self
+            .len
+            .checked_add(additional)
+            .expect("AlignedVec capacity overflow");
+        if required > self.cap {
+            self.grow(required.max(self.cap.saturating_mul(2)).max(4));
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len == self.cap {
+            self.reserve(1);
+        }
+        // SAFETY: `len < cap`, so the slot is allocated and unused
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: the slot at the old last index is initialized and no longer counted
+        Some(unsafe { self.ptr.as_ptr().add(self.len).read() })
+    }
+
+    /// Keep only the first `len` elements
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = ptr::slice_from_raw_parts_mut(
+            // SAFETY: `len < self.len`, so the offset is within the allocation
+            unsafe { self.ptr.as_ptr().add(len) },
+            self.len - len,
+        );
+        // Shorten first so a panicking destructor leaks instead of double-dropping
+        self.len = len;
+        // SAFETY: the tail elements are initialized and no longer reachable
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Remove element `i`, moving the last element into its place
+    pub fn swap_remove(&mut self, i: usize) -> T {
+        let len = self.len;
+        assert!(
+            i < len,
+            "swap_remove index {} out of bounds (len {})",
+            i,
+            len
+        );
+        self.as_mut_slice().swap(i, len - 1);
+        self.pop().unwrap()
+    }
+
+    /// Remove element `i`, shifting every later element down by one
+    pub fn remove(&mut self, i: usize) -> T {
+        let len = self.len;
+        assert!(i < len, "remove index {} out of bounds (len {})", i, len);
+        // SAFETY: `i < len`; the tail is moved down over the read-out slot
+        unsafe {
+            let p = self.ptr.as_ptr().add(i);
+            let value = p.read();
+            ptr::copy(p.add(1), p, len - i - 1);
+            self.len = len - 1;
+            value
+        }
+    }
+
+    /// Keep only the elements for which `f` returns `true`, preserving their order
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if f(&self[i]) {
+                self.as_mut_slice().swap(kept, i);
+                kept += 1;
+            }
+        }
+        self.truncate(kept);
+    }
+
+    /// Remove the elements in `range`, returning them in order
+    pub fn drain(&mut self, range: Range<usize>) -> Vec<T> {
+        let len = self.len;
+        assert!(range.start <= range.end, "drain range start is after end");
+        assert!(
+            range.end <= len,
+            "drain range end {} out of bounds (len {})",
+            range.end,
+            len
+        );
+        // Forget the drained and trailing elements until the tail has been moved back
+        self.len = range.start;
+        let mut drained = Vec::with_capacity(range.len());
+        // SAFETY: every index read is initialized and read exactly once; the tail is then
+        // moved down over the drained slots
+        unsafe {
+            let base = self.ptr.as_ptr();
+            for i in range.clone() {
+                drained.push(base.add(i).read());
+            }
+            ptr::copy(base.add(range.end), base.add(range.start), len - range.end);
+        }
+        self.len = len - range.len();
+        drained
+    }
+
+    /// Move the elements into a `Vec<T>`
+    pub fn into_vec(self) -> Vec<T> {
+        self.into_iter().collect()
+    }
+
+    fn dangling() -> NonNull<T> {
+        // A non-null, suitably aligned address that is never dereferenced
+        NonNull::new(ptr::without_provenance_mut(Self::alignment())).unwrap()
+    }
+
+    fn layout(cap: usize) -> Layout {
+        let size = mem::size_of::<T>()
+            .checked_mul(cap)
+            .expect("AlignedVec capacity overflow");
+        Layout::from_size_align(size, Self::alignment()).expect("AlignedVec capacity overflow")
+    }
+
+    fn grow(&mut self, new_cap: usize) {
+        // Zero-sized elements never allocate and start with `cap == usize::MAX`
+        debug_assert!(mem::size_of::<T>() != 0);
+        let new_layout = Self::layout(new_cap);
+        // SAFETY: `new_layout` has a non-zero size; an existing buffer was allocated with
+        // `Self::layout(self.cap)`, and `realloc` keeps its alignment
+        let new_ptr = unsafe {
+            if self.cap == 0 {
+                alloc::alloc(new_layout)
+            } else {
+                alloc::realloc(
+                    self.ptr.as_ptr().cast(),
+                    Self::layout(self.cap),
+                    new_layout.size(),
+                )
+            }
+        };
+        self.ptr = match NonNull::new(new_ptr.cast()) {
+            Some(p) => p,
+            None => alloc::handle_alloc_error(new_layout),
+        };
+        self.cap = new_cap;
+    }
+}
+
+impl<T, const ALIGN: usize> Drop for AlignedVec<T, ALIGN> {
+    fn drop(&mut self) {
+        self.clear();
+        if self.cap != 0 && mem::size_of::<T>() != 0 {
+            // SAFETY: the buffer was allocated with this layout
+            unsafe { alloc::dealloc(self.ptr.as_ptr().cast(), Self::layout(self.cap)) };
+        }
+    }
+}
+
+impl<T, const ALIGN: usize> Default for AlignedVec<T, ALIGN> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const ALIGN: usize> Clone for AlignedVec<T, ALIGN> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug, const ALIGN: usize> fmt::Debug for AlignedVec<T, ALIGN> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<T: PartialEq, const ALIGN: usize> PartialEq for AlignedVec<T, ALIGN> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T, const ALIGN: usize> Deref for AlignedVec<T, ALIGN> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const ALIGN: usize> DerefMut for AlignedVec<T, ALIGN> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const ALIGN: usize> FromIterator<T> for AlignedVec<T, ALIGN> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut v = Self::new();
+        v.extend(iter);
+        v
+    }
+}
+
+impl<T, const ALIGN: usize> Extend<T> for AlignedVec<T, ALIGN> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T, const ALIGN: usize> From<Vec<T>> for AlignedVec<T, ALIGN> {
+    fn from(values: Vec<T>) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl<T: Clone, const ALIGN: usize> From<&[T]> for AlignedVec<T, ALIGN> {
+    fn from(values: &[T]) -> Self {
+        values.iter().cloned().collect()
+    }
+}
+
+impl<'a, T, const ALIGN: usize> IntoIterator for &'a AlignedVec<T, ALIGN> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T, const ALIGN: usize> IntoIterator for &'a mut AlignedVec<T, ALIGN> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+impl<T, const ALIGN: usize> IntoIterator for AlignedVec<T, ALIGN> {
+    type Item = T;
+    type IntoIter = IntoIter<T, ALIGN>;
+
+    fn into_iter(mut self) -> IntoIter<T, ALIGN> {
+        let end = self.len;
+        // The iterator now owns the elements; `self` only frees the buffer
+        self.len = 0;
+        IntoIter {
+            vec: self,
+            start: 0,
+            end,
+        }
+    }
+}
+
+/// Owning iterator over an [`AlignedVec`]
+pub struct IntoIter<T, const ALIGN: usize = 64> {
+    vec: AlignedVec<T, ALIGN>,
+    start: usize,
+    end: usize,
+}
+
+impl<T, const ALIGN: usize> Iterator for IntoIter<T, ALIGN> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.start += 1;
+        // SAFETY: `start - 1` is in the unread range, which is initialized
+        Some(unsafe { self.vec.ptr.as_ptr().add(self.start - 1).read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.start;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T, const ALIGN: usize> DoubleEndedIterator for IntoIter<T, ALIGN> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        // SAFETY: `end` is in the unread range, which is initialized
+        Some(unsafe { self.vec.ptr.as_ptr().add(self.end).read() })
+    }
+}
+
+impl<T, const ALIGN: usize> ExactSizeIterator for IntoIter<T, ALIGN> {}
+impl<T, const ALIGN: usize> FusedIterator for IntoIter<T, ALIGN> {}
+
+impl<T, const ALIGN: usize> Drop for IntoIter<T, ALIGN> {
+    fn drop(&mut self) {
+        // SAFETY: the unread range is initialized and dropped exactly once
+        unsafe {
+            let unread = ptr::slice_from_raw_parts_mut(
+                self.vec.ptr.as_ptr().add(self.start),
+                self.end - self.start,
+            );
+            ptr::drop_in_place(unread);
+        }
+    }
+}
+
+// These exercise the unsafe code above; also run them under Miri with
+// `cargo +nightly miri test -p soa_runtime aligned`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Counts its own drops into a shared counter
+    struct Tracked(Rc<Cell<usize>>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    fn tracked(n: usize) -> (AlignedVec<Tracked>, Rc<Cell<usize>>) {
+        let drops = Rc::new(Cell::new(0));
+        let v = (0..n).map(|_| Tracked(drops.clone())).collect();
+        (v, drops)
+    }
+
+    #[test]
+    fn test_alignment_after_growth() {
+        let mut v: AlignedVec<u8, 128> = AlignedVec::new();
+        for i in 0..1000 {
+            v.push(i as u8);
+            assert_eq!(v.as_ptr() as usize % 128, 0);
+        }
+        assert_eq!(v.len(), 1000);
+        assert!(v.iter().enumerate().all(|(i, &x)| x == i as u8));
+
+        // `T`'s own alignment wins when it is larger than `ALIGN`
+        #[repr(align(32))]
+        struct Wide(#[allow(dead_code)] u8);
+        assert_eq!(AlignedVec::<Wide, 8>::alignment(), 32);
+        let mut wide: AlignedVec<Wide, 8> = AlignedVec::with_capacity(3);
+        (0..10).for_each(|i| wide.push(Wide(i)));
+        assert_eq!(wide.as_ptr() as usize % 32, 0);
+    }
+
+    #[test]
+    fn test_remove_retain_truncate() {
+        let mut v: AlignedVec<u32> = (0..10).collect();
+        assert_eq!(v.remove(0), 0);
+        assert_eq!(v.remove(8), 9);
+        assert_eq!(v.swap_remove(1), 2);
+        assert_eq!(&v[..], &[1, 8, 3, 4, 5, 6, 7]);
+
+        v.retain(|&x| x % 2 == 1);
+        assert_eq!(&v[..], &[1, 3, 5, 7]);
+        v.truncate(10);
+        assert_eq!(v.len(), 4);
+        v.truncate(1);
+        assert_eq!(&v[..], &[1]);
+        v.clear();
+        assert!(v.is_empty());
+        assert_eq!(v.pop(), None);
+    }
+
+    #[test]
+    fn test_drops_on_remove_retain_truncate() {
+        let (mut v, drops) = tracked(10);
+        drop(v.remove(3));
+        drop(v.swap_remove(0));
+        assert_eq!(drops.get(), 2);
+
+        let mut keep = [true, false].into_iter().cycle();
+        v.retain(|_| keep.next().unwrap());
+        assert_eq!((v.len(), drops.get()), (4, 6));
+        v.truncate(1);
+        assert_eq!(drops.get(), 9);
+        drop(v);
+        assert_eq!(drops.get(), 10);
+    }
+
+    #[test]
+    fn test_drops_with_partial_into_iter() {
+        let (v, drops) = tracked(6);
+        let mut iter = v.into_iter();
+        drop(iter.next());
+        drop(iter.next_back());
+        assert_eq!(iter.len(), 4);
+        assert_eq!(drops.get(), 2);
+        drop(iter);
+        assert_eq!(drops.get(), 6);
+
+        let (v, drops) = tracked(3);
+        assert_eq!(v.into_vec().len(), 3);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut v: AlignedVec<String> = (0..6).map(|i| i.to_string()).collect();
+        assert_eq!(v.drain(1..3), ["1", "2"]);
+        assert_eq!(&v[..], ["0", "3", "4", "5"]);
+        assert!(v.drain(2..2).is_empty());
+        assert_eq!(v.drain(2..4), ["4", "5"]);
+        assert_eq!(&v[..], ["0", "3"]);
+
+        let (mut v, drops) = tracked(8);
+        let drained = v.drain(2..5);
+        assert_eq!((v.len(), drops.get()), (5, 0));
+        drop(drained);
+        assert_eq!(drops.get(), 3);
+        drop(v);
+        assert_eq!(drops.get(), 8);
+    }
+
+    #[test]
+    fn test_zero_sized_elements() {
+        let mut v: AlignedVec<()> = AlignedVec::new();
+        assert_eq!(v.capacity(), usize::MAX);
+        (0..100).for_each(|_| v.push(()));
+        assert_eq!(v.len(), 100);
+        v.remove(5);
+        v.retain(|_| false);
+        assert!(v.is_empty());
+
+        // A zero-sized type cannot hold a counter, so count its drops in a thread-local
+        thread_local!(static DROPS: Cell<usize> = const { Cell::new(0) });
+        #[derive(Debug, PartialEq)]
+        struct Unit;
+        impl Drop for Unit {
+            fn drop(&mut self) {
+                DROPS.with(|d| d.set(d.get() + 1));
+            }
+        }
+
+        let mut units: AlignedVec<Unit> = (0..5).map(|_| Unit).collect();
+        units.truncate(3);
+        assert_eq!(DROPS.with(Cell::get), 2);
+        assert_eq!(units.drain(0..1).len(), 1);
+        assert_eq!(DROPS.with(Cell::get), 3);
+        let mut iter = units.into_iter();
+        drop(iter.next());
+        drop(iter);
+        assert_eq!(DROPS.with(Cell::get), 5);
+    }
+}
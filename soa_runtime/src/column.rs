@@ -0,0 +1,448 @@
+//! Row-level operations shared by every column type `#[derive(SoA)]` generates.
+
+use std::iter::{FusedIterator, Map, Zip};
+use std::ops::{Bound, Range, RangeBounds};
+
+use crate::{AlignedVec, BinaryColumn, Bitmap, NullableColumn, StringColumn};
+
+/// Structural row operations implemented by every SoA column type.
+///
+/// The generated `*SoA` applies each operation to all of its columns, which keeps them
+/// the same length. Row indices follow `Vec` semantics and out-of-bounds indices panic.
+pub trait Column: IntoIterator<Item = <Self as Column>::Owned> {
+    /// Owned value of a single row
+    type Owned;
+
+    /// Reserve room for at least `additional` more rows
+    fn reserve(&mut self, additional: usize);
+
+    /// Remove row `i`, moving the last row into its place
+    fn swap_remove(&mut self, i: usize) -> Self::Owned;
+
+    /// Remove row `i`, shifting every later row down by one
+    fn remove(&mut self, i: usize) -> Self::Owned;
+
+    /// Keep only the first `len` rows
+    fn truncate(&mut self, len: usize);
+
+    fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Keep row `i` only if `keep[i]` is set; `keep` covers every row
+    fn retain_mask(&mut self, keep: &[bool]);
+
+    /// Remove the rows in `range`, returning them in order
+    fn drain(&mut self, range: Range<usize>) -> Vec<Self::Owned>;
+
+    /// Move every row of `other` to the end, leaving `other` empty
+    fn append(&mut self, other: &mut Self);
+}
+
+/// Resolve `range` against a column of `len` rows, panicking like `Vec::drain`
+pub fn resolve_range(range: impl RangeBounds<usize>, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&s) => s,
+        Bound::Excluded(&s) => s.checked_add(1).expect("range start overflows usize"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&e) => e.checked_add(1).expect("range end overflows usize"),
+        Bound::Excluded(&e) => e,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range start {} is after end {}", start, end);
+    assert!(end <= len, "range end {} out of bounds (len {})", end, len);
+    start..end
+}
+
+impl<T> Column for Vec<T> {
+    type Owned = T;
+
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> T {
+        Vec::swap_remove(self, i)
+    }
+
+    fn remove(&mut self, i: usize) -> T {
+        Vec::remove(self, i)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+
+    fn retain_mask(&mut self, keep: &[bool]) {
+        assert_eq!(self.len(), keep.len(), "retain mask length mismatch");
+        let mut keep = keep.iter();
+        self.retain(|_| *keep.next().unwrap());
+    }
+
+    fn drain(&mut self, range: Range<usize>) -> Vec<T> {
+        Vec::drain(self, range).collect()
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        Vec::append(self, other);
+    }
+}
+
+impl<T, const ALIGN: usize> Column for AlignedVec<T, ALIGN> {
+    type Owned = T;
+
+    fn reserve(&mut self, additional: usize) {
+        AlignedVec::reserve(self, additional);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> T {
+        AlignedVec::swap_remove(self, i)
+    }
+
+    fn remove(&mut self, i: usize) -> T {
+        AlignedVec::remove(self, i)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        AlignedVec::truncate(self, len);
+    }
+
+    fn retain_mask(&mut self, keep: &[bool]) {
+        assert_eq!(self.len(), keep.len(), "retain mask length mismatch");
+        let mut keep = keep.iter();
+        self.retain(|_| *keep.next().unwrap());
+    }
+
+    fn drain(&mut self, range: Range<usize>) -> Vec<T> {
+        AlignedVec::drain(self, range)
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        self.extend(std::mem::take(other));
+    }
+}
+
+impl Column for Bitmap {
+    type Owned = bool;
+
+    fn reserve(&mut self, additional: usize) {
+        Bitmap::reserve(self, additional);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> bool {
+        let removed = self.get(i);
+        let last = self.get(self.len() - 1);
+        self.set(i, last);
+        Column::truncate(self, self.len() - 1);
+        removed
+    }
+
+    fn remove(&mut self, i: usize) -> bool {
+        let removed = self.get(i);
+        for j in i..self.len() - 1 {
+            let next = self.get(j + 1);
+            self.set(j, next);
+        }
+        Column::truncate(self, self.len() - 1);
+        removed
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Bitmap::truncate(self, len);
+    }
+
+    fn retain_mask(&mut self, keep: &[bool]) {
+        assert_eq!(self.len(), keep.len(), "retain mask length mismatch");
+        *self = self
+            .iter()
+            .zip(keep)
+            .filter(|(_, &k)| k)
+            .map(|(bit, _)| bit)
+            .collect();
+    }
+
+    fn drain(&mut self, range: Range<usize>) -> Vec<bool> {
+        let bits: Vec<bool> = self.iter().collect();
+        let drained = bits[range.clone()].to_vec();
+        *self = bits[..range.start]
+            .iter()
+            .chain(&bits[range.end..])
+            .copied()
+            .collect();
+        drained
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        Bitmap::reserve(self, other.len());
+        for bit in std::mem::take(other) {
+            self.push(bit);
+        }
+    }
+}
+
+impl<T> Column for NullableColumn<T> {
+    type Owned = Option<T>;
+
+    fn reserve(&mut self, additional: usize) {
+        let (values, validity) = self.parts_mut();
+        values.reserve(additional);
+        Column::reserve(validity, additional);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> Option<T> {
+        let (values, validity) = self.parts_mut();
+        let value = values.swap_remove(i);
+        Column::swap_remove(validity, i).then_some(value)
+    }
+
+    fn remove(&mut self, i: usize) -> Option<T> {
+        let (values, validity) = self.parts_mut();
+        let value = values.remove(i);
+        Column::remove(validity, i).then_some(value)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        let (values, validity) = self.parts_mut();
+        values.truncate(len);
+        Column::truncate(validity, len);
+    }
+
+    fn retain_mask(&mut self, keep: &[bool]) {
+        let (values, validity) = self.parts_mut();
+        values.retain_mask(keep);
+        validity.retain_mask(keep);
+    }
+
+    fn drain(&mut self, range: Range<usize>) -> Vec<Option<T>> {
+        let (values, validity) = self.parts_mut();
+        let valid = Column::drain(validity, range.clone());
+        values
+            .drain(range)
+            .zip(valid)
+            .map(|(value, valid)| valid.then_some(value))
+            .collect()
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        let (values, validity) = self.parts_mut();
+        let (other_values, other_validity) = other.parts_mut();
+        values.append(other_values);
+        Column::append(validity, other_validity);
+    }
+}
+
+impl Column for BinaryColumn {
+    type Owned = Vec<u8>;
+
+    fn reserve(&mut self, additional: usize) {
+        BinaryColumn::reserve(self, additional);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> Vec<u8> {
+        let removed = self.get(i).to_vec();
+        let last = self.len() - 1;
+        if i != last {
+            let moved = self.get(last).to_vec();
+            self.set(i, moved);
+        }
+        Column::truncate(self, last);
+        removed
+    }
+
+    fn remove(&mut self, i: usize) -> Vec<u8> {
+        Column::drain(self, i..i + 1).pop().unwrap()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        BinaryColumn::truncate(self, len);
+    }
+
+    fn retain_mask(&mut self, keep: &[bool]) {
+        assert_eq!(self.len(), keep.len(), "retain mask length mismatch");
+        *self = self
+            .iter()
+            .zip(keep)
+            .filter(|(_, &k)| k)
+            .map(|(row, _)| row)
+            .collect();
+    }
+
+    fn drain(&mut self, range: Range<usize>) -> Vec<Vec<u8>> {
+        let drained = range.clone().map(|i| self.get(i).to_vec()).collect();
+        self.remove_rows(range);
+        drained
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        BinaryColumn::append(self, other);
+    }
+}
+
+impl Column for StringColumn {
+    type Owned = String;
+
+    fn reserve(&mut self, additional: usize) {
+        StringColumn::reserve(self, additional);
+    }
+
+    fn swap_remove(&mut self, i: usize) -> String {
+        let removed = self.get(i).to_string();
+        let last = self.len() - 1;
+        if i != last {
+            let moved = self.get(last).to_string();
+            self.set(i, moved);
+        }
+        Column::truncate(self, last);
+        removed
+    }
+
+    fn remove(&mut self, i: usize) -> String {
+        Column::drain(self, i..i + 1).pop().unwrap()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        StringColumn::truncate(self, len);
+    }
+
+    fn retain_mask(&mut self, keep: &[bool]) {
+        assert_eq!(self.len(), keep.len(), "retain mask length mismatch");
+        *self = self
+            .iter()
+            .zip(keep)
+            .filter(|(_, &k)| k)
+            .map(|(row, _)| row)
+            .collect();
+    }
+
+    fn drain(&mut self, range: Range<usize>) -> Vec<String> {
+        let drained = range.clone().map(|i| self.get(i).to_string()).collect();
+        self.remove_rows(range);
+        drained
+    }
+
+    fn append(&mut self, other: &mut Self) {
+        StringColumn::append(self, other);
+    }
+}
+
+/// Borrowing iterator over the rows of a [`Bitmap`], [`BinaryColumn`] or [`StringColumn`].
+#[derive(Clone, Debug)]
+pub struct Rows<'a, C> {
+    column: &'a C,
+    rows: Range<usize>,
+}
+
+impl<'a, C> Rows<'a, C> {
+    pub(crate) fn new(column: &'a C, len: usize) -> Self {
+        Self {
+            column,
+            rows: 0..len,
+        }
+    }
+}
+
+macro_rules! impl_rows {
+    ($($column:ty => $item:ty, |$c:ident, $i:ident| $row:expr;)*) => {
+        $(
+            impl<'a> Iterator for Rows<'a, $column> {
+                type Item = $item;
+
+                fn next(&mut self) -> Option<$item> {
+                    let ($c, $i) = (self.column, self.rows.next()?);
+                    Some($row)
+                }
+
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    self.rows.size_hint()
+                }
+            }
+
+            impl<'a> DoubleEndedIterator for Rows<'a, $column> {
+                fn next_back(&mut self) -> Option<$item> {
+                    let ($c, $i) = (self.column, self.rows.next_back()?);
+                    Some($row)
+                }
+            }
+
+            impl ExactSizeIterator for Rows<'_, $column> {}
+            impl FusedIterator for Rows<'_, $column> {}
+        )*
+    };
+}
+
+impl_rows! {
+    Bitmap => bool, |c, i| c.get(i);
+    BinaryColumn => &'a [u8], |c, i| c.get(i);
+    StringColumn => &'a str, |c, i| c.get(i);
+}
+
+/// Owning iterator over the rows of a [`Bitmap`], [`BinaryColumn`] or [`StringColumn`].
+#[derive(Clone, Debug)]
+pub struct IntoRows<C> {
+    column: C,
+    rows: Range<usize>,
+}
+
+macro_rules! impl_into_rows {
+    ($($column:ty => $item:ty, |$c:ident, $i:ident| $row:expr;)*) => {
+        $(
+            impl Iterator for IntoRows<$column> {
+                type Item = $item;
+
+                fn next(&mut self) -> Option<$item> {
+                    let ($c, $i) = (&self.column, self.rows.next()?);
+                    Some($row)
+                }
+
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    self.rows.size_hint()
+                }
+            }
+
+            impl DoubleEndedIterator for IntoRows<$column> {
+                fn next_back(&mut self) -> Option<$item> {
+                    let ($c, $i) = (&self.column, self.rows.next_back()?);
+                    Some($row)
+                }
+            }
+
+            impl ExactSizeIterator for IntoRows<$column> {}
+            impl FusedIterator for IntoRows<$column> {}
+
+            impl IntoIterator for $column {
+                type Item = $item;
+                type IntoIter = IntoRows<$column>;
+
+                fn into_iter(self) -> IntoRows<$column> {
+                    let rows = 0..self.len();
+                    IntoRows { column: self, rows }
+                }
+            }
+        )*
+    };
+}
+
+impl_into_rows! {
+    Bitmap => bool, |c, i| c.get(i);
+    BinaryColumn => Vec<u8>, |c, i| c.get(i).to_vec();
+    StringColumn => String, |c, i| c.get(i).to_string();
+}
+
+/// Owning iterator over the rows of a [`NullableColumn`]
+pub type NullableIntoIter<T> =
+    Map<Zip<std::vec::IntoIter<T>, IntoRows<Bitmap>>, fn((T, bool)) -> Option<T>>;
+
+impl<T> IntoIterator for NullableColumn<T> {
+    type Item = Option<T>;
+    type IntoIter = NullableIntoIter<T>;
+
+    fn into_iter(self) -> NullableIntoIter<T> {
+        let (values, validity) = self.into_parts();
+        values
+            .into_iter()
+            .zip(validity)
+            .map((|(value, valid)| valid.then_some(value)) as fn((T, bool)) -> Option<T>)
+    }
+}
@@ -0,0 +1,54 @@
+//! Fixed-width lane arrays used by the AoSoA tiles `#[derive(SoA)]` generates.
+
+use std::ops::{Deref, DerefMut};
+
+/// Field types an AoSoA tile can hold: values are copied in and out of their lane, and the
+/// unused lanes of a partially filled tile hold `Default::default()`.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be stored in AoSoA lanes",
+    label = "`layout = \"aosoa\"` needs every field to be `Copy + Default`",
+    note = "derive `Clone, Copy, Default` for `{Self}`, mark the field `#[soa(skip)]`, or use \
+            the default layout"
+)]
+pub trait LaneValue: Copy + Default {}
+
+impl<T: Copy + Default> LaneValue for T {}
+
+/// Compile-time check emitted by `#[soa(layout = "aosoa")]` for each field type
+#[doc(hidden)]
+pub const fn assert_lane_value<T: LaneValue>() {}
+
+/// `N` values of one field within an AoSoA tile, aligned to a 64-byte cache line.
+///
+/// Every field of a tile starts on its own cache line, so SIMD kernels can use aligned
+/// loads (`_mm256_load_pd` and friends) on any lane array.
+#[repr(C, align(64))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Lanes<T, const N: usize>(pub [T; N]);
+
+impl<T, const N: usize> Lanes<T, N> {
+    /// Alignment of every lane array, in bytes
+    pub const ALIGN: usize = 64;
+
+    pub fn as_ptr(&self) -> *const T {
+        self.0.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.0.as_mut_ptr()
+    }
+}
+
+impl<T, const N: usize> Deref for Lanes<T, N> {
+    type Target = [T; N];
+
+    fn deref(&self) -> &[T; N] {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> DerefMut for Lanes<T, N> {
+    fn deref_mut(&mut self) -> &mut [T; N] {
+        &mut self.0
+    }
+}
@@ -1,5 +1,35 @@
 //! Traits and helpers used by the `#[derive(SoA)]` proc-macro.
 
+mod aligned;
+mod bitmap;
+mod column;
+mod lanes;
+mod nullable;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod row_set;
+mod stable_hash;
+mod store;
+mod strided;
+mod varlen;
+
+pub use aligned::{AlignedVec, IntoIter as AlignedIntoIter};
+pub use bitmap::Bitmap;
+pub use column::{resolve_range, Column, IntoRows, NullableIntoIter, Rows};
+pub use lanes::{assert_lane_value, LaneValue, Lanes};
+pub use nullable::{IterMut as NullableIterMut, NullPlaceholder, NullableColumn};
+#[cfg(feature = "parallel")]
+pub use parallel::par_row_chunks;
+pub use row_set::RowSet;
+pub use stable_hash::{jump_hash, StableHash, StableHasher};
+pub use store::{shard_skew, DuplicateKeyError};
+pub use strided::{Strided, StridedIter};
+pub use varlen::{BinaryColumn, StringColumn};
+
+/// Re-exported for the generated `par_*` methods
+#[cfg(feature = "parallel")]
+pub use rayon;
+
 pub trait SoaModel {
     type Soa;
     type View<'a>
@@ -14,6 +44,30 @@ pub trait SoaModel {
     fn view_mut(soa: &mut Self::Soa, i: usize) -> Self::ViewMut<'_>;
 }
 
+/// Fieldless enum with a stable `u8` discriminant mapping, implemented by `#[derive(SoAEnum)]`.
+///
+/// Discriminants follow declaration order, so `VARIANTS[v.to_u8() as usize]` is the name of `v`.
+pub trait SoaEnum: Sized + 'static {
+    /// Name of the enum type
+    const NAME: &'static str;
+    /// Variant names in declaration order
+    const VARIANTS: &'static [&'static str];
+
+    fn to_u8(&self) -> u8;
+    fn from_u8(value: u8) -> Option<Self>;
+
+    fn variant_name(&self) -> &'static str {
+        Self::VARIANTS[self.to_u8() as usize]
+    }
+
+    fn from_variant_name(name: &str) -> Option<Self> {
+        Self::VARIANTS
+            .iter()
+            .position(|v| *v == name)
+            .and_then(|i| Self::from_u8(i as u8))
+    }
+}
+
 /// Simple cache-line padding wrapper to reduce false sharing between adjacent items.
 #[repr(align(64))]
 pub struct CachePadded<T>(pub T);
@@ -0,0 +1,181 @@
+//! Column storage for `Option<T>` fields.
+
+use std::ops::Range;
+
+use crate::Bitmap;
+
+/// Column of optional values stored as a dense value column plus a packed validity bitmap.
+///
+/// Null rows hold `T::default()` as a placeholder in `values()`, so the value column can be
+/// scanned directly as long as the validity bitmap is consulted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NullableColumn<T> {
+    values: Vec<T>,
+    validity: Bitmap,
+}
+
+impl<T> NullableColumn<T> {
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            validity: Bitmap::new(),
+        }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(cap),
+            validity: Bitmap::with_capacity(cap),
+        }
+    }
+
+    /// Build a column from values and a validity bitmap of the same length
+    pub fn from_parts(values: Vec<T>, validity: Bitmap) -> Self {
+        assert_eq!(
+            values.len(),
+            validity.len(),
+            "NullableColumn values and validity length mismatch"
+        );
+        Self { values, validity }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+        self.validity.reserve(additional);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Value at row `i`, `None` for nulls; panics if `i` is out of bounds
+    #[inline]
+    pub fn value(&self, i: usize) -> Option<&T> {
+        if self.validity.get(i) {
+            Some(&self.values[i])
+        } else {
+            None
+        }
+    }
+
+    /// Mutable value at row `i`, `None` for nulls; panics if `i` is out of bounds
+    #[inline]
+    pub fn value_mut(&mut self, i: usize) -> Option<&mut T> {
+        if self.validity.get(i) {
+            Some(&mut self.values[i])
+        } else {
+            None
+        }
+    }
+
+    /// Dense value column, with placeholders at null rows
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Validity bitmap: bit `i` is set when row `i` holds a value
+    pub fn validity(&self) -> &Bitmap {
+        &self.validity
+    }
+
+    pub fn null_count(&self) -> usize {
+        self.len() - self.validity.count_ones()
+    }
+
+    /// Split into the value column and the validity bitmap
+    pub fn into_parts(self) -> (Vec<T>, Bitmap) {
+        (self.values, self.validity)
+    }
+
+    pub(crate) fn parts_mut(&mut self) -> (&mut Vec<T>, &mut Bitmap) {
+        (&mut self.values, &mut self.validity)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Option<&T>> + ExactSizeIterator + '_ {
+        (0..self.len()).map(move |i| self.value(i))
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            values: self.values.iter_mut(),
+            validity: &self.validity,
+            rows: 0..self.validity.len(),
+        }
+    }
+}
+
+/// Mutable iterator over a [`NullableColumn`], yielding `None` for null rows.
+pub struct IterMut<'a, T> {
+    values: std::slice::IterMut<'a, T>,
+    validity: &'a Bitmap,
+    rows: Range<usize>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = Option<&'a mut T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.values.next()?;
+        let i = self.rows.next()?;
+        Some(self.validity.get(i).then_some(value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for IterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let value = self.values.next_back()?;
+        let i = self.rows.next_back()?;
+        Some(self.validity.get(i).then_some(value))
+    }
+}
+
+impl<T> ExactSizeIterator for IterMut<'_, T> {}
+
+/// Value types a [`NullableColumn`] can store: null rows hold `T::default()`, so this is
+/// every `Default` type. It exists to explain a missing `Default` on an `Option<T>` field.
+#[diagnostic::on_unimplemented(
+    message = "`Option<{Self}>` fields need `{Self}: Default` for the placeholder stored at null rows",
+    label = "`{Self}` has no `Default` to store at null rows",
+    note = "derive or implement `Default` for `{Self}` (an enum can mark a variant `#[default]`)"
+)]
+pub trait NullPlaceholder: Default {}
+
+impl<T: Default> NullPlaceholder for T {}
+
+impl<T> NullableColumn<T> {
+    pub fn push(&mut self, value: Option<T>)
+    where
+        T: NullPlaceholder,
+    {
+        self.validity.push(value.is_some());
+        self.values.push(value.unwrap_or_default());
+    }
+
+    /// Replace row `i`; panics if `i` is out of bounds
+    pub fn set(&mut self, i: usize, value: Option<T>)
+    where
+        T: NullPlaceholder,
+    {
+        self.validity.set(i, value.is_some());
+        self.values[i] = value.unwrap_or_default();
+    }
+}
+
+impl<T: NullPlaceholder> FromIterator<Option<T>> for NullableColumn<T> {
+    fn from_iter<I: IntoIterator<Item = Option<T>>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut column = NullableColumn::with_capacity(iter.size_hint().0);
+        for value in iter {
+            column.push(value);
+        }
+        column
+    }
+}
@@ -0,0 +1,18 @@
+//! Rayon support used by the `par_*` methods `#[derive(SoA)]` and `#[derive(SoAStore)]`
+//! generate when `soa_macros/parallel` is enabled.
+
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+/// Rows `0..len` split into consecutive ranges of `chunk_size` rows (the last may be shorter),
+/// processed in parallel. Panics if `chunk_size` is zero.
+pub fn par_row_chunks(
+    len: usize,
+    chunk_size: usize,
+) -> impl IndexedParallelIterator<Item = Range<usize>> {
+    assert!(chunk_size > 0, "chunk size must be non-zero");
+    (0..len.div_ceil(chunk_size))
+        .into_par_iter()
+        .map(move |c| c * chunk_size..((c + 1) * chunk_size).min(len))
+}
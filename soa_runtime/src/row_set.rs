@@ -0,0 +1,482 @@
+//! Compressed set of row indices, the value type of `#[soa(index = "bitmap")]` indexes.
+//!
+//! Rows are split into chunks of 2^16. A chunk holding few rows stores their sorted low bits
+//! (2 bytes per row); once it holds more than `ARRAY_MAX` it switches to an 8 KiB bit block,
+//! so a set never costs more than one bit per row plus a small per-chunk header, and sparse
+//! sets cost far less.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{BitAnd, BitOr};
+
+const CHUNK_SHIFT: u32 = 16;
+const CHUNK_MASK: usize = (1 << CHUNK_SHIFT) - 1;
+const BLOCK_WORDS: usize = (1 << CHUNK_SHIFT) / 64;
+/// Largest chunk kept as a sorted array; at this size the array and the bit block are equal
+const ARRAY_MAX: usize = 4096;
+
+/// Set of row indices, iterated in ascending order and combined with `&` / `|`.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct RowSet {
+    /// Non-empty chunks sorted by chunk number (`row >> 16`)
+    chunks: Vec<(usize, Chunk)>,
+}
+
+/// Low 16 bits of the rows of one chunk. Always in its canonical form: `Array` up to
+/// `ARRAY_MAX` rows, `Block` above, so equal sets compare equal.
+#[derive(Clone, PartialEq, Eq)]
+enum Chunk {
+    /// Sorted, without duplicates
+    Array(Vec<u16>),
+    /// One bit per row, with the number of bits set
+    Block(Box<[u64; BLOCK_WORDS]>, usize),
+}
+
+impl RowSet {
+    pub const fn new() -> Self {
+        Self { chunks: Vec::new() }
+    }
+
+    /// Number of rows in the set
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(|(_, c)| c.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn contains(&self, row: usize) -> bool {
+        self.find(row >> CHUNK_SHIFT)
+            .is_ok_and(|c| self.chunks[c].1.contains(low(row)))
+    }
+
+    /// Add `row`; returns `false` if it was already present
+    pub fn insert(&mut self, row: usize) -> bool {
+        let key = row >> CHUNK_SHIFT;
+        match self.find(key) {
+            Ok(c) => self.chunks[c].1.insert(low(row)),
+            Err(c) => {
+                self.chunks.insert(c, (key, Chunk::Array(vec![low(row)])));
+                true
+            }
+        }
+    }
+
+    /// Remove `row`; returns `false` if it was not present
+    pub fn remove(&mut self, row: usize) -> bool {
+        let Ok(c) = self.find(row >> CHUNK_SHIFT) else {
+            return false;
+        };
+        let removed = self.chunks[c].1.remove(low(row));
+        if self.chunks[c].1.len() == 0 {
+            self.chunks.remove(c);
+        }
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// Rows in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.chunks.iter().flat_map(|(key, chunk)| {
+            let base = key << CHUNK_SHIFT;
+            chunk.iter().map(move |lo| base | lo as usize)
+        })
+    }
+
+    /// Rows in both sets
+    pub fn and(&self, other: &RowSet) -> RowSet {
+        let mut chunks = Vec::new();
+        let (mut a, mut b) = (
+            self.chunks.iter().peekable(),
+            other.chunks.iter().peekable(),
+        );
+        while let (Some((ka, ca)), Some((kb, cb))) = (a.peek(), b.peek()) {
+            match ka.cmp(kb) {
+                Ordering::Less => {
+                    a.next();
+                }
+                Ordering::Greater => {
+                    b.next();
+                }
+                Ordering::Equal => {
+                    let chunk = ca.and(cb);
+                    if chunk.len() > 0 {
+                        chunks.push((*ka, chunk));
+                    }
+                    a.next();
+                    b.next();
+                }
+            }
+        }
+        RowSet { chunks }
+    }
+
+    /// Rows in either set
+    pub fn or(&self, other: &RowSet) -> RowSet {
+        let mut chunks = Vec::with_capacity(self.chunks.len().max(other.chunks.len()));
+        let (mut a, mut b) = (
+            self.chunks.iter().peekable(),
+            other.chunks.iter().peekable(),
+        );
+        loop {
+            let next = match (a.peek(), b.peek()) {
+                (Some((ka, ca)), Some((kb, cb))) => match ka.cmp(kb) {
+                    Ordering::Less => a.next().cloned(),
+                    Ordering::Greater => b.next().cloned(),
+                    Ordering::Equal => {
+                        let chunk = (*ka, ca.or(cb));
+                        a.next();
+                        b.next();
+                        Some(chunk)
+                    }
+                },
+                (Some(_), None) => a.next().cloned(),
+                (None, Some(_)) => b.next().cloned(),
+                (None, None) => break,
+            };
+            chunks.extend(next);
+        }
+        RowSet { chunks }
+    }
+
+    fn find(&self, key: usize) -> Result<usize, usize> {
+        self.chunks.binary_search_by_key(&key, |(k, _)| *k)
+    }
+}
+
+#[inline]
+fn low(row: usize) -> u16 {
+    (row & CHUNK_MASK) as u16
+}
+
+impl Chunk {
+    fn len(&self) -> usize {
+        match self {
+            Chunk::Array(rows) => rows.len(),
+            Chunk::Block(_, count) => *count,
+        }
+    }
+
+    fn contains(&self, lo: u16) -> bool {
+        match self {
+            Chunk::Array(rows) => rows.binary_search(&lo).is_ok(),
+            Chunk::Block(words, _) => words[lo as usize / 64] >> (lo % 64) & 1 == 1,
+        }
+    }
+
+    fn insert(&mut self, lo: u16) -> bool {
+        match self {
+            Chunk::Array(rows) => {
+                let Err(p) = rows.binary_search(&lo) else {
+                    return false;
+                };
+                rows.insert(p, lo);
+                if rows.len() > ARRAY_MAX {
+                    let block = Chunk::block(rows);
+                    *self = block;
+                }
+                true
+            }
+            Chunk::Block(words, count) => {
+                let (w, bit) = (lo as usize / 64, 1u64 << (lo % 64));
+                let added = words[w] & bit == 0;
+                words[w] |= bit;
+                *count += added as usize;
+                added
+            }
+        }
+    }
+
+    fn remove(&mut self, lo: u16) -> bool {
+        match self {
+            Chunk::Array(rows) => {
+                let Ok(p) = rows.binary_search(&lo) else {
+                    return false;
+                };
+                rows.remove(p);
+                true
+            }
+            Chunk::Block(words, count) => {
+                let (w, bit) = (lo as usize / 64, 1u64 << (lo % 64));
+                let removed = words[w] & bit != 0;
+                words[w] &= !bit;
+                *count -= removed as usize;
+                if *count <= ARRAY_MAX {
+                    let rows = self.iter().collect();
+                    *self = Chunk::Array(rows);
+                }
+                removed
+            }
+        }
+    }
+
+    fn and(&self, other: &Chunk) -> Chunk {
+        match (self, other) {
+            (Chunk::Block(a, _), Chunk::Block(b, _)) => {
+                let mut words = Box::new([0u64; BLOCK_WORDS]);
+                for (w, (a, b)) in words.iter_mut().zip(a.iter().zip(b.iter())) {
+                    *w = a & b;
+                }
+                Chunk::from_words(words)
+            }
+            (Chunk::Array(rows), other) | (other, Chunk::Array(rows)) => Chunk::Array(
+                rows.iter()
+                    .copied()
+                    .filter(|&lo| other.contains(lo))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn or(&self, other: &Chunk) -> Chunk {
+        match (self, other) {
+            (Chunk::Array(a), Chunk::Array(b)) => {
+                let mut rows = Vec::with_capacity(a.len() + b.len());
+                let (mut i, mut j) = (0, 0);
+                while i < a.len() && j < b.len() {
+                    match a[i].cmp(&b[j]) {
+                        Ordering::Less => {
+                            rows.push(a[i]);
+                            i += 1;
+                        }
+                        Ordering::Greater => {
+                            rows.push(b[j]);
+                            j += 1;
+                        }
+                        Ordering::Equal => {
+                            rows.push(a[i]);
+                            i += 1;
+                            j += 1;
+                        }
+                    }
+                }
+                rows.extend_from_slice(&a[i..]);
+                rows.extend_from_slice(&b[j..]);
+                if rows.len() > ARRAY_MAX {
+                    Chunk::block(&rows)
+                } else {
+                    Chunk::Array(rows)
+                }
+            }
+            (Chunk::Block(words, _), other) | (other, Chunk::Block(words, _)) => {
+                let mut words = words.clone();
+                match other {
+                    Chunk::Array(rows) => {
+                        for &lo in rows {
+                            words[lo as usize / 64] |= 1u64 << (lo % 64);
+                        }
+                    }
+                    Chunk::Block(b, _) => {
+                        for (w, b) in words.iter_mut().zip(b.iter()) {
+                            *w |= b;
+                        }
+                    }
+                }
+                Chunk::from_words(words)
+            }
+        }
+    }
+
+    fn iter(&self) -> ChunkIter<'_> {
+        match self {
+            Chunk::Array(rows) => ChunkIter::Array(rows.iter()),
+            Chunk::Block(words, _) => ChunkIter::Block {
+                words: &words[..],
+                w: 0,
+                bits: words[0],
+            },
+        }
+    }
+
+    fn block(rows: &[u16]) -> Chunk {
+        let mut words = Box::new([0u64; BLOCK_WORDS]);
+        for &lo in rows {
+            words[lo as usize / 64] |= 1u64 << (lo % 64);
+        }
+        Chunk::Block(words, rows.len())
+    }
+
+    /// Canonical chunk for the bits of `words`
+    fn from_words(words: Box<[u64; BLOCK_WORDS]>) -> Chunk {
+        let count = words.iter().map(|w| w.count_ones() as usize).sum();
+        let block = Chunk::Block(words, count);
+        if count <= ARRAY_MAX {
+            Chunk::Array(block.iter().collect())
+        } else {
+            block
+        }
+    }
+}
+
+/// Low bits of the rows of a chunk, ascending
+enum ChunkIter<'a> {
+    Array(std::slice::Iter<'a, u16>),
+    Block {
+        words: &'a [u64],
+        w: usize,
+        bits: u64,
+    },
+}
+
+impl Iterator for ChunkIter<'_> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        match self {
+            ChunkIter::Array(rows) => rows.next().copied(),
+            ChunkIter::Block { words, w, bits } => {
+                while *bits == 0 {
+                    *w += 1;
+                    *bits = *words.get(*w)?;
+                }
+                let lo = *w * 64 + bits.trailing_zeros() as usize;
+                *bits &= *bits - 1;
+                Some(lo as u16)
+            }
+        }
+    }
+}
+
+impl BitAnd for &RowSet {
+    type Output = RowSet;
+
+    fn bitand(self, other: &RowSet) -> RowSet {
+        self.and(other)
+    }
+}
+
+impl BitOr for &RowSet {
+    type Output = RowSet;
+
+    fn bitor(self, other: &RowSet) -> RowSet {
+        self.or(other)
+    }
+}
+
+impl FromIterator<usize> for RowSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = RowSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<usize> for RowSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for row in iter {
+            self.insert(row);
+        }
+    }
+}
+
+impl fmt::Debug for RowSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    const CHUNK: usize = 1 << CHUNK_SHIFT;
+
+    /// Chunk number and whether it is stored as a bit block, per chunk
+    fn kinds(set: &RowSet) -> Vec<(usize, bool)> {
+        set.chunks
+            .iter()
+            .map(|(key, chunk)| (*key, matches!(chunk, Chunk::Block(..))))
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_promotion_and_demotion() {
+        let mut set: RowSet = (0..ARRAY_MAX).map(|i| i * 2).collect();
+        assert_eq!(kinds(&set), vec![(0, false)]);
+
+        // One row past `ARRAY_MAX` switches to a block, and dropping back to it switches back
+        assert!(set.insert(1));
+        assert_eq!(kinds(&set), vec![(0, true)]);
+        assert_eq!(set.len(), ARRAY_MAX + 1);
+        assert!(set.contains(1) && set.contains(2 * (ARRAY_MAX - 1)));
+        assert!(!set.insert(1));
+        assert!(!set.remove(3));
+        assert_eq!(kinds(&set), vec![(0, true)]);
+
+        assert!(set.remove(0));
+        assert_eq!(kinds(&set), vec![(0, false)]);
+        assert_eq!(set.len(), ARRAY_MAX);
+        assert!(!set.contains(0) && set.contains(1));
+
+        // The canonical form makes sets equal however they were built
+        let expected: RowSet = (1..2).chain((1..ARRAY_MAX).map(|i| i * 2)).collect();
+        assert_eq!(set, expected);
+        assert!(set.iter().eq(expected.iter()));
+    }
+
+    #[test]
+    fn test_and_or_across_chunk_kinds() {
+        // Chunk 0: block & array; chunk 1: array & block; chunk 2 only in `b`
+        let a_rows: BTreeSet<usize> = (0..10_000)
+            .step_by(2)
+            .chain((CHUNK..CHUNK + 900).step_by(3))
+            .collect();
+        let b_rows: BTreeSet<usize> = (0..600)
+            .step_by(3)
+            .chain(CHUNK..CHUNK + 5_000)
+            .chain([2 * CHUNK + 7])
+            .collect();
+        let (a, b): (RowSet, RowSet) = (
+            a_rows.iter().copied().collect(),
+            b_rows.iter().copied().collect(),
+        );
+        assert_eq!(kinds(&a), vec![(0, true), (1, false)]);
+        assert_eq!(kinds(&b), vec![(0, false), (1, true), (2, false)]);
+
+        for (x, y) in [(&a, &b), (&b, &a)] {
+            let and = x & y;
+            assert!(and.iter().eq(a_rows.intersection(&b_rows).copied()));
+            assert_eq!(kinds(&and), vec![(0, false), (1, false)]);
+
+            let or = x | y;
+            assert!(or.iter().eq(a_rows.union(&b_rows).copied()));
+            assert_eq!(kinds(&or), vec![(0, true), (1, true), (2, false)]);
+        }
+
+        // Two blocks intersect to an array, or to nothing; two arrays unite to a block
+        let evens: RowSet = (0..20_000).step_by(2).collect();
+        let low: RowSet = (0..ARRAY_MAX + 10).collect();
+        assert_eq!(kinds(&(&evens & &low)), vec![(0, false)]);
+        let odds: RowSet = (1..20_000).step_by(2).collect();
+        assert!((&evens & &odds).is_empty());
+        let (first, second): (RowSet, RowSet) = ((0..3_000).collect(), (3_000..6_000).collect());
+        let both = &first | &second;
+        assert_eq!(kinds(&both), vec![(0, true)]);
+        assert_eq!(both, (0..6_000).collect());
+    }
+
+    #[test]
+    fn test_remove_last_in_chunk() {
+        let mut set: RowSet = [5, CHUNK + 1, 3 * CHUNK].into_iter().collect();
+        assert_eq!(kinds(&set), vec![(0, false), (1, false), (3, false)]);
+
+        // An emptied chunk is dropped, so it is neither iterated nor compared
+        assert!(set.remove(CHUNK + 1));
+        assert!(!set.remove(CHUNK + 1));
+        assert_eq!(kinds(&set), vec![(0, false), (3, false)]);
+        assert_eq!(set, [5, 3 * CHUNK].into_iter().collect());
+        assert!(!set.contains(CHUNK + 1));
+
+        assert!(set.remove(3 * CHUNK));
+        assert!(set.remove(5));
+        assert!(set.is_empty());
+        assert_eq!(set, RowSet::new());
+        assert!(set.insert(CHUNK + 1));
+        assert_eq!(kinds(&set), vec![(1, false)]);
+    }
+}
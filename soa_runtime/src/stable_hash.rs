@@ -0,0 +1,232 @@
+//! Shard routing of `#[derive(SoAStore)]`: the default key hasher and jump consistent hashing.
+//!
+//! `std`'s `DefaultHasher` may change between Rust releases, which would move keys to other
+//! shards. [`StableHasher`] is a fixed algorithm and part of the store's contract: together with
+//! [`StableHash`], which spells out the bytes each key type feeds it, a key hashes the same on
+//! every toolchain and platform. `std`'s `Hash` impls make no such promise (e.g. for `str` and
+//! slices), so the default routing does not go through them.
+
+use std::hash::Hasher;
+use std::rc::Rc;
+use std::sync::Arc;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a over the bytes written, finished with the SplitMix64 mixer so that consecutive keys
+/// spread evenly under `% shards`.
+///
+/// Integers are written as little-endian bytes, and `usize` / `isize` as 64-bit values, so the
+/// result does not depend on the target's endianness or pointer width. Feed it keys through
+/// [`StableHash`] for a hash that is stable for non-integer keys too.
+#[derive(Clone, Copy, Debug)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(FNV_OFFSET)
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        let mut z = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write(&[n]);
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i8(&mut self, n: i8) {
+        self.write_u8(n as u8);
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.write_u16(n as u16);
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u32(n as u32);
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i128(&mut self, n: i128) {
+        self.write_u128(n as u128);
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.write_i64(n as i64);
+    }
+}
+
+/// Jump consistent hash (Lamping & Veach): the bucket in `0..buckets` for a key hash.
+///
+/// Growing from `n` to `m` buckets moves only the `1 - n / m` share of keys that land in the
+/// new buckets (half of them when doubling), and shrinking moves only keys of the removed
+/// buckets. Panics if `buckets` is zero.
+pub fn jump_hash(mut hash: u64, buckets: usize) -> usize {
+    assert!(buckets > 0, "jump_hash needs at least one bucket");
+    let (mut b, mut j) = (-1i64, 0i64);
+    while j < buckets as i64 {
+        b = j;
+        hash = hash.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((hash >> 33) + 1) as f64)) as i64;
+    }
+    b as usize
+}
+
+/// Key types with a fixed byte encoding for shard routing.
+///
+/// Unlike `std::hash::Hash`, every impl writes explicit bytes: integers as little-endian
+/// (`usize` / `isize` widened to 64 bits), `bool` as one byte, `char` as its `u32`, strings and
+/// slices as their length as a `u64` followed by their elements, `Option` as a `0` / `1` tag
+/// followed by the value, and tuples and arrays as their elements in order. Implement it for
+/// your own key types by hashing their fields in a fixed order.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` has no stable encoding for shard routing",
+    label = "the default `StableHasher` routing needs `{Self}: StableHash`",
+    note = "implement `soa_runtime::StableHash` for `{Self}`, or route with `std::hash::Hash` \
+            through `#[soa_store(hasher = \"...\")]`"
+)]
+pub trait StableHash {
+    fn stable_hash<H: Hasher>(&self, state: &mut H);
+}
+
+macro_rules! stable_hash_le_bytes {
+    ($($ty:ty => $as:ty),* $(,)?) => {$(
+        impl StableHash for $ty {
+            fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                state.write(&(*self as $as).to_le_bytes());
+            }
+        }
+    )*};
+}
+
+stable_hash_le_bytes! {
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => u64,
+    i8 => i8, i16 => i16, i32 => i32, i64 => i64, i128 => i128, isize => i64,
+    bool => u8, char => u32,
+}
+
+impl StableHash for str {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().stable_hash(state);
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().stable_hash(state);
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (self.len() as u64).stable_hash(state);
+        for item in self {
+            item.stable_hash(state);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().stable_hash(state);
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        for item in self {
+            item.stable_hash(state);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for Option<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            None => 0u8.stable_hash(state),
+            Some(value) => {
+                1u8.stable_hash(state);
+                value.stable_hash(state);
+            }
+        }
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).stable_hash(state);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Box<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).stable_hash(state);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Rc<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).stable_hash(state);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Arc<T> {
+    fn stable_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).stable_hash(state);
+    }
+}
+
+macro_rules! stable_hash_tuple {
+    ($($name:ident)*) => {
+        impl<$($name: StableHash),*> StableHash for ($($name,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn stable_hash<H: Hasher>(&self, state: &mut H) {
+                let ($($name,)*) = self;
+                $($name.stable_hash(state);)*
+            }
+        }
+    };
+}
+
+stable_hash_tuple!();
+stable_hash_tuple!(A);
+stable_hash_tuple!(A B);
+stable_hash_tuple!(A B C);
+stable_hash_tuple!(A B C D);
+stable_hash_tuple!(A B C D E);
+stable_hash_tuple!(A B C D E F);
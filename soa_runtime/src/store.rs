@@ -0,0 +1,37 @@
+//! Types shared by the stores `#[derive(SoAStore)]` generates.
+
+use std::error::Error;
+use std::fmt;
+
+/// Returned by a store's `add` when a row with the same key already exists.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateKeyError<K> {
+    /// The rejected key
+    pub key: K,
+    /// Row currently holding `key`
+    pub row: usize,
+}
+
+impl<K: fmt::Debug> fmt::Display for DuplicateKeyError<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "duplicate key {:?} (already at row {})",
+            self.key, self.row
+        )
+    }
+}
+
+impl<K: fmt::Debug> Error for DuplicateKeyError<K> {}
+
+/// Largest shard size relative to the mean: `1.0` for balanced (or empty) shards, up to
+/// `sizes.len()` when every row sits in one shard.
+pub fn shard_skew(sizes: &[usize]) -> f64 {
+    let total: usize = sizes.iter().sum();
+    let max = sizes.iter().copied().max().unwrap_or(0);
+    if total == 0 {
+        1.0
+    } else {
+        (max * sizes.len()) as f64 / total as f64
+    }
+}
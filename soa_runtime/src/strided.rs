@@ -0,0 +1,104 @@
+//! Read-only access to one field across a slice of records.
+//!
+//! Fields listed in `#[soa(pack(...))]` are interleaved into one record per row, so there is
+//! no plain slice to return from a `*_raw_array` accessor. They get a `*_strided` accessor
+//! returning a [`Strided`] instead, which indexes, slices and iterates the same way.
+
+use std::fmt;
+use std::iter::Map;
+use std::ops::{Index, Range};
+use std::slice;
+
+/// Field `T` of every record in a `&[R]`, read with a stride of `size_of::<R>()` bytes.
+pub struct Strided<'a, R, T: 'a> {
+    rows: &'a [R],
+    field: fn(&R) -> &T,
+}
+
+/// Iterator over the field values of a [`Strided`]
+pub type StridedIter<'a, R, T> = Map<slice::Iter<'a, R>, fn(&'a R) -> &'a T>;
+
+impl<'a, R, T> Strided<'a, R, T> {
+    pub fn new(rows: &'a [R], field: fn(&R) -> &T) -> Self {
+        Self { rows, field }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Value at row `i`, or `None` if `i` is out of bounds
+    #[inline]
+    pub fn get(&self, i: usize) -> Option<&'a T> {
+        self.rows.get(i).map(self.field)
+    }
+
+    /// Rows in `range`; panics like slice indexing if it is out of bounds
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        Self {
+            rows: &self.rows[range],
+            field: self.field,
+        }
+    }
+
+    /// The records backing this view
+    pub fn records(&self) -> &'a [R] {
+        self.rows
+    }
+
+    pub fn iter(&self) -> StridedIter<'a, R, T> {
+        self.rows.iter().map(self.field as fn(&'a R) -> &'a T)
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<R, T> Clone for Strided<'_, R, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R, T> Copy for Strided<'_, R, T> {}
+
+impl<R, T> Index<usize> for Strided<'_, R, T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, i: usize) -> &T {
+        (self.field)(&self.rows[i])
+    }
+}
+
+impl<'a, R, T> IntoIterator for Strided<'a, R, T> {
+    type Item = &'a T;
+    type IntoIter = StridedIter<'a, R, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, R, T> IntoIterator for &Strided<'a, R, T> {
+    type Item = &'a T;
+    type IntoIter = StridedIter<'a, R, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<R, T: fmt::Debug> fmt::Debug for Strided<'_, R, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,276 @@
+//! Column storage for variable-length `String` and `Vec<u8>` fields.
+//!
+//! Values are concatenated into one contiguous buffer and row `i` spans
+//! `values[offsets[i]..offsets[i + 1]]`, the same layout Arrow uses for `LargeBinary` /
+//! `LargeUtf8`. Both buffers sit behind an `Arc`: the persistence layer shares them with
+//! Arrow without copying, and the column copies a buffer before writing to it while Arrow
+//! still holds it.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::Rows;
+
+/// Column of byte blobs stored as a contiguous values buffer plus `i64` offsets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryColumn {
+    offsets: Arc<Vec<i64>>,
+    values: Arc<Vec<u8>>,
+}
+
+impl Default for BinaryColumn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BinaryColumn {
+    pub fn new() -> Self {
+        Self {
+            offsets: Arc::new(vec![0]),
+            values: Arc::default(),
+        }
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        let mut offsets = Vec::with_capacity(cap + 1);
+        offsets.push(0);
+        Self {
+            offsets: Arc::new(offsets),
+            values: Arc::default(),
+        }
+    }
+
+    /// Build a column from Arrow-style offsets and values.
+    ///
+    /// Panics unless `offsets` starts at 0, never decreases and ends at `values.len()`.
+    pub fn from_parts(offsets: Vec<i64>, values: Vec<u8>) -> Self {
+        assert_eq!(offsets.first(), Some(&0), "offsets must start at 0");
+        assert!(
+            offsets.windows(2).all(|w| w[0] <= w[1]),
+            "offsets must be non-decreasing"
+        );
+        assert_eq!(
+            offsets.last().map(|&o| o as usize),
+            Some(values.len()),
+            "last offset must equal the values length"
+        );
+        Self {
+            offsets: Arc::new(offsets),
+            values: Arc::new(values),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Reserve room for at least `additional` more rows; the values buffer grows on demand
+    pub fn reserve(&mut self, additional: usize) {
+        Arc::make_mut(&mut self.offsets).reserve(additional);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes of row `i`; panics if `i` is out of bounds
+    #[inline]
+    pub fn get(&self, i: usize) -> &[u8] {
+        &self.values[self.range(i)]
+    }
+
+    pub fn push(&mut self, value: impl AsRef<[u8]>) {
+        let values = Arc::make_mut(&mut self.values);
+        values.extend_from_slice(value.as_ref());
+        Arc::make_mut(&mut self.offsets).push(offset(values.len()));
+    }
+
+    /// Replace row `i`; panics if `i` is out of bounds.
+    ///
+    /// Shifts every later row when the length changes, so this is O(total bytes after `i`).
+    pub fn set(&mut self, i: usize, value: impl AsRef<[u8]>) {
+        let value = value.as_ref();
+        let range = self.range(i);
+        let delta = offset(value.len()) - offset(range.len());
+        Arc::make_mut(&mut self.values).splice(range, value.iter().copied());
+        if delta != 0 {
+            for offset in &mut Arc::make_mut(&mut self.offsets)[i + 1..] {
+                *offset += delta;
+            }
+        }
+    }
+
+    /// Keep only the first `len` rows
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            Arc::make_mut(&mut self.values).truncate(self.offsets[len] as usize);
+            Arc::make_mut(&mut self.offsets).truncate(len + 1);
+        }
+    }
+
+    /// Move every row of `other` to the end of this column, leaving `other` empty
+    pub fn append(&mut self, other: &mut Self) {
+        let base = offset(self.values.len());
+        Arc::make_mut(&mut self.values).extend_from_slice(&other.values);
+        Arc::make_mut(&mut self.offsets).extend(other.offsets[1..].iter().map(|o| o + base));
+        *other = Self::new();
+    }
+
+    /// Remove the rows in `range`, shifting later rows down
+    pub(crate) fn remove_rows(&mut self, range: Range<usize>) {
+        let bytes = self.offsets[range.start] as usize..self.offsets[range.end] as usize;
+        let removed = offset(bytes.len());
+        Arc::make_mut(&mut self.values).drain(bytes);
+        let offsets = Arc::make_mut(&mut self.offsets);
+        offsets.drain(range.start + 1..range.end + 1);
+        for offset in &mut offsets[range.start + 1..] {
+            *offset -= removed;
+        }
+    }
+
+    /// Offsets buffer of `len() + 1` entries
+    pub fn offsets(&self) -> &[i64] {
+        &self.offsets
+    }
+
+    /// Concatenated bytes of every row
+    pub fn values(&self) -> &[u8] {
+        &self.values
+    }
+
+    /// Shared handles to the offsets and values buffers. They never change: the column
+    /// copies a buffer before its next write while a handle is alive.
+    pub fn shared_buffers(&self) -> (Arc<Vec<i64>>, Arc<Vec<u8>>) {
+        (self.offsets.clone(), self.values.clone())
+    }
+
+    pub fn iter(&self) -> Rows<'_, BinaryColumn> {
+        Rows::new(self, self.len())
+    }
+
+    #[inline]
+    fn range(&self, i: usize) -> Range<usize> {
+        self.offsets[i] as usize..self.offsets[i + 1] as usize
+    }
+}
+
+/// Offset of a byte position; lossless, as no allocation exceeds `isize::MAX` bytes
+#[inline]
+fn offset(len: usize) -> i64 {
+    len as i64
+}
+
+impl<B: AsRef<[u8]>> FromIterator<B> for BinaryColumn {
+    fn from_iter<I: IntoIterator<Item = B>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut column = BinaryColumn::with_capacity(iter.size_hint().0);
+        for value in iter {
+            column.push(value);
+        }
+        column
+    }
+}
+
+/// Column of UTF-8 strings stored as a contiguous values buffer plus `i64` offsets.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StringColumn {
+    bytes: BinaryColumn,
+}
+
+impl StringColumn {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            bytes: BinaryColumn::with_capacity(cap),
+        }
+    }
+
+    /// Build a column from Arrow-style offsets and values.
+    ///
+    /// Panics if the offsets are invalid or any row is not valid UTF-8.
+    pub fn from_parts(offsets: Vec<i64>, values: Vec<u8>) -> Self {
+        let bytes = BinaryColumn::from_parts(offsets, values);
+        assert!(
+            bytes.iter().all(|row| std::str::from_utf8(row).is_ok()),
+            "StringColumn rows must be valid UTF-8"
+        );
+        Self { bytes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Reserve room for at least `additional` more rows; the values buffer grows on demand
+    pub fn reserve(&mut self, additional: usize) {
+        self.bytes.reserve(additional);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// String at row `i`; panics if `i` is out of bounds
+    #[inline]
+    pub fn get(&self, i: usize) -> &str {
+        // SAFETY: rows are only ever written from `&str` or validated in `from_parts`
+        unsafe { std::str::from_utf8_unchecked(self.bytes.get(i)) }
+    }
+
+    pub fn push(&mut self, value: impl AsRef<str>) {
+        self.bytes.push(value.as_ref());
+    }
+
+    /// Replace row `i`; panics if `i` is out of bounds (see [`BinaryColumn::set`])
+    pub fn set(&mut self, i: usize, value: impl AsRef<str>) {
+        self.bytes.set(i, value.as_ref());
+    }
+
+    /// Keep only the first `len` rows
+    pub fn truncate(&mut self, len: usize) {
+        self.bytes.truncate(len);
+    }
+
+    /// Move every row of `other` to the end of this column, leaving `other` empty
+    pub fn append(&mut self, other: &mut Self) {
+        self.bytes.append(&mut other.bytes);
+    }
+
+    pub(crate) fn remove_rows(&mut self, range: Range<usize>) {
+        self.bytes.remove_rows(range);
+    }
+
+    /// Offsets buffer of `len() + 1` entries
+    pub fn offsets(&self) -> &[i64] {
+        self.bytes.offsets()
+    }
+
+    /// Concatenated UTF-8 bytes of every row
+    pub fn values(&self) -> &[u8] {
+        self.bytes.values()
+    }
+
+    /// Shared handles to the offsets and values buffers (see [`BinaryColumn::shared_buffers`])
+    pub fn shared_buffers(&self) -> (Arc<Vec<i64>>, Arc<Vec<u8>>) {
+        self.bytes.shared_buffers()
+    }
+
+    pub fn iter(&self) -> Rows<'_, StringColumn> {
+        Rows::new(self, self.len())
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for StringColumn {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut column = StringColumn::with_capacity(iter.size_hint().0);
+        for value in iter {
+            column.push(value);
+        }
+        column
+    }
+}